@@ -1,6 +1,7 @@
 struct VertexInput {
     @location(0) pos: vec3<f32>,
     @location(1) tex_coord: vec2<f32>,
+    @location(2) color: vec4<f32>,
     @builtin(instance_index) instance: u32,
 }
 
@@ -18,6 +19,13 @@ struct Object {
 struct Globals {
     viewproj: mat4x4<f32>,
 }
+
+struct Gradient {
+    kind: u32,
+    radius: f32,
+    direction: vec2<f32>,
+    center: vec2<f32>,
+}
     
 @group(0) @binding(0)
 var<uniform> globals: Globals;
@@ -31,12 +39,40 @@ var default_sampler: sampler;
 @group(2) @binding(1)
 var fill_image: texture_2d<f32>;
 
+@group(2) @binding(2)
+var gradient_lut: texture_2d<f32>;
+
+@group(2) @binding(3)
+var<uniform> gradient: Gradient;
+
+// Returns the position along the gradient for the given texture coordinate
+fn gradient_factor(tex_coord: vec2<f32>) -> f32 {
+    switch gradient.kind {
+        // Linear
+        case 1u: {
+            // Project onto the gradient line through the center, which spans the rect corner to
+            // corner in the given direction
+            let dir = gradient.direction;
+            let extent = (abs(dir.x) + abs(dir.y)) * 0.5;
+            return dot(tex_coord - vec2<f32>(0.5), dir) / (2.0 * extent) + 0.5;
+        }
+        // Radial
+        case 2u: {
+            return length(tex_coord - gradient.center) / max(gradient.radius, 1e-4);
+        }
+        // No gradient, the lookup table is a single white texel
+        default: {
+            return 0.0;
+        }
+    }
+}
+
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     let object = objects[in.instance];
     out.pos = globals.viewproj * object.world_matrix * vec4<f32>(in.pos, 1.0);
-    out.color = object.color;
+    out.color = object.color * in.color;
     out.tex_coord = in.tex_coord;
 
     return out;
@@ -44,5 +80,8 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color * textureSample(fill_image, default_sampler, in.tex_coord);
+    let t = clamp(gradient_factor(in.tex_coord), 0.0, 1.0);
+    let gradient_color = textureSample(gradient_lut, default_sampler, vec2<f32>(t, 0.5));
+
+    return in.color * textureSample(fill_image, default_sampler, in.tex_coord) * gradient_color;
 }
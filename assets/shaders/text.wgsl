@@ -1,6 +1,7 @@
 struct VertexInput {
     @location(0) pos: vec3<f32>,
     @location(1) tex_coord: vec2<f32>,
+    @location(2) color: vec4<f32>,
     @builtin(instance_index) instance: u32,
 }
 
@@ -36,7 +37,7 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     let object = objects[in.instance];
     out.pos = globals.viewproj * object.world_matrix * vec4<f32>(in.pos, 1.0);
-    out.color = object.color;
+    out.color = object.color * in.color;
     out.tex_coord = in.tex_coord;
 
     return out;
@@ -44,6 +45,8 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let coverage = textureSample(font_atlas, default_sampler, in.tex_coord).r;
+    // Texture coordinates are given in pixels
+    let uv = in.tex_coord / vec2<f32>(textureDimensions(font_atlas));
+    let coverage = textureSample(font_atlas, default_sampler, uv).r;
     return in.color * coverage;
 }
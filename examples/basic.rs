@@ -1,26 +1,27 @@
-use anyhow::Context;
 use flax::name;
 use futures::StreamExt;
 use glam::{vec2, Vec2};
-use image::DynamicImage;
 use palette::{named::WHITE, Hsla, IntoColor, Srgba};
 use std::{path::PathBuf, time::Duration};
 use tracing_subscriber::EnvFilter;
 use violet::{
-    assets::{fs::BytesFromFile, AssetKey},
+    assets::{
+        fs::{BytesFromFile, DirSource},
+        image::ImageFromFile,
+        AssetCache,
+    },
     components::{
         self, color, filled_rect, font_size, layout, local_position, margin, padding, rect,
-        screen_position, size, text, Edges,
+        rich_text, screen_position, selectable, size, text, Edges,
     },
+    font::{FontFamilyDesc, FontFromFile, FontStyle, FontWeight},
     input::{on_focus, on_mouse_input},
     layout::{CrossAlign, Direction, Layout},
     shapes::FilledRect,
+    text::{RichText, TextSpan},
     time::interval,
     unit::Unit,
-    wgpu::{
-        components::{font_from_file, model_matrix},
-        font::FontFromFile,
-    },
+    wgpu::components::{font_family, font_from_file, model_matrix},
     App, Frame, Scope, StreamEffect, Widget, WidgetCollection,
 };
 use winit::event::ElementState;
@@ -125,6 +126,7 @@ impl Widget for Rectangle {
                 FilledRect {
                     color: self.color,
                     fill_image: None,
+                    gradient: None,
                 },
             )
             .set(color(), self.color)
@@ -154,6 +156,7 @@ impl Widget for Button {
                 FilledRect {
                     color: WHITE.into_format().into_color(),
                     fill_image: None,
+                    gradient: None,
                 },
             )
             .set(color(), self.normal_color)
@@ -181,42 +184,35 @@ impl Widget for Button {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
-struct ImageFromPath {
-    path: PathBuf,
-}
-
-impl AssetKey for ImageFromPath {
-    type Output = DynamicImage;
-
-    fn load(&self, _: &violet::assets::AssetCache) -> Self::Output {
-        image::open(&self.path)
-            .context("Failed to load image")
-            .unwrap()
-    }
-}
-
 struct Image<P> {
     path: P,
 }
 
 impl<P: Into<PathBuf>> Widget for Image<P> {
     fn mount(self, scope: &mut Scope) {
-        let image = scope.assets_mut().load(&ImageFromPath {
-            path: self.path.into(),
-        });
+        let image = scope
+            .assets_mut()
+            .load_async(&ImageFromFile::new(self.path));
+
+        // The image is displayed once it has loaded in the background
+        scope.spawn(image.on_loaded(|scope: &mut Scope, image| match image {
+            Ok(image) => {
+                scope.set(
+                    filled_rect(),
+                    FilledRect {
+                        color: Srgba::new(1.0, 1.0, 1.0, 1.0),
+                        fill_image: Some(image),
+                        gradient: None,
+                    },
+                );
+            }
+            Err(err) => tracing::error!("{err:?}"),
+        }));
 
         scope
             .set(name(), "Image".into())
             .set_default(screen_position())
             .set_default(local_position())
-            .set(
-                filled_rect(),
-                FilledRect {
-                    color: Srgba::new(1.0, 1.0, 1.0, 1.0),
-                    fill_image: Some(image),
-                },
-            )
             .set_default(model_matrix())
             .set_default(rect());
     }
@@ -310,6 +306,7 @@ impl<W: WidgetCollection> Widget for List<W> {
                     // color: Hsla::new(190.0, 0.048, 0.143, 1.0).into_color(),
                     color: bg,
                     fill_image: None,
+                    gradient: None,
                 }),
             )
             .set(layout(), self.layout)
@@ -326,19 +323,51 @@ impl<W: WidgetCollection> Widget for List<W> {
 
 struct HelloWorld {}
 
+/// The bundled static Inter faces
+fn inter() -> FontFamilyDesc {
+    [
+        (FontWeight::THIN, "Thin"),
+        (FontWeight::EXTRA_LIGHT, "ExtraLight"),
+        (FontWeight::LIGHT, "Light"),
+        (FontWeight::REGULAR, "Regular"),
+        (FontWeight::MEDIUM, "Medium"),
+        (FontWeight::SEMI_BOLD, "SemiBold"),
+        (FontWeight::BOLD, "Bold"),
+        (FontWeight::EXTRA_BOLD, "ExtraBold"),
+        (FontWeight::BLACK, "Black"),
+    ]
+    .into_iter()
+    .fold(FontFamilyDesc::new("Inter"), |family, (weight, name)| {
+        family.with_face(
+            weight,
+            FontStyle::Normal,
+            FontFromFile {
+                path: BytesFromFile(format!("assets/fonts/Inter/static/Inter-{name}.ttf").into()),
+            },
+        )
+    })
+}
+
 impl Widget for HelloWorld {
     fn mount(self, scope: &mut Scope<'_>) {
-        let font = FontFromFile {
-            path: BytesFromFile("assets/fonts/Inter/static/Inter-Bold.ttf".into()),
-        };
-
         scope
             .set(name(), "Inter Font".into())
             .set(font_size(), 24.0)
             .set_default(screen_position())
             .set_default(local_position())
-            .set(font_from_file(), font)
-            .set(text(), "Hello, World!".into())
+            .set(font_family(), inter())
+            .set(
+                rich_text(),
+                RichText::new([
+                    TextSpan::new("Hello, "),
+                    TextSpan::new("World")
+                        .with_weight(FontWeight::BOLD)
+                        .with_color(Hsla::new(270.0, 0.5, 0.7, 1.0).into_color())
+                        .with_underline(),
+                    TextSpan::new("!").with_font_size(32.0),
+                ]),
+            )
+            .set_default(selectable())
             .set_default(model_matrix())
             .set_default(rect());
     }
@@ -385,7 +414,7 @@ impl Widget for MainApp {
         //         scope.attach(
         //             Positioned::new(
         //                 Sized::new(Image {
-        //                     path: "./assets/images/uv.png",
+        //                     path: "assets/images/uv.png",
         //                 })
         //                 .with_size(Unit::px(vec2(400.0, 400.0))),
         //             )
@@ -489,5 +518,12 @@ pub fn main() -> anyhow::Result<()> {
         .without_time()
         .init();
 
-    App::new().run(MainApp)
+    // Resolve assets relative to the crate rather than the working directory
+    let assets = AssetCache::new();
+    assets.fs().mount(
+        "assets",
+        DirSource::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets")),
+    );
+
+    App::new().with_assets(assets).run(MainApp)
 }
@@ -0,0 +1,478 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use flax::{Component, ComponentValue};
+use glam::{Vec2, Vec3, Vec4};
+use palette::{LinSrgba, Mix, Srgba};
+
+use crate::{
+    components::{Edges, Rect},
+    effect::Effect,
+    time::{FrameInterval, GLOBAL_FRAMES},
+    Scope,
+};
+
+/// A value which can be interpolated
+pub trait Tweenable: Clone {
+    /// Interpolates between `self` and `other`, where `t` is usually in `0..=1`
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec2::lerp(*self, *other, t)
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec3::lerp(*self, *other, t)
+    }
+}
+
+impl Tweenable for Vec4 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec4::lerp(*self, *other, t)
+    }
+}
+
+/// Interpolates in linear space
+impl Tweenable for Srgba {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let a: LinSrgba = self.into_linear();
+        let b: LinSrgba = other.into_linear();
+        Srgba::from_linear(a.mix(b, t))
+    }
+}
+
+impl Tweenable for Edges {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Edges {
+            left: self.left.lerp(&other.left, t),
+            right: self.right.lerp(&other.right, t),
+            top: self.top.lerp(&other.top, t),
+            bottom: self.bottom.lerp(&other.bottom, t),
+        }
+    }
+}
+
+impl Tweenable for Rect {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Rect {
+            min: self.min.lerp(other.min, t),
+            max: self.max.lerp(other.max, t),
+        }
+    }
+}
+
+/// Maps the linear progress of an animation to the progress of the value
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// A cubic bezier curve from `(0, 0)` to `(1, 1)` with the two control points, as in CSS
+    CubicBezier(Vec2, Vec2),
+    /// A damped spring which overshoots and settles at the target.
+    ///
+    /// `frequency` is the number of oscillations over the duration, and `damping` the damping
+    /// ratio in `0..1`, where lower values bounce more.
+    Spring {
+        frequency: f32,
+        damping: f32,
+    },
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::CubicBezier(p1, p2) => cubic_bezier(p1, p2, t),
+            Easing::Spring { frequency, damping } => {
+                if t >= 1.0 {
+                    return 1.0;
+                }
+
+                let damping = damping.clamp(0.0, 0.999);
+                let omega = std::f32::consts::TAU * frequency.max(0.01);
+                let omega_d = omega * (1.0 - damping * damping).sqrt();
+
+                let decay = (-damping * omega * t).exp();
+                1.0 - decay
+                    * ((omega_d * t).cos() + damping * omega / omega_d * (omega_d * t).sin())
+            }
+        }
+    }
+}
+
+/// Evaluates the curve at the point where its x coordinate is `x`
+fn cubic_bezier(p1: Vec2, p2: Vec2, x: f32) -> f32 {
+    let sample = |a: f32, b: f32, s: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * a + 3.0 * inv * s * s * b + s * s * s
+    };
+
+    // The x coordinate increases monotonically for control points in `0..=1`
+    let (mut lo, mut hi) = (0.0, 1.0);
+    let mut s = x;
+    for _ in 0..32 {
+        let found = sample(p1.x, p2.x, s);
+        if (found - x).abs() < 1e-5 {
+            break;
+        }
+
+        if found < x {
+            lo = s;
+        } else {
+            hi = s;
+        }
+
+        s = (lo + hi) / 2.0;
+    }
+
+    sample(p1.y, p2.y, s)
+}
+
+/// How many times an animation runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Count(u32),
+    Forever,
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Self::Count(1)
+    }
+}
+
+/// Animates an entity from its previous layout to the new one when the layout changes.
+///
+/// See [`layout_transition`](crate::components::layout_transition)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutTransition {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl LayoutTransition {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::EaseInOut,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+/// Describes how to animate a value towards a target
+#[derive(Debug, Clone)]
+pub struct Tween<T> {
+    /// Starts from the current value of the component if not set
+    pub from: Option<T>,
+    pub to: T,
+    pub duration: Duration,
+    pub delay: Duration,
+    pub easing: Easing,
+    pub repeat: Repeat,
+    /// Reverses the direction of every other repetition
+    pub yoyo: bool,
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(to: T, duration: Duration) -> Self {
+        Self {
+            from: None,
+            to,
+            duration,
+            delay: Duration::ZERO,
+            easing: Easing::default(),
+            repeat: Repeat::default(),
+            yoyo: false,
+        }
+    }
+
+    pub fn with_from(mut self, from: T) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_yoyo(mut self, yoyo: bool) -> Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    /// Returns the value `elapsed` after the start of the animation, and whether it has finished
+    pub fn sample(&self, from: &T, elapsed: Duration) -> (T, bool) {
+        let elapsed = elapsed.saturating_sub(self.delay);
+
+        let cycles = match self.repeat {
+            Repeat::Count(count) => count.max(1) as f32,
+            Repeat::Forever => f32::INFINITY,
+        };
+
+        let cycle = if self.duration.is_zero() {
+            cycles
+        } else {
+            elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        };
+
+        // Yoyo animations end at the start if the last cycle runs backwards
+        let reversed = |index: f32| self.yoyo && index % 2.0 == 1.0;
+
+        if cycle >= cycles {
+            let value = if reversed(cycles - 1.0) {
+                from.clone()
+            } else {
+                self.to.clone()
+            };
+
+            return (value, true);
+        }
+
+        let mut t = cycle.fract();
+        if reversed(cycle.floor()) {
+            t = 1.0 - t;
+        }
+
+        (from.lerp(&self.to, self.easing.apply(t)), false)
+    }
+}
+
+/// Animates a component of a widget each frame.
+///
+/// Usually created through [`Scope::animate`].
+pub struct Animation<T: ComponentValue> {
+    component: Component<T>,
+    tween: Tween<T>,
+    interval: FrameInterval,
+    start: Option<(Instant, T)>,
+}
+
+impl<T: ComponentValue + Tweenable> Animation<T> {
+    pub fn new(component: Component<T>, tween: Tween<T>) -> Self {
+        Self {
+            component,
+            tween,
+            interval: GLOBAL_FRAMES.interval(),
+            start: None,
+        }
+    }
+
+    /// Use a different source of frames, such as a [`FrameClock`](crate::time::FrameClock)
+    /// advanced manually
+    pub fn with_interval(mut self, interval: FrameInterval) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl<T> Unpin for Animation<T> where T: ComponentValue {}
+
+impl<'a, T> Effect<Scope<'a>> for Animation<T>
+where
+    T: ComponentValue + Tweenable,
+{
+    type Output = ();
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        scope: &mut Scope<'a>,
+    ) -> Poll<()> {
+        // Skip to the latest frame
+        let mut time = None;
+        while let Poll::Ready(frame) = self.interval.poll_tick(context) {
+            time = Some(frame);
+        }
+
+        let Some(time) = time else {
+            return Poll::Pending;
+        };
+
+        let this = &mut *self;
+        let component = this.component;
+        let tween = &this.tween;
+
+        let (start, from) = this.start.get_or_insert_with(|| {
+            let from = tween.from.clone().unwrap_or_else(|| {
+                scope
+                    .entity()
+                    .get(component)
+                    .map(|v| v.clone())
+                    .unwrap_or_else(|_| tween.to.clone())
+            });
+
+            (time, from)
+        });
+
+        let (value, finished) = tween.sample(from, time.saturating_duration_since(*start));
+        scope.set(component, value);
+
+        if finished {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flax::World;
+
+    use crate::{
+        assets::AssetCache, background::ThreadPool, components::opacity, executor::Executor,
+        time::FrameClock, Frame,
+    };
+
+    use super::*;
+
+    #[test]
+    fn easing() {
+        let easings = [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::CubicBezier(Vec2::new(0.25, 0.1), Vec2::new(0.25, 1.0)),
+            Easing::Spring {
+                frequency: 2.0,
+                damping: 0.5,
+            },
+        ];
+
+        for easing in easings {
+            assert!(easing.apply(0.0).abs() < 1e-4, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-4, "{easing:?}");
+        }
+
+        let linear = Easing::CubicBezier(Vec2::new(0.25, 0.25), Vec2::new(0.75, 0.75));
+        assert!((linear.apply(0.3) - 0.3).abs() < 1e-3);
+
+        // Overshoots the target
+        let spring = Easing::Spring {
+            frequency: 2.0,
+            damping: 0.3,
+        };
+        assert!((0..100).any(|i| spring.apply(i as f32 / 100.0) > 1.0));
+    }
+
+    #[test]
+    fn tween() {
+        let ms = Duration::from_millis;
+
+        let tween = Tween::new(10.0, ms(100)).with_delay(ms(50));
+        assert_eq!(tween.sample(&0.0, ms(0)), (0.0, false));
+        assert_eq!(tween.sample(&0.0, ms(100)), (5.0, false));
+        assert_eq!(tween.sample(&0.0, ms(150)), (10.0, true));
+
+        let yoyo = Tween::new(10.0, ms(100))
+            .with_repeat(Repeat::Count(2))
+            .with_yoyo(true);
+        assert_eq!(yoyo.sample(&0.0, ms(50)), (5.0, false));
+        assert_eq!(yoyo.sample(&0.0, ms(125)), (7.5, false));
+        assert_eq!(yoyo.sample(&0.0, ms(200)), (0.0, true));
+
+        let forever = Tween::new(10.0, ms(100)).with_repeat(Repeat::Forever);
+        let (value, finished) = forever.sample(&0.0, ms(1050));
+        assert!((value - 5.0).abs() < 1e-3 && !finished);
+    }
+
+    #[test]
+    fn animation() {
+        let ms = Duration::from_millis;
+
+        let mut ex = Executor::new();
+        let mut frame = Frame {
+            world: World::new(),
+            spawner: ex.spawner(),
+            assets: AssetCache::new(),
+            pool: ThreadPool::new(1),
+        };
+
+        let clock = FrameClock::new();
+        let start = clock.time();
+
+        let (id, handle) = {
+            let mut scope = Scope::new(&mut frame);
+            scope.set(opacity(), 0.2);
+
+            let animation =
+                Animation::new(opacity(), Tween::new(1.0, ms(100))).with_interval(clock.interval());
+
+            (scope.id(), scope.spawn(animation))
+        };
+
+        let assert_opacity = |frame: &Frame, expected: f32| {
+            let value = *frame.world.get(id, opacity()).unwrap();
+            assert!((value - expected).abs() < 1e-4, "{value} != {expected}");
+        };
+
+        // Waits for the first frame
+        ex.tick(&mut frame);
+        assert_opacity(&frame, 0.2);
+
+        // Starts from the current value of the component
+        clock.advance(start + ms(10));
+        ex.tick(&mut frame);
+        assert_opacity(&frame, 0.2);
+
+        clock.advance(start + ms(60));
+        ex.tick(&mut frame);
+        assert_opacity(&frame, 0.6);
+
+        // Skips to the latest frame
+        clock.advance(start + ms(70));
+        clock.advance(start + ms(85));
+        ex.tick(&mut frame);
+        assert_opacity(&frame, 0.8);
+        assert!(!handle.is_finished());
+
+        clock.advance(start + ms(200));
+        ex.tick(&mut frame);
+        assert_opacity(&frame, 1.0);
+        assert_eq!(futures::executor::block_on(handle), Ok(()));
+    }
+}
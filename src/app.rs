@@ -1,21 +1,61 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use flax::{name, Schedule, World};
+use futures::task::ArcWake;
 use glam::{vec2, Vec2};
+use parking_lot::Mutex;
 use winit::{
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoopBuilder},
+    event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy},
     window::WindowBuilder,
 };
 
 use crate::{
     assets::AssetCache,
+    background::ThreadPool,
+    clipboard::{Clipboard, MemoryClipboard},
     components::{self, local_position, rect, screen_position, Rect},
     executor::Executor,
     input::InputState,
-    systems::{layout_system, transform_system},
-    wgpu::{graphics::Gpu, systems::load_fonts_system, window_renderer::WindowRenderer},
+    systems::{layout_system, layout_transition_system, transform_system},
+    time::GLOBAL_FRAMES,
+    wgpu::{
+        graphics::Gpu,
+        shaping::TextShaper,
+        systems::{
+            load_font_families_system, load_fonts_system, load_rich_text_fonts_system,
+            reload_assets_system,
+        },
+        window_renderer::WindowRenderer,
+    },
     Frame, Widget,
 };
 
+/// How often the files of loaded assets are checked for modifications
+const ASSET_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Events sent to the event loop from other threads
+#[derive(Debug)]
+enum AppEvent {
+    /// A task is ready to be polled by the executor
+    Wake,
+}
+
+/// Wakes the event loop when the executor needs to be ticked
+struct EventLoopWaker {
+    proxy: Mutex<EventLoopProxy<AppEvent>>,
+}
+
+impl ArcWake for EventLoopWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        // Fails if the event loop has already exited
+        let _ = arc_self.proxy.lock().send_event(AppEvent::Wake);
+    }
+}
+
 pub struct Canvas<W> {
     size: Vec2,
     root: W,
@@ -39,11 +79,44 @@ impl<W: Widget> Widget for Canvas<W> {
     }
 }
 
-pub struct App {}
+pub struct App {
+    clipboard: Arc<dyn Clipboard>,
+    assets: AssetCache,
+    pool: ThreadPool,
+}
 
 impl App {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            clipboard: default_clipboard(),
+            assets: AssetCache::new(),
+            pool: ThreadPool::default(),
+        }
+    }
+
+    /// Set the clipboard used for copying selected text.
+    ///
+    /// Defaults to the clipboard of the operating system when the `clipboard` feature is enabled.
+    /// Otherwise, or if the system clipboard is unavailable, a [`MemoryClipboard`] is used, which
+    /// is not shared with other applications.
+    pub fn with_clipboard(mut self, clipboard: impl Clipboard + 'static) -> Self {
+        self.clipboard = Arc::new(clipboard);
+        self
+    }
+
+    /// Use an existing asset cache, such as one with mounted asset directories
+    pub fn with_assets(mut self, assets: AssetCache) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    /// Set the thread pool used for [`Frame::spawn_background`] and loading assets in the
+    /// background.
+    ///
+    /// Defaults to one worker per available core.
+    pub fn with_thread_pool(mut self, pool: ThreadPool) -> Self {
+        self.pool = pool;
+        self
     }
 
     pub fn run(self, root: impl Widget) -> anyhow::Result<()> {
@@ -53,19 +126,29 @@ impl App {
 
         let world = World::new();
 
+        self.assets.set_thread_pool(self.pool.clone());
+
         let mut frame = Frame {
             world,
             spawner,
-            assets: AssetCache::new(),
+            assets: self.assets.clone(),
+            pool: self.pool.clone(),
         };
 
-        let event_loop = EventLoopBuilder::new().build();
+        let event_loop = EventLoopBuilder::with_user_event().build();
+
+        // Task and timer wakeups are delivered as events, which allows the event loop to sleep
+        // until there is something to do
+        let waker = futures::task::waker(Arc::new(EventLoopWaker {
+            proxy: Mutex::new(event_loop.create_proxy()),
+        }));
+        ex.set_waker(waker.clone());
 
         let window = WindowBuilder::new().build(&event_loop)?;
         let window_size = window.inner_size();
         let window_size = vec2(window_size.width as f32, window_size.height as f32);
 
-        let mut input_state = InputState::new(Vec2::ZERO);
+        let mut input_state = InputState::new(Vec2::ZERO, self.clipboard.clone());
 
         // Mount the root widget
         let root = frame.new_root(Canvas {
@@ -78,21 +161,60 @@ impl App {
 
         let mut window_renderer = WindowRenderer::new(gpu, &mut frame, surface);
 
-        let mut schedule = Schedule::new()
-            .with_system(layout_system())
+        let schedule = Schedule::new()
+            .with_system(layout_system(Arc::new(TextShaper)))
+            .with_system(layout_transition_system(
+                (*GLOBAL_FRAMES).clone(),
+                waker.clone(),
+            ))
             .with_system(transform_system())
-            .with_system(load_fonts_system(frame.assets.clone()));
+            .with_system(load_fonts_system(frame.assets.clone()))
+            .with_system(load_font_families_system(frame.assets.clone()))
+            .with_system(load_rich_text_fonts_system(frame.assets.clone()))
+            .with_system(reload_assets_system(frame.assets.clone()));
+
+        #[cfg(feature = "svg")]
+        let schedule = schedule.with_system(crate::systems::svg_fill_system(
+            frame.assets.clone(),
+            waker.clone(),
+        ));
+
+        let mut schedule = schedule;
+
+        // Set when an effect was woken, input arrived or assets were modified, and the world needs
+        // to be updated and redrawn
+        let mut dirty = true;
+        let mut next_asset_poll = Instant::now() + ASSET_POLL_INTERVAL;
 
         event_loop.run(move |event, _, ctl| match event {
+            Event::NewEvents(_) => {
+                let now = Instant::now();
+                if now >= next_asset_poll {
+                    next_asset_poll = now + ASSET_POLL_INTERVAL;
+
+                    let modified = frame.assets.check_modified();
+                    if modified > 0 {
+                        tracing::info!(modified, "Asset files modified");
+                        dirty = true;
+                    }
+                }
+            }
+            Event::UserEvent(AppEvent::Wake) => {
+                dirty = true;
+            }
             Event::MainEventsCleared => {
-                ex.tick(&mut frame);
+                if dirty {
+                    dirty = false;
 
-                schedule.execute_seq(&mut frame.world).unwrap();
+                    ex.tick(&mut frame);
 
-                if let Err(err) = window_renderer.draw(&mut frame) {
-                    tracing::error!("Failed to draw to window: {err:?}");
-                    *ctl = ControlFlow::Exit
+                    schedule.execute_seq(&mut frame.world).unwrap();
+                    frame.assets.collect();
+
+                    window_renderer.window().request_redraw();
                 }
+
+                ctl.set_wait_until(next_asset_poll);
             }
             Event::RedrawRequested(_) => {
                 tracing::trace!("Redraw requested");
@@ -100,20 +222,34 @@ impl App {
                     tracing::error!("Failed to draw to window: {err:?}");
                     *ctl = ControlFlow::Exit
                 }
+
+                // Wakes the frame intervals, which keeps rendering while any are waiting
+                GLOBAL_FRAMES.advance(Instant::now());
             }
             Event::WindowEvent { window_id, event } => match event {
                 WindowEvent::MouseInput { state, button, .. } => {
                     input_state.on_mouse_input(&mut frame, state, button);
+                    dirty = true;
                 }
                 WindowEvent::KeyboardInput {
                     input,
                     is_synthetic,
                     ..
-                } => input_state.on_keyboard_input(&mut frame, input),
+                } => {
+                    input_state.on_keyboard_input(&mut frame, input);
+                    dirty = true;
+                }
                 WindowEvent::CursorMoved { position, .. } => {
-                    input_state.on_cursor_move(vec2(position.x as f32, position.y as f32))
+                    let pos = vec2(position.x as f32, position.y as f32);
+                    if input_state.on_cursor_move(&mut frame, pos) {
+                        dirty = true;
+                    }
+                }
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    input_state.on_modifiers_changed(modifiers)
                 }
                 WindowEvent::Resized(size) => {
+                    dirty = true;
                     frame
                         .world_mut()
                         .set(
@@ -147,3 +283,19 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(feature = "clipboard")]
+fn default_clipboard() -> Arc<dyn Clipboard> {
+    match crate::clipboard::SystemClipboard::new() {
+        Ok(clipboard) => Arc::new(clipboard),
+        Err(err) => {
+            tracing::warn!("System clipboard is unavailable, using an in-process clipboard: {err}");
+            Arc::new(MemoryClipboard::new())
+        }
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn default_clipboard() -> Arc<dyn Clipboard> {
+    Arc::new(MemoryClipboard::new())
+}
@@ -1,20 +1,62 @@
-use std::sync::Arc;
+use std::{
+    any::{type_name, Any},
+    cmp::Reverse,
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use slotmap::SlotMap;
 
 use super::{handle::WeakHandle, AssetId, Handle};
 
+/// Determines how long assets are kept after their last handle is dropped
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// Release assets as soon as they are unused
+    #[default]
+    None,
+    /// Keep the `n` most recently unused assets
+    LastUnused(usize),
+    /// Keep unused assets for a period of time
+    For(Duration),
+}
+
+/// Statistics for the assets of a single type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetStats {
+    pub type_name: &'static str,
+    /// Number of assets which are loaded, including the retained ones
+    pub alive: usize,
+    /// Number of assets only kept alive by the retention policy
+    pub retained: usize,
+    /// Estimated memory of the loaded assets in bytes
+    pub memory: usize,
+}
+
+struct Retained<V> {
+    handle: Handle<V>,
+    unused_since: Option<Instant>,
+}
+
 /// Contains the actual asset data
 ///
 /// Allows acessing an asset by its id
 pub struct AssetCell<V> {
     values: SlotMap<AssetId, WeakHandle<V>>,
+    retention: Retention,
+    /// Keeps assets alive according to the retention policy
+    retained: HashMap<AssetId, Retained<V>>,
+    memory: fn(&V) -> usize,
 }
 
 impl<V> AssetCell<V> {
     pub fn new() -> Self {
         Self {
             values: SlotMap::with_key(),
+            retention: Retention::None,
+            retained: HashMap::new(),
+            memory: |_| std::mem::size_of::<V>(),
         }
     }
 
@@ -30,7 +72,13 @@ impl<V> AssetCell<V> {
             id,
         });
 
-        Handle { value, id }
+        let handle = Handle { value, id };
+
+        if self.retention != Retention::None {
+            self.retain(handle.clone());
+        }
+
+        handle
     }
 
     pub fn prune(&mut self) {
@@ -40,6 +88,92 @@ impl<V> AssetCell<V> {
     pub(super) fn get(&self, id: AssetId) -> Option<&WeakHandle<V>> {
         self.values.get(id)
     }
+
+    pub fn set_retention(&mut self, retention: Retention) {
+        self.retention = retention;
+
+        if retention == Retention::None {
+            self.retained.clear();
+        } else {
+            // Start retaining the assets which are already loaded
+            let alive = self
+                .values
+                .values()
+                .filter_map(|v| v.upgrade())
+                .collect::<Vec<_>>();
+            alive.into_iter().for_each(|v| self.retain(v));
+        }
+    }
+
+    pub fn set_memory_estimate(&mut self, memory: fn(&V) -> usize) {
+        self.memory = memory;
+    }
+
+    fn retain(&mut self, handle: Handle<V>) {
+        self.retained.entry(handle.id).or_insert(Retained {
+            handle,
+            unused_since: None,
+        });
+    }
+
+    /// Releases the retained assets which have been unused for longer than the policy allows
+    pub fn collect(&mut self, now: Instant) {
+        let mut unused = Vec::new();
+
+        for (&id, retained) in &mut self.retained {
+            // The retained handle is the only one left
+            if Arc::strong_count(&retained.handle.value) > 1 {
+                retained.unused_since = None;
+            } else {
+                unused.push((*retained.unused_since.get_or_insert(now), id));
+            }
+        }
+
+        match self.retention {
+            Retention::None => {}
+            Retention::LastUnused(count) => {
+                if unused.len() > count {
+                    unused.sort_unstable_by_key(|&(since, _)| Reverse(since));
+
+                    for (_, id) in &unused[count..] {
+                        self.retained.remove(id);
+                    }
+                }
+            }
+            Retention::For(duration) => {
+                for (since, id) in unused {
+                    if now.duration_since(since) >= duration {
+                        self.retained.remove(&id);
+                    }
+                }
+            }
+        }
+
+        self.prune();
+    }
+
+    pub fn stats(&self) -> AssetStats {
+        let (alive, memory) = self
+            .values
+            .values()
+            .filter_map(|v| v.value.upgrade())
+            .fold((0, 0), |(count, memory), v| {
+                (count + 1, memory + (self.memory)(&v))
+            });
+
+        let retained = self
+            .retained
+            .values()
+            .filter(|v| Arc::strong_count(&v.handle.value) == 1)
+            .count();
+
+        AssetStats {
+            type_name: type_name::<V>(),
+            alive,
+            retained,
+            memory,
+        }
+    }
 }
 
 impl<V> Default for AssetCell<V> {
@@ -47,3 +181,24 @@ impl<V> Default for AssetCell<V> {
         Self::new()
     }
 }
+
+/// Type erased access to the cell of each asset type
+pub(super) trait ErasedCell: Send + Sync {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn collect(&mut self, now: Instant);
+    fn stats(&self) -> AssetStats;
+}
+
+impl<V: 'static + Send + Sync> ErasedCell for AssetCell<V> {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn collect(&mut self, now: Instant) {
+        AssetCell::collect(self, now)
+    }
+
+    fn stats(&self) -> AssetStats {
+        AssetCell::stats(self)
+    }
+}
@@ -1,9 +1,17 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
 
-use super::{AssetCache, AssetKey};
+use anyhow::Context;
+use parking_lot::RwLock;
+
+use super::{AssetCache, TryAssetKey};
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
-/// Loads bytes from a file
+/// Loads bytes from a file of the virtual filesystem of the asset cache
 pub struct BytesFromFile(pub PathBuf);
 
 impl std::ops::Deref for BytesFromFile {
@@ -14,10 +22,291 @@ impl std::ops::Deref for BytesFromFile {
     }
 }
 
-impl AssetKey for BytesFromFile {
+impl TryAssetKey for BytesFromFile {
     type Output = Vec<u8>;
 
-    fn load(&self, _: &AssetCache) -> Self::Output {
-        std::fs::read(&self.0).unwrap()
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        let fs = assets.fs();
+
+        if let Some(path) = fs.disk_path(&self.0) {
+            assets.track_file(&path);
+        }
+
+        fs.read(&self.0)
+            .with_context(|| format!("Failed to read {:?}", self.0))
+    }
+}
+
+/// Provides the files of a mount point.
+///
+/// Paths are relative to the mount point
+pub trait FileSource: Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Returns the location of the file on disk, used for detecting modifications
+    fn disk_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Serves files from a directory on disk
+#[derive(Debug, Clone)]
+pub struct DirSource {
+    root: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl FileSource for DirSource {
+    fn exists(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(path))
+    }
+
+    fn disk_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.root.join(path))
+    }
+}
+
+/// Serves files embedded in the binary.
+///
+/// Usually created using [`embed_files!`](crate::embed_files)
+#[derive(Default, Debug, Clone)]
+pub struct EmbeddedSource {
+    files: HashMap<PathBuf, &'static [u8]>,
+}
+
+impl EmbeddedSource {
+    pub fn new<'a>(files: impl IntoIterator<Item = (&'a str, &'static [u8])>) -> Self {
+        Self {
+            files: files
+                .into_iter()
+                .map(|(path, data)| (normalize(Path::new(path)), data))
+                .collect(),
+        }
+    }
+}
+
+impl FileSource for EmbeddedSource {
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .get(path)
+            .map(|v| v.to_vec())
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+/// Embeds files relative to `root` in the binary using `include_bytes!`.
+///
+/// ```ignore
+/// let source = violet::embed_files!(
+///     concat!(env!("CARGO_MANIFEST_DIR"), "/assets"),
+///     ["fonts/Inter/static/Inter-Regular.ttf", "images/uv.png"]
+/// );
+///
+/// assets.fs().mount("assets", source);
+/// ```
+#[macro_export]
+macro_rules! embed_files {
+    ($root:expr, [$($path:literal),* $(,)?]) => {
+        $crate::assets::fs::EmbeddedSource::new([
+            $(($path, include_bytes!(concat!($root, "/", $path)) as &'static [u8])),*
+        ])
+    };
+}
+
+/// Serves files stored in memory.
+///
+/// Clones share the same files, which allows adding files after mounting
+#[derive(Default, Debug, Clone)]
+pub struct MemorySource {
+    files: Arc<RwLock<HashMap<PathBuf, Arc<[u8]>>>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(self, path: impl AsRef<Path>, data: impl Into<Arc<[u8]>>) -> Self {
+        self.insert(path, data);
+        self
+    }
+
+    /// Adds or replaces a file
+    pub fn insert(&self, path: impl AsRef<Path>, data: impl Into<Arc<[u8]>>) {
+        self.files
+            .write()
+            .insert(normalize(path.as_ref()), data.into());
+    }
+
+    pub fn remove(&self, path: impl AsRef<Path>) -> Option<Arc<[u8]>> {
+        self.files.write().remove(&normalize(path.as_ref()))
     }
 }
+
+impl FileSource for MemorySource {
+    fn exists(&self, path: &Path) -> bool {
+        self.files.read().contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .read()
+            .get(path)
+            .map(|v| v.to_vec())
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+struct Mount {
+    prefix: PathBuf,
+    source: Arc<dyn FileSource>,
+}
+
+/// Resolves asset paths through a set of mount points.
+///
+/// A path is served by the mount with the longest matching prefix which contains the file. When
+/// several mounts share a prefix the most recently mounted is consulted first, which allows
+/// overriding embedded files with a directory during development.
+///
+/// Paths outside of all mount points are read from disk relative to the working directory.
+#[derive(Default)]
+pub struct VirtualFs {
+    mounts: RwLock<Vec<Mount>>,
+}
+
+impl std::fmt::Debug for VirtualFs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.mounts.read().iter().map(|v| &v.prefix))
+            .finish()
+    }
+}
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts `source` at `prefix`.
+    ///
+    /// An empty prefix mounts the source at the root
+    pub fn mount(&self, prefix: impl AsRef<Path>, source: impl FileSource + 'static) {
+        let prefix = normalize(prefix.as_ref());
+        let depth = prefix.components().count();
+
+        let mut mounts = self.mounts.write();
+        let index = mounts.partition_point(|v| v.prefix.components().count() > depth);
+
+        mounts.insert(
+            index,
+            Mount {
+                prefix,
+                source: Arc::new(source),
+            },
+        );
+    }
+
+    /// Removes all sources mounted at `prefix`
+    pub fn unmount(&self, prefix: impl AsRef<Path>) {
+        let prefix = normalize(prefix.as_ref());
+        self.mounts.write().retain(|v| v.prefix != prefix);
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        match self.find(path.as_ref()) {
+            Resolved::Mounted(..) => true,
+            Resolved::Missing => false,
+            Resolved::Unmounted(path) => path.is_file(),
+        }
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        match self.find(path.as_ref()) {
+            Resolved::Mounted(source, path) => source.read(&path),
+            Resolved::Missing => Err(io::ErrorKind::NotFound.into()),
+            Resolved::Unmounted(path) => std::fs::read(path),
+        }
+    }
+
+    /// Returns the location of the file on disk, if it is backed by one
+    pub fn disk_path(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        match self.find(path.as_ref()) {
+            Resolved::Mounted(source, path) => source.disk_path(&path),
+            Resolved::Missing => None,
+            Resolved::Unmounted(path) => Some(path),
+        }
+    }
+
+    fn find(&self, path: &Path) -> Resolved {
+        let path = normalize(path);
+
+        // Leaving the root could reach files outside of the mount points
+        if path.starts_with(Component::ParentDir) {
+            return Resolved::Missing;
+        }
+
+        let mounts = self.mounts.read();
+
+        let mut matched = false;
+        for mount in mounts.iter() {
+            let Ok(relative) = path.strip_prefix(&mount.prefix) else {
+                continue;
+            };
+
+            matched = true;
+            if mount.source.exists(relative) {
+                return Resolved::Mounted(mount.source.clone(), relative.to_path_buf());
+            }
+        }
+
+        if matched {
+            Resolved::Missing
+        } else {
+            Resolved::Unmounted(path)
+        }
+    }
+}
+
+enum Resolved {
+    Mounted(Arc<dyn FileSource>, PathBuf),
+    /// The path is inside a mount point but no source contains it, or it leaves the root
+    Missing,
+    Unmounted(PathBuf),
+}
+
+/// Removes `.` components and resolves `..` so that equivalent relative paths compare equal.
+///
+/// Leading `..` components which leave the root of a relative path are kept.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => result.push(component),
+            },
+            component => result.push(component),
+        }
+    }
+
+    result
+}
@@ -0,0 +1,213 @@
+use std::{io::Cursor, path::PathBuf};
+
+use ::image::{io::Reader, DynamicImage, ImageFormat};
+use anyhow::Context;
+
+use super::{fs::BytesFromFile, AssetCache, Handle, TryAssetKey};
+
+/// Controls how an image is decoded
+#[derive(Default, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Decode using a specific format rather than guessing it from the contents.
+    ///
+    /// Each format requires its cargo feature, `png`, `jpeg`, `webp` or `gif`.
+    pub format: Option<ImageFormat>,
+    /// Convert to RGBA8 with the color channels multiplied by alpha
+    pub premultiply: bool,
+}
+
+impl ImageOptions {
+    pub fn with_format(mut self, format: ImageFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_premultiply(mut self, premultiply: bool) -> Self {
+        self.premultiply = premultiply;
+        self
+    }
+}
+
+/// Decodes an image from memory.
+///
+/// Animated GIFs decode to their first frame.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ImageFromBytes {
+    pub bytes: Handle<Vec<u8>>,
+    pub options: ImageOptions,
+}
+
+impl ImageFromBytes {
+    pub fn new(bytes: Handle<Vec<u8>>) -> Self {
+        Self {
+            bytes,
+            options: ImageOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ImageOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl TryAssetKey for ImageFromBytes {
+    type Output = DynamicImage;
+
+    fn try_load(&self, _: &AssetCache) -> anyhow::Result<Self::Output> {
+        decode(&self.bytes, &self.options, None)
+    }
+}
+
+/// Loads an image from a file of the virtual filesystem of the asset cache.
+///
+/// Paths outside of all mount points are read from disk, see [`super::fs::VirtualFs`]. The image
+/// can be decoded in the background using [`AssetCache::load_async`], and used directly as
+/// [`FilledRect::fill_image`](crate::shapes::FilledRect::fill_image).
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ImageFromFile {
+    pub path: BytesFromFile,
+    pub options: ImageOptions,
+}
+
+impl ImageFromFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: BytesFromFile(path.into()),
+            options: ImageOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ImageOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl TryAssetKey for ImageFromFile {
+    type Output = DynamicImage;
+
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        let bytes = assets.try_load(&self.path)?;
+
+        decode(
+            &bytes,
+            &self.options,
+            ImageFormat::from_path(&*self.path).ok(),
+        )
+        .with_context(|| format!("Failed to load image {:?}", *self.path))
+    }
+}
+
+/// Loads an image directly from disk, bypassing the mount points of the virtual filesystem
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct ImageFromPath {
+    pub path: PathBuf,
+    pub options: ImageOptions,
+}
+
+impl ImageFromPath {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            options: ImageOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ImageOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl TryAssetKey for ImageFromPath {
+    type Output = DynamicImage;
+
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        assets.track_file(&self.path);
+        let bytes =
+            std::fs::read(&self.path).with_context(|| format!("Failed to read {:?}", self.path))?;
+
+        decode(
+            &bytes,
+            &self.options,
+            ImageFormat::from_path(&self.path).ok(),
+        )
+        .with_context(|| format!("Failed to load image {:?}", self.path))
+    }
+}
+
+/// Decodes the image, guessing the format from the contents unless specified.
+///
+/// `fallback` is used when the format can not be guessed, such as the format of the file extension
+fn decode(
+    bytes: &[u8],
+    options: &ImageOptions,
+    fallback: Option<ImageFormat>,
+) -> anyhow::Result<DynamicImage> {
+    let mut reader = Reader::new(Cursor::new(bytes));
+
+    if let Some(format) = options.format.or(fallback) {
+        reader.set_format(format);
+    }
+
+    if options.format.is_none() {
+        reader = reader.with_guessed_format()?;
+    }
+
+    let format = reader.format();
+    let image = reader
+        .decode()
+        .with_context(|| format!("Failed to decode image of format {format:?}"))?;
+
+    if options.premultiply {
+        Ok(premultiply(image))
+    } else {
+        Ok(image)
+    }
+}
+
+fn premultiply(image: DynamicImage) -> DynamicImage {
+    let mut image = image.into_rgba8();
+
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let mul = |v: u8| ((v as u16 * a as u16 + 127) / 255) as u8;
+        pixel.0 = [mul(r), mul(g), mul(b), a];
+    }
+
+    DynamicImage::ImageRgba8(image)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_png() {
+        let image = ::image::RgbaImage::from_pixel(2, 1, ::image::Rgba([255, 128, 0, 128]));
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        let assets = AssetCache::new();
+        let bytes = assets.insert(bytes);
+
+        let image = assets
+            .try_load(&ImageFromBytes::new(bytes.clone()))
+            .unwrap();
+        assert_eq!(image.to_rgba8().get_pixel(1, 0).0, [255, 128, 0, 128]);
+
+        let premultiplied = assets
+            .try_load(
+                &ImageFromBytes::new(bytes)
+                    .with_options(ImageOptions::default().with_premultiply(true)),
+            )
+            .unwrap();
+        assert_eq!(
+            premultiplied.to_rgba8().get_pixel(1, 0).0,
+            [128, 64, 0, 128]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fmt::{Debug, Display},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use futures::Future;
+use parking_lot::Mutex;
+
+use crate::effect::FutureEffect;
+
+use super::Handle;
+
+/// The reason an asset failed to load.
+///
+/// Cheap to clone, as the same failure is reported to every holder of the asset
+#[derive(Clone)]
+pub struct AssetError(Arc<anyhow::Error>);
+
+impl AssetError {
+    pub fn new(error: anyhow::Error) -> Self {
+        Self(Arc::new(error))
+    }
+}
+
+impl Debug for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&*self.0, f)
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// The progress of an asset which is loaded in the background
+#[derive(Debug)]
+pub enum LoadState<T> {
+    Loading,
+    Ready(Handle<T>),
+    Failed(AssetError),
+}
+
+impl<T> Clone for LoadState<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Loading => Self::Loading,
+            Self::Ready(handle) => Self::Ready(handle.clone()),
+            Self::Failed(err) => Self::Failed(err.clone()),
+        }
+    }
+}
+
+struct LoadCellInner<T> {
+    state: LoadState<T>,
+    wakers: Vec<Waker>,
+}
+
+/// Shared state of a background load, stored in the asset cache for deduplication
+pub(crate) struct LoadCell<T> {
+    inner: Mutex<LoadCellInner<T>>,
+}
+
+impl<T> LoadCell<T> {
+    pub(crate) fn new(state: LoadState<T>) -> Self {
+        Self {
+            inner: Mutex::new(LoadCellInner {
+                state,
+                wakers: Vec::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn complete(&self, result: Result<Handle<T>, AssetError>) {
+        let mut inner = self.inner.lock();
+
+        inner.state = match result {
+            Ok(handle) => LoadState::Ready(handle),
+            Err(err) => LoadState::Failed(err),
+        };
+
+        inner.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+/// Handle to an asset which is loaded in the background.
+///
+/// The load is shared between all handles to the same key, and the asset is kept alive for as
+/// long as any of them exist.
+pub struct AsyncHandle<T> {
+    cell: Handle<LoadCell<T>>,
+}
+
+impl<T> Clone for AsyncHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+impl<T> Debug for AsyncHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match &self.cell.inner.lock().state {
+            LoadState::Loading => "Loading",
+            LoadState::Ready(_) => "Ready",
+            LoadState::Failed(_) => "Failed",
+        };
+
+        f.debug_struct("AsyncHandle")
+            .field("id", &self.cell.id())
+            .field("state", &state)
+            .finish()
+    }
+}
+
+impl<T> AsyncHandle<T> {
+    pub(crate) fn new(cell: Handle<LoadCell<T>>) -> Self {
+        Self { cell }
+    }
+
+    /// Returns the current state of the load
+    pub fn state(&self) -> LoadState<T> {
+        self.cell.inner.lock().state.clone()
+    }
+
+    /// Returns the asset if it has finished loading
+    pub fn get(&self) -> Option<Handle<T>> {
+        match &self.cell.inner.lock().state {
+            LoadState::Ready(handle) => Some(handle.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(self.cell.inner.lock().state, LoadState::Loading)
+    }
+
+    /// Returns a future which resolves once the asset has loaded or failed
+    pub fn loaded(&self) -> Loaded<T> {
+        Loaded {
+            cell: self.cell.clone(),
+        }
+    }
+
+    /// Returns an effect which invokes `func` once the asset has loaded or failed
+    pub fn on_loaded<Data, F>(&self, func: F) -> FutureEffect<Loaded<T>, F>
+    where
+        F: FnOnce(&mut Data, Result<Handle<T>, AssetError>),
+    {
+        FutureEffect::new(self.loaded(), func)
+    }
+}
+
+/// Future returned by [`AsyncHandle::loaded`]
+pub struct Loaded<T> {
+    cell: Handle<LoadCell<T>>,
+}
+
+impl<T> Future for Loaded<T> {
+    type Output = Result<Handle<T>, AssetError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.cell.inner.lock();
+
+        match &inner.state {
+            LoadState::Loading => {
+                if !inner.wakers.iter().any(|v| v.will_wake(cx.waker())) {
+                    inner.wakers.push(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+            LoadState::Ready(handle) => Poll::Ready(Ok(handle.clone())),
+            LoadState::Failed(err) => Poll::Ready(Err(err.clone())),
+        }
+    }
+}
@@ -3,24 +3,70 @@ use std::{
     borrow::Borrow,
     collections::HashMap,
     hash::Hash,
+    path::Path,
     sync::Arc,
+    time::Instant,
 };
 
 use dashmap::DashMap;
+use parking_lot::Mutex;
 
 pub mod cell;
 pub mod fs;
 mod handle;
+pub mod image;
+pub mod loader;
 pub mod map;
+mod reload;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub use cell::{AssetStats, Retention};
 pub use handle::Handle;
+pub use reload::AssetNode;
 
-use self::{cell::AssetCell, handle::WeakHandle};
+use self::{
+    cell::{AssetCell, ErasedCell},
+    fs::VirtualFs,
+    handle::WeakHandle,
+    loader::{AssetError, AsyncHandle, LoadCell, LoadState},
+    reload::{LoadGuard, ReloadTracker, Reloader},
+};
+use crate::background::ThreadPool;
 
 slotmap::new_key_type! {
     pub struct AssetId;
 }
 
-type KeyMap<K> = HashMap<K, WeakHandle<<K as AssetKey>::Output>>;
+/// Maps keys to the assets loaded from them.
+///
+/// Stored by the type of the map, as a key may be used to load different kinds of values
+type KeyMap<K, V> = HashMap<K, WeakHandle<V>>;
+
+/// Type erased access to the key maps
+trait ErasedKeyMap: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Removes the keys of released assets
+    fn prune(&mut self);
+}
+
+impl<K, V> ErasedKeyMap for KeyMap<K, V>
+where
+    K: 'static + Send + Sync,
+    V: 'static + Send + Sync,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn prune(&mut self) {
+        self.retain(|_, v| v.strong_count() > 0)
+    }
+}
 
 #[derive(Clone)]
 pub struct AssetCache {
@@ -35,20 +81,40 @@ impl std::fmt::Debug for AssetCache {
 
 /// Stores assets which are accessible through handles
 struct AssetCacheInner {
-    keys: DashMap<TypeId, Box<dyn Any + Send + Sync>>,
-    cells: DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    keys: DashMap<TypeId, Box<dyn ErasedKeyMap>>,
+    cells: DashMap<TypeId, Box<dyn ErasedCell>>,
+    reload: ReloadTracker,
+    fs: VirtualFs,
+    /// Runs background loads, started on first use unless set
+    pool: Mutex<Option<ThreadPool>>,
 }
 
 impl AssetCache {
     pub fn new() -> Self {
-        Self {
+        let assets = Self {
             inner: Arc::new(AssetCacheInner {
                 keys: DashMap::new(),
                 cells: DashMap::new(),
+                reload: ReloadTracker::default(),
+                fs: VirtualFs::new(),
+                pool: Mutex::new(None),
             }),
-        }
+        };
+
+        assets.set_memory_estimate::<Vec<u8>>(|v| std::mem::size_of_val(v) + v.capacity());
+        assets.set_memory_estimate::<::image::DynamicImage>(|v| {
+            std::mem::size_of_val(v) + v.as_bytes().len()
+        });
+
+        assets
     }
 
+    /// Loads an asset, or returns the existing asset if the key is already loaded.
+    ///
+    /// # Panics
+    ///
+    /// If the key is loaded again while it is loading, such as by a key which depends on itself.
+    /// Use [`Self::try_load`] to receive an error instead.
     #[tracing::instrument(level = "info", skip(key), fields(key = type_name::<K>()))]
     pub fn load<K>(&self, key: &K) -> Handle<K::Output>
     where
@@ -60,43 +126,315 @@ impl AssetCache {
         }
 
         // Load the asset and insert it to get a handle
+        let guard = match LoadGuard::new::<K, K::Output>(self.cache_id(), key) {
+            Ok(v) => v,
+            Err(err) => panic!("{err}"),
+        };
+
         let value = key.load(self);
+        let deps = guard.finish();
 
         let handle = self.insert(value);
+        self.insert_key(key.clone(), &handle);
+
+        let key = key.clone();
+        let reload: Reloader = Arc::new(move |assets: &AssetCache| {
+            Some(Box::new(assets.load(&key)) as Box<dyn Any + Send + Sync>)
+        });
 
         self.inner
-            .keys
-            .entry(TypeId::of::<K>())
-            .or_insert_with(|| Box::<HashMap<K, WeakHandle<<K as AssetKey>::Output>>>::default())
-            .downcast_mut::<KeyMap<K>>()
-            .unwrap()
-            .insert(key.clone(), handle.downgrade());
+            .reload
+            .insert::<K, _>(self.cache_id(), &handle, deps, reload);
 
         handle
     }
 
     #[tracing::instrument(level = "info", skip(key), fields(key = type_name::<K>()))]
     pub fn get<K: AssetKey>(&self, key: &K) -> Option<Handle<K::Output>> {
-        // Keys of K
-        let keys = self.inner.keys.get(&TypeId::of::<K>())?;
+        self.get_by_key(key)
+    }
+
+    /// Loads an asset which may fail, such as a file which does not exist.
+    ///
+    /// Failures are not cached, and the next call will attempt to load the asset again. Loading a
+    /// key which depends on itself fails instead of recursing.
+    #[tracing::instrument(level = "info", skip(key), fields(key = type_name::<K>()))]
+    pub fn try_load<K>(&self, key: &K) -> Result<Handle<K::Output>, AssetError>
+    where
+        K: TryAssetKey + Clone,
+    {
+        if let Some(handle) = self.get_by_key(key) {
+            return Ok(handle);
+        }
+
+        let guard =
+            LoadGuard::new::<K, K::Output>(self.cache_id(), key).map_err(AssetError::new)?;
+        let value = key.try_load(self).map_err(AssetError::new)?;
+        let deps = guard.finish();
+
+        let handle = self.insert(value);
+        self.insert_key(key.clone(), &handle);
+
+        let key = key.clone();
+        let reload: Reloader = Arc::new(move |assets: &AssetCache| match assets.try_load(&key) {
+            Ok(handle) => Some(Box::new(handle) as Box<dyn Any + Send + Sync>),
+            Err(err) => {
+                tracing::error!(key = type_name::<K>(), "Failed to reload asset: {err:?}");
+                None
+            }
+        });
+
+        self.inner
+            .reload
+            .insert::<K, _>(self.cache_id(), &handle, deps, reload);
+
+        Ok(handle)
+    }
+
+    /// Set the thread pool which [`Self::load_async`] loads assets on
+    pub fn set_thread_pool(&self, pool: ThreadPool) {
+        *self.inner.pool.lock() = Some(pool);
+    }
+
+    fn thread_pool(&self) -> ThreadPool {
+        self.inner
+            .pool
+            .lock()
+            .get_or_insert_with(ThreadPool::default)
+            .clone()
+    }
+
+    /// Loads an asset on a background thread of the cache's [`ThreadPool`].
+    ///
+    /// Returns immediately with a handle which can be observed to find out when the asset is
+    /// ready. Loading the same key again while the previous handle is alive shares the same load,
+    /// including its failure.
+    #[tracing::instrument(level = "info", skip(key), fields(key = type_name::<K>()))]
+    pub fn load_async<K>(&self, key: &K) -> AsyncHandle<K::Output>
+    where
+        K: TryAssetKey + Clone,
+    {
+        if let Some(cell) = self.get_by_key::<K, LoadCell<K::Output>>(key) {
+            return AsyncHandle::new(cell);
+        }
+
+        // Already loaded synchronously
+        if let Some(handle) = self.get_by_key::<K, K::Output>(key) {
+            let cell = self.insert(LoadCell::new(LoadState::Ready(handle)));
+            self.insert_key(key.clone(), &cell);
+            return AsyncHandle::new(cell);
+        }
+
+        let cell = self.insert(LoadCell::new(LoadState::Loading));
+        self.insert_key(key.clone(), &cell);
+
+        let assets = self.clone();
+        let key = key.clone();
+        let thread_cell = cell.clone();
+
+        self.thread_pool().execute(move || {
+            let result = assets.try_load(&key);
+            if let Err(err) = &result {
+                tracing::error!(key = type_name::<K>(), "Failed to load asset: {err:?}");
+            }
+
+            thread_cell.complete(result);
+        });
+
+        AsyncHandle::new(cell)
+    }
+
+    fn get_by_key<K, V>(&self, key: &K) -> Option<Handle<V>>
+    where
+        K: 'static + Hash + Eq,
+        V: 'static,
+    {
+        let keys = self.inner.keys.get(&TypeId::of::<KeyMap<K, V>>())?;
 
         let handle = keys
-            .downcast_ref::<KeyMap<K>>()
+            .as_any()
+            .downcast_ref::<KeyMap<K, V>>()
             .unwrap()
             .get(key)?
             .upgrade()?;
 
+        // Stale assets are loaded again
+        if self.inner.reload.is_stale::<V>(handle.id()) {
+            return None;
+        }
+
+        // Anything loading this asset depends on the same files
+        self.inner
+            .reload
+            .track_existing::<V>(self.cache_id(), handle.id());
+
         Some(handle)
     }
 
-    pub fn insert<T: 'static + Send + Sync>(&self, value: T) -> Handle<T> {
+    /// Returns the filesystem which file based keys such as [`fs::BytesFromFile`] are read through
+    pub fn fs(&self) -> &VirtualFs {
+        &self.inner.fs
+    }
+
+    /// Records that the asset currently being loaded reads `path`.
+    ///
+    /// When the file is modified the asset, and every asset which loaded it, becomes stale and can
+    /// be reloaded using [`Self::reload`].
+    pub fn track_file(&self, path: &Path) {
+        self.inner.reload.track_file(self.cache_id(), path);
+    }
+
+    /// Checks the modification time of all tracked files, marking the assets depending on modified
+    /// files as stale.
+    ///
+    /// Returns the number of modified files
+    pub fn check_modified(&self) -> usize {
+        self.inner.reload.check_modified()
+    }
+
+    /// Returns a counter which is incremented whenever assets become stale.
+    ///
+    /// Allows holders of handles to only look for stale assets when something has changed.
+    pub fn stale_generation(&self) -> u64 {
+        self.inner.reload.generation()
+    }
+
+    /// Marks the asset, and every asset which was loaded using it, as stale so that they are loaded
+    /// again by [`Self::reload`] or the next load of their keys.
+    ///
+    /// Returns the number of assets which became stale
+    pub fn invalidate<T: 'static>(&self, handle: &Handle<T>) -> usize {
+        self.inner.reload.invalidate::<T>(handle.id())
+    }
+
+    /// Returns the assets which were loaded by the key of the asset, which the asset usually keeps
+    /// alive
+    pub fn dependencies<T: 'static>(&self, handle: &Handle<T>) -> Vec<AssetNode> {
+        self.inner.reload.dependencies::<T>(handle.id())
+    }
+
+    /// Returns the assets whose keys loaded the asset
+    pub fn dependents<T: 'static>(&self, handle: &Handle<T>) -> Vec<AssetNode> {
+        self.inner.reload.dependents::<T>(handle.id())
+    }
+
+    /// Returns true if the asset, or any asset it was loaded using, has been modified or
+    /// invalidated
+    pub fn is_stale<T: 'static>(&self, handle: &Handle<T>) -> bool {
+        self.inner.reload.is_stale::<T>(handle.id())
+    }
+
+    /// Loads a stale asset again from its key.
+    ///
+    /// Returns `None` if the asset is not stale or could not be reloaded. All holders of the old
+    /// handle receive the same new handle.
+    pub fn reload<T: 'static + Send + Sync>(&self, handle: &Handle<T>) -> Option<Handle<T>> {
+        let reload = self.inner.reload.reloader::<T>(handle.id())?;
+
+        match reload(self).and_then(|v| v.downcast::<Handle<T>>().ok()) {
+            Some(new) => Some(*new),
+            None => {
+                self.inner.reload.restore::<T>(handle.id());
+                None
+            }
+        }
+    }
+
+    /// Distinguishes caches loading on the same thread
+    fn cache_id(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
+    fn insert_key<K, V>(&self, key: K, handle: &Handle<V>)
+    where
+        K: 'static + Send + Sync + Hash + Eq,
+        V: 'static + Send + Sync,
+    {
         self.inner
+            .keys
+            .entry(TypeId::of::<KeyMap<K, V>>())
+            .or_insert_with(|| Box::<KeyMap<K, V>>::default())
+            .as_any_mut()
+            .downcast_mut::<KeyMap<K, V>>()
+            .unwrap()
+            .insert(key, handle.downgrade());
+    }
+
+    pub fn insert<T: 'static + Send + Sync>(&self, value: T) -> Handle<T> {
+        self.with_cell(|cell| cell.insert(value))
+    }
+
+    /// Sets how long assets of type `T` are kept after their last handle is dropped.
+    ///
+    /// Retaining assets avoids loading them again when they are frequently dropped and reused,
+    /// such as images of a carousel. Retained assets are released by [`Self::collect`].
+    pub fn set_retention<T: 'static + Send + Sync>(&self, retention: Retention) {
+        self.with_cell::<T, _>(|cell| cell.set_retention(retention))
+    }
+
+    /// Sets the function estimating the memory used by an asset of type `T`.
+    ///
+    /// Defaults to the size of the type, which excludes any heap allocations.
+    pub fn set_memory_estimate<T: 'static + Send + Sync>(&self, memory: fn(&T) -> usize) {
+        self.with_cell(|cell| cell.set_memory_estimate(memory))
+    }
+
+    /// Releases retained assets according to the retention policies, and removes the entries of
+    /// released assets.
+    ///
+    /// Called every frame by the app.
+    pub fn collect(&self) {
+        let now = Instant::now();
+
+        self.inner.cells.iter_mut().for_each(|mut v| v.collect(now));
+
+        self.inner.keys.iter_mut().for_each(|mut v| v.prune());
+        self.inner.reload.prune();
+    }
+
+    /// Returns statistics for each type of asset in the cache
+    pub fn stats(&self) -> Vec<AssetStats> {
+        let mut stats = self
+            .inner
             .cells
-            .entry(TypeId::of::<T>())
-            .or_insert_with(|| Box::new(AssetCell::<T>::new()))
-            .downcast_mut::<AssetCell<T>>()
+            .iter()
+            .map(|v| v.stats())
+            .collect::<Vec<_>>();
+
+        stats.sort_by_key(|v| v.type_name);
+        stats
+    }
+
+    /// Returns the keys of type `K` whose loaded asset of type `V` is alive
+    pub fn alive_keys<K, V>(&self) -> Vec<K>
+    where
+        K: 'static + Hash + Eq + Clone,
+        V: 'static,
+    {
+        let Some(keys) = self.inner.keys.get(&TypeId::of::<KeyMap<K, V>>()) else {
+            return Vec::new();
+        };
+
+        keys.as_any()
+            .downcast_ref::<KeyMap<K, V>>()
             .unwrap()
-            .insert(value)
+            .iter()
+            .filter(|(_, v)| v.strong_count() > 0)
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    fn with_cell<T, R>(&self, f: impl FnOnce(&mut AssetCell<T>) -> R) -> R
+    where
+        T: 'static + Send + Sync,
+    {
+        let mut cell = self
+            .inner
+            .cells
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(AssetCell::<T>::new()));
+
+        f(cell.as_any_mut().downcast_mut::<AssetCell<T>>().unwrap())
     }
 }
 
@@ -113,6 +451,13 @@ pub trait AssetKey: 'static + Send + Sync + Hash + Eq {
     fn load(&self, assets: &AssetCache) -> Self::Output;
 }
 
+/// Describes an asset which may fail to load
+pub trait TryAssetKey: 'static + Send + Sync + Hash + Eq {
+    type Output: 'static + Send + Sync;
+
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,4 +490,264 @@ mod tests {
         assert!(assets.get(&Key("Foo".to_string())).is_none());
         assert!(assets.get(&Key("Bar".to_string())).is_some());
     }
+
+    #[derive(Hash, Eq, PartialEq, Clone)]
+    struct Parse(&'static str);
+
+    impl TryAssetKey for Parse {
+        type Output = i32;
+
+        fn try_load(&self, _: &AssetCache) -> anyhow::Result<Self::Output> {
+            Ok(self.0.parse()?)
+        }
+    }
+
+    #[test]
+    fn try_load() {
+        let assets = AssetCache::new();
+
+        let value = assets.try_load(&Parse("5")).unwrap();
+        assert_eq!(*value, 5);
+        assert_eq!(assets.try_load(&Parse("5")).unwrap(), value);
+
+        assert!(assets.try_load(&Parse("five")).is_err());
+    }
+
+    #[test]
+    fn load_async() {
+        let assets = AssetCache::new();
+
+        let handle = assets.load_async(&Parse("5"));
+        let value = futures::executor::block_on(handle.loaded()).unwrap();
+        assert_eq!(*value, 5);
+
+        // Shares the load and the synchronously loaded value
+        assert_eq!(assets.load_async(&Parse("5")).get(), Some(value.clone()));
+        assert_eq!(assets.try_load(&Parse("5")).unwrap(), value);
+
+        let failed = assets.load_async(&Parse("five"));
+        assert!(futures::executor::block_on(failed.loaded()).is_err());
+        assert!(matches!(failed.state(), LoadState::Failed(_)));
+    }
+
+    #[test]
+    fn load_async_pool() {
+        #[derive(Hash, Eq, PartialEq, Clone)]
+        struct ThreadName(usize);
+
+        impl TryAssetKey for ThreadName {
+            type Output = Option<String>;
+
+            fn try_load(&self, _: &AssetCache) -> anyhow::Result<Self::Output> {
+                Ok(std::thread::current().name().map(ToOwned::to_owned))
+            }
+        }
+
+        let assets = AssetCache::new();
+        assets.set_thread_pool(ThreadPool::new(2));
+
+        let handles = (0..64)
+            .map(|i| assets.load_async(&ThreadName(i)))
+            .collect::<Vec<_>>();
+
+        // Every load runs on one of the two workers rather than a thread of its own
+        for handle in handles {
+            let name = futures::executor::block_on(handle.loaded()).unwrap();
+            assert!(
+                matches!(name.as_deref(), Some("background-0" | "background-1")),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reload() {
+        #[derive(Hash, Eq, PartialEq, Clone)]
+        struct Upper(fs::BytesFromFile);
+
+        impl TryAssetKey for Upper {
+            type Output = String;
+
+            fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+                let bytes = assets.try_load(&self.0)?;
+                Ok(String::from_utf8(bytes.to_vec())?.to_uppercase())
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("violet-reload-{}", std::process::id()));
+        std::fs::write(&path, "foo").unwrap();
+
+        let assets = AssetCache::new();
+        let key = Upper(fs::BytesFromFile(path.clone()));
+
+        let bytes = assets.try_load(&key.0).unwrap();
+        let upper = assets.try_load(&key).unwrap();
+        assert_eq!(&*upper, "FOO");
+        assert_eq!(assets.check_modified(), 0);
+
+        std::fs::write(&path, "bar").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+
+        assert_eq!(assets.check_modified(), 1);
+        assert!(assets.is_stale(&bytes));
+        assert!(assets.is_stale(&upper));
+
+        let new_upper = assets.reload(&upper).unwrap();
+        assert_eq!(&*new_upper, "BAR");
+        assert_eq!(assets.reload(&upper), Some(new_upper.clone()));
+        assert!(!assets.is_stale(&new_upper));
+
+        // The file was already reloaded as a dependency
+        assert_eq!(&**assets.reload(&bytes).unwrap(), b"bar");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn virtual_fs() {
+        let assets = AssetCache::new();
+
+        let memory = fs::MemorySource::new().with_file("a.txt", *b"a");
+        assets.fs().mount("assets", memory.clone());
+        assets.fs().mount(
+            "assets/nested",
+            fs::MemorySource::new().with_file("b.txt", *b"nested"),
+        );
+
+        let read = |path: &str| assets.try_load(&fs::BytesFromFile(path.into()));
+
+        assert_eq!(&**read("assets/a.txt").unwrap(), b"a");
+        assert_eq!(&**read("./assets/nested/b.txt").unwrap(), b"nested");
+        assert!(read("assets/b.txt").is_err());
+
+        // Parent directories are resolved, but may not leave the root
+        assert_eq!(&**read("assets/nested/../a.txt").unwrap(), b"a");
+        for path in ["assets/../../Cargo.toml", "../Cargo.toml", "assets/../.."] {
+            let err = assets.fs().read(path).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound, "{path}");
+            assert!(!assets.fs().exists(path), "{path}");
+            assert_eq!(assets.fs().disk_path(path), None, "{path}");
+        }
+
+        // Overrides the existing mount for the files it contains
+        assets
+            .fs()
+            .mount("assets", fs::MemorySource::new().with_file("c.txt", *b"c"));
+        assert_eq!(&**read("assets/c.txt").unwrap(), b"c");
+
+        memory.insert("d.txt", *b"d");
+        assert!(assets.fs().exists("assets/d.txt"));
+        assert_eq!(assets.fs().disk_path("assets/d.txt"), None);
+
+        assets.fs().unmount("assets");
+        assert!(!assets.fs().exists("assets/a.txt"));
+    }
+
+    #[test]
+    fn retention() {
+        #[derive(Hash, Eq, PartialEq, Clone, Debug)]
+        struct Key(u32);
+
+        impl AssetKey for Key {
+            type Output = u32;
+
+            fn load(&self, _: &AssetCache) -> Self::Output {
+                self.0
+            }
+        }
+
+        let assets = AssetCache::new();
+        assets.set_retention::<u32>(Retention::LastUnused(1));
+
+        let first = assets.load(&Key(1));
+        let second = assets.load(&Key(2));
+        assets.collect();
+
+        drop(first);
+        assets.collect();
+        drop(second);
+        assets.collect();
+
+        // Only the most recently unused asset is kept
+        assert!(assets.get(&Key(1)).is_none());
+        assert!(assets.get(&Key(2)).is_some());
+        assert_eq!(assets.alive_keys::<Key, u32>(), [Key(2)]);
+
+        let stats = assets.stats();
+        let stats = stats.iter().find(|v| v.type_name == "u32").unwrap();
+        assert_eq!(stats.alive, 1);
+        assert_eq!(stats.retained, 1);
+        assert_eq!(stats.memory, 4);
+
+        assets.set_retention::<u32>(Retention::For(std::time::Duration::ZERO));
+        assets.collect();
+        assert!(assets.get(&Key(2)).is_none());
+    }
+
+    #[test]
+    fn dependencies() {
+        #[derive(Hash, Eq, PartialEq, Clone)]
+        struct Sum(Vec<&'static str>);
+
+        impl TryAssetKey for Sum {
+            type Output = i32;
+
+            fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+                let sum = self
+                    .0
+                    .iter()
+                    .map(|v| assets.try_load(&Parse(v)).map(|v| *v))
+                    .sum::<Result<i32, _>>()?;
+
+                Ok(sum)
+            }
+        }
+
+        let assets = AssetCache::new();
+
+        let one = assets.try_load(&Parse("1")).unwrap();
+        let sum = assets.try_load(&Sum(vec!["1", "2"])).unwrap();
+        assert_eq!(*sum, 3);
+
+        let dependencies = assets.dependencies(&sum);
+        assert_eq!(dependencies.len(), 1, "Only loaded assets are listed");
+        assert_eq!(dependencies[0].id, one.id());
+        assert_eq!(assets.dependents(&one)[0].id, sum.id());
+
+        // Invalidating a dependency cascades to the assets loaded using it
+        assert_eq!(assets.invalidate(&one), 2);
+        assert!(assets.is_stale(&sum));
+
+        let new_sum = assets.reload(&sum).unwrap();
+        assert_eq!(*new_sum, 3);
+        assert_ne!(new_sum, sum);
+    }
+
+    #[test]
+    fn cycle() {
+        #[derive(Hash, Eq, PartialEq, Clone)]
+        struct Cycle(u32);
+
+        impl TryAssetKey for Cycle {
+            type Output = ();
+
+            fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+                assets.try_load(&Cycle((self.0 + 1) % 2))?;
+                Ok(())
+            }
+        }
+
+        let assets = AssetCache::new();
+
+        let err = assets.try_load(&Cycle(0)).unwrap_err();
+        assert!(format!("{err:?}").contains("Cycle detected"));
+
+        // The failed load does not leave anything behind
+        assert!(assets.try_load(&Cycle(0)).is_err());
+    }
 }
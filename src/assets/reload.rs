@@ -0,0 +1,452 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::SystemTime,
+};
+
+use parking_lot::Mutex;
+
+use super::{AssetCache, AssetId, Handle};
+
+/// A loaded asset in the dependency graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetNode {
+    pub id: AssetId,
+    /// The type of the asset
+    pub type_name: &'static str,
+    /// The type of the key the asset was loaded from
+    pub key: &'static str,
+}
+
+/// Loads the asset again from its key, returning a boxed `Handle<T>`
+pub(crate) type Reloader =
+    Arc<dyn Fn(&AssetCache) -> Option<Box<dyn Any + Send + Sync>> + Send + Sync>;
+
+/// Identifies an asset across all asset types
+type AssetKeyId = (TypeId, AssetId);
+
+struct TrackedFile {
+    modified: Option<SystemTime>,
+    /// Assets which read the file while loading, including the assets which loaded them
+    dependents: Vec<AssetKeyId>,
+}
+
+struct TrackedAsset {
+    value: Weak<dyn Any + Send + Sync>,
+    type_name: &'static str,
+    key: &'static str,
+    files: Vec<PathBuf>,
+    /// Assets loaded by this asset's key
+    dependencies: Vec<AssetKeyId>,
+    /// Assets whose keys loaded this asset
+    dependents: Vec<AssetKeyId>,
+    reload: Reloader,
+    stale: bool,
+}
+
+/// A load in progress on the current thread
+struct LoadFrame {
+    cache: usize,
+    key: Box<dyn Any>,
+    key_name: &'static str,
+    output: TypeId,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    dependencies: Vec<AssetKeyId>,
+}
+
+/// The files and assets used while loading an asset
+pub(crate) struct LoadDeps {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    dependencies: Vec<AssetKeyId>,
+}
+
+thread_local! {
+    /// The keys currently being loaded, which are dependents of any file read
+    static LOADING: RefCell<Vec<LoadFrame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps track of which assets were loaded from which files and other assets
+#[derive(Default)]
+pub(crate) struct ReloadTracker {
+    files: Mutex<HashMap<PathBuf, TrackedFile>>,
+    assets: Mutex<HashMap<AssetKeyId, TrackedAsset>>,
+    /// Incremented whenever assets become stale
+    generation: AtomicU64,
+}
+
+impl ReloadTracker {
+    /// Associates the file with every asset currently being loaded
+    pub(crate) fn track_file(&self, cache: usize, path: &Path) {
+        let modified = modified_time(path);
+        push_files(cache, [(path.to_path_buf(), modified)]);
+    }
+
+    /// Records an existing asset as a dependency of the asset currently being loaded, and
+    /// propagates its files to all loads in progress.
+    pub(crate) fn track_existing<T: 'static>(&self, cache: usize, id: AssetId) {
+        if !is_loading(cache) {
+            return;
+        }
+
+        let id = (TypeId::of::<T>(), id);
+        let assets = self.assets.lock();
+        let Some(asset) = assets.get(&id) else {
+            return;
+        };
+
+        push_dependency(cache, id);
+
+        let files = self.files.lock();
+        push_files(
+            cache,
+            asset.files.iter().map(|path| {
+                let modified = files.get(path).and_then(|v| v.modified);
+                (path.clone(), modified)
+            }),
+        );
+    }
+
+    /// Records a loaded asset which depends on the files and assets in `deps`
+    pub(crate) fn insert<K: 'static, T: 'static + Send + Sync>(
+        &self,
+        cache: usize,
+        handle: &Handle<T>,
+        deps: LoadDeps,
+        reload: Reloader,
+    ) {
+        let id = (TypeId::of::<T>(), handle.id());
+        let LoadDeps {
+            files,
+            dependencies,
+        } = deps;
+
+        // The asset which is loading this one depends on it
+        push_dependency(cache, id);
+
+        // Locks are always taken in the order of assets then files
+        let mut assets = self.assets.lock();
+
+        for dependency in &dependencies {
+            if let Some(dependency) = assets.get_mut(dependency) {
+                dependency.dependents.push(id);
+            }
+        }
+
+        let mut tracked_files = self.files.lock();
+        for (path, modified) in &files {
+            let file = tracked_files
+                .entry(path.clone())
+                .or_insert_with(|| TrackedFile {
+                    modified: *modified,
+                    dependents: Vec::new(),
+                });
+
+            file.modified = *modified;
+            file.dependents.push(id);
+        }
+
+        let value: Weak<dyn Any + Send + Sync> = Arc::downgrade(&handle.value) as _;
+
+        assets.insert(
+            id,
+            TrackedAsset {
+                value,
+                type_name: type_name::<T>(),
+                key: type_name::<K>(),
+                files: files.into_iter().map(|v| v.0).collect(),
+                dependencies,
+                dependents: Vec::new(),
+                reload,
+                stale: false,
+            },
+        );
+    }
+
+    pub(crate) fn is_stale<T: 'static>(&self, id: AssetId) -> bool {
+        self.assets
+            .lock()
+            .get(&(TypeId::of::<T>(), id))
+            .is_some_and(|v| v.stale)
+    }
+
+    pub(crate) fn reloader<T: 'static>(&self, id: AssetId) -> Option<Reloader> {
+        self.assets
+            .lock()
+            .get(&(TypeId::of::<T>(), id))
+            .filter(|v| v.stale)
+            .map(|v| v.reload.clone())
+    }
+
+    /// Stops considering the asset stale until its files are modified again.
+    ///
+    /// Used when reloading fails, such as when a file is only partially written.
+    pub(crate) fn restore<T: 'static>(&self, id: AssetId) {
+        let id = (TypeId::of::<T>(), id);
+        let mut assets = self.assets.lock();
+        let Some(asset) = assets.get_mut(&id) else {
+            return;
+        };
+
+        asset.stale = false;
+
+        let mut files = self.files.lock();
+        for path in &asset.files {
+            files
+                .entry(path.clone())
+                .or_insert_with(|| TrackedFile {
+                    modified: modified_time(path),
+                    dependents: Vec::new(),
+                })
+                .dependents
+                .push(id);
+        }
+    }
+
+    /// Marks the assets of all modified files as stale.
+    ///
+    /// Returns the number of modified files
+    pub(crate) fn check_modified(&self) -> usize {
+        let mut changed = Vec::new();
+        let mut count = 0;
+
+        {
+            let mut files = self.files.lock();
+            for (path, file) in files.iter_mut() {
+                let modified = modified_time(path);
+                if modified != file.modified {
+                    tracing::info!(?path, "File modified");
+                    file.modified = modified;
+                    changed.append(&mut file.dependents);
+                    count += 1;
+                }
+            }
+
+            // Assets re-register their files when they are reloaded
+            files.retain(|_, v| !v.dependents.is_empty());
+        }
+
+        let mut assets = self.assets.lock();
+        let stale = changed
+            .iter()
+            .map(|&id| invalidate(&mut assets, id))
+            .sum::<usize>();
+
+        if stale > 0 {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+
+        assets.retain(|_, v| v.value.strong_count() > 0);
+
+        count
+    }
+
+    /// Marks the asset and everything which depends on it as stale.
+    ///
+    /// Returns the number of assets which became stale
+    pub(crate) fn invalidate<T: 'static>(&self, id: AssetId) -> usize {
+        let stale = invalidate(&mut self.assets.lock(), (TypeId::of::<T>(), id));
+        if stale > 0 {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+
+        stale
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Returns the loaded assets which were loaded by the key of the asset.
+    ///
+    /// These are usually kept alive by the asset
+    pub(crate) fn dependencies<T: 'static>(&self, id: AssetId) -> Vec<AssetNode> {
+        self.related((TypeId::of::<T>(), id), |v| &v.dependencies)
+    }
+
+    /// Returns the loaded assets whose keys loaded the asset
+    pub(crate) fn dependents<T: 'static>(&self, id: AssetId) -> Vec<AssetNode> {
+        self.related((TypeId::of::<T>(), id), |v| &v.dependents)
+    }
+
+    fn related(
+        &self,
+        id: AssetKeyId,
+        edges: impl Fn(&TrackedAsset) -> &Vec<AssetKeyId>,
+    ) -> Vec<AssetNode> {
+        let assets = self.assets.lock();
+        let Some(asset) = assets.get(&id) else {
+            return Vec::new();
+        };
+
+        edges(asset)
+            .iter()
+            .filter_map(|id| {
+                let asset = assets.get(id).filter(|v| v.value.strong_count() > 0)?;
+                Some(AssetNode {
+                    id: id.1,
+                    type_name: asset.type_name,
+                    key: asset.key,
+                })
+            })
+            .collect()
+    }
+
+    /// Removes released assets from the graph
+    pub(crate) fn prune(&self) {
+        let mut assets = self.assets.lock();
+        assets.retain(|_, v| v.value.strong_count() > 0);
+
+        let alive = assets.keys().copied().collect::<HashSet<_>>();
+        for asset in assets.values_mut() {
+            asset.dependents.retain(|v| alive.contains(v));
+        }
+    }
+}
+
+/// Tracks the files and assets used while loading an asset on the current thread.
+///
+/// Loads nest, and each file is attributed to every load in progress.
+pub(crate) struct LoadGuard {
+    cache: usize,
+    depth: usize,
+}
+
+impl LoadGuard {
+    /// Starts loading `key`.
+    ///
+    /// Fails if the same key is already being loaded on this thread, as the load would never
+    /// finish.
+    pub(crate) fn new<K, V>(cache: usize, key: &K) -> Result<Self, anyhow::Error>
+    where
+        K: 'static + Eq + Clone,
+        V: 'static,
+    {
+        let depth = LOADING.with(|v| {
+            let mut v = v.borrow_mut();
+
+            let cycle = v.iter().position(|frame| {
+                frame.cache == cache
+                    && frame.output == TypeId::of::<V>()
+                    && frame.key.downcast_ref::<K>() == Some(key)
+            });
+
+            if let Some(start) = cycle {
+                let chain = v[start..]
+                    .iter()
+                    .filter(|v| v.cache == cache)
+                    .map(|v| v.key_name)
+                    .chain([type_name::<K>()])
+                    .collect::<Vec<_>>();
+
+                anyhow::bail!(
+                    "Cycle detected while loading assets: {}",
+                    chain.join(" -> ")
+                );
+            }
+
+            v.push(LoadFrame {
+                cache,
+                key: Box::new(key.clone()),
+                key_name: type_name::<K>(),
+                output: TypeId::of::<V>(),
+                files: Vec::new(),
+                dependencies: Vec::new(),
+            });
+
+            Ok(v.len())
+        })?;
+
+        Ok(Self { cache, depth })
+    }
+
+    /// Finishes the load, returning the files and assets which were used
+    pub(crate) fn finish(self) -> LoadDeps {
+        let deps = LOADING.with(|v| {
+            let mut v = v.borrow_mut();
+            debug_assert_eq!(v.len(), self.depth);
+            debug_assert_eq!(v.last().map(|v| v.cache), Some(self.cache));
+            v.last_mut().map(|v| LoadDeps {
+                files: std::mem::take(&mut v.files),
+                dependencies: std::mem::take(&mut v.dependencies),
+            })
+        });
+
+        deps.unwrap_or(LoadDeps {
+            files: Vec::new(),
+            dependencies: Vec::new(),
+        })
+    }
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        LOADING.with(|v| v.borrow_mut().truncate(self.depth - 1));
+    }
+}
+
+/// Marks the asset and everything which transitively depends on it as stale
+fn invalidate(assets: &mut HashMap<AssetKeyId, TrackedAsset>, id: AssetKeyId) -> usize {
+    let mut stack = vec![id];
+    let mut count = 0;
+
+    while let Some(id) = stack.pop() {
+        let Some(asset) = assets.get_mut(&id) else {
+            continue;
+        };
+
+        if asset.stale {
+            continue;
+        }
+
+        asset.stale = true;
+        count += 1;
+        stack.extend_from_slice(&asset.dependents);
+    }
+
+    count
+}
+
+/// Records a dependency of the innermost load in progress
+fn push_dependency(cache: usize, id: AssetKeyId) {
+    LOADING.with(|v| {
+        let mut v = v.borrow_mut();
+        if let Some(frame) = v.iter_mut().rev().find(|v| v.cache == cache) {
+            if !frame.dependencies.contains(&id) {
+                frame.dependencies.push(id);
+            }
+        }
+    })
+}
+
+fn push_files(cache: usize, files: impl IntoIterator<Item = (PathBuf, Option<SystemTime>)>) {
+    LOADING.with(|v| {
+        let mut v = v.borrow_mut();
+        let mut frames = v.iter_mut().filter(|v| v.cache == cache).peekable();
+        if frames.peek().is_none() {
+            return;
+        }
+
+        let files = files.into_iter().collect::<Vec<_>>();
+        for frame in frames {
+            for file in &files {
+                if !frame.files.iter().any(|v| v.0 == file.0) {
+                    frame.files.push(file.clone());
+                }
+            }
+        }
+    })
+}
+
+fn is_loading(cache: usize) -> bool {
+    LOADING.with(|v| v.borrow().iter().any(|v| v.cache == cache))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
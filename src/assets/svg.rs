@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use ::image::{DynamicImage, RgbaImage};
+use anyhow::Context;
+use glam::{vec2, UVec2};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{self, TreeParsing},
+};
+
+use super::{fs::BytesFromFile, AssetCache, Handle, TryAssetKey};
+
+/// Where to read an SVG document from
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum SvgSource {
+    /// A file of the virtual filesystem of the asset cache
+    File(BytesFromFile),
+    Bytes(Handle<Vec<u8>>),
+}
+
+impl SvgSource {
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::File(BytesFromFile(path.into()))
+    }
+}
+
+/// How an SVG document is scaled to the size of the image
+#[derive(Default, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum SvgScaling {
+    /// Scale uniformly to fit inside the image, centered along the other axis
+    #[default]
+    Fit,
+    /// Scale each axis separately to fill the image
+    Stretch,
+}
+
+/// Rasterizes an SVG document to an image of `size` pixels.
+///
+/// Each size is cached separately, so the same icon displayed at different sizes is rasterized
+/// once per size.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct SvgImage {
+    pub source: SvgSource,
+    pub size: UVec2,
+    pub scaling: SvgScaling,
+}
+
+impl SvgImage {
+    pub fn new(source: SvgSource, size: UVec2) -> Self {
+        Self {
+            source,
+            size,
+            scaling: SvgScaling::default(),
+        }
+    }
+
+    /// Set how the document is scaled to the image
+    pub fn with_scaling(mut self, scaling: SvgScaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+}
+
+impl TryAssetKey for SvgImage {
+    type Output = DynamicImage;
+
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        let bytes = match &self.source {
+            SvgSource::File(path) => assets.try_load(path)?,
+            SvgSource::Bytes(bytes) => bytes.clone(),
+        };
+
+        rasterize(&bytes, self.size, self.scaling)
+            .with_context(|| format!("Failed to load svg {:?}", self.source))
+    }
+}
+
+fn rasterize(bytes: &[u8], size: UVec2, scaling: SvgScaling) -> anyhow::Result<DynamicImage> {
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())?;
+    let tree = resvg::Tree::from_usvg(&tree);
+
+    let size = size.max(UVec2::ONE);
+    let mut pixmap = Pixmap::new(size.x, size.y).context("Invalid svg image size")?;
+
+    let scale = size.as_vec2() / vec2(tree.size.width(), tree.size.height());
+
+    let transform = match scaling {
+        SvgScaling::Fit => {
+            let scale = scale.min_element();
+            let offset =
+                (size.as_vec2() - vec2(tree.size.width(), tree.size.height()) * scale) / 2.0;
+            Transform::from_scale(scale, scale).post_translate(offset.x, offset.y)
+        }
+        SvgScaling::Stretch => Transform::from_scale(scale.x, scale.y),
+    };
+
+    tree.render(transform, &mut pixmap.as_mut());
+
+    // The pixmap is premultiplied, while images are expected to have straight alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|v| {
+            let v = v.demultiply();
+            [v.red(), v.green(), v.blue(), v.alpha()]
+        })
+        .collect();
+
+    let image = RgbaImage::from_raw(size.x, size.y, pixels).context("Mismatched pixmap size")?;
+
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A red and a blue square side by side
+    const SVG: &[u8] = br##"
+        <svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+            <rect width="10" height="10" fill="#ff0000"/>
+            <rect x="10" width="10" height="10" fill="#0000ff"/>
+        </svg>
+    "##;
+
+    #[test]
+    fn rasterize_bytes() {
+        let assets = AssetCache::new();
+        let source = SvgSource::Bytes(assets.insert(SVG.to_vec()));
+
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let clear = [0, 0, 0, 0];
+
+        let cases = [
+            // Letterboxed vertically, covering the rows 10..30
+            (
+                SvgScaling::Fit,
+                [
+                    (10, 20, red),
+                    (30, 20, blue),
+                    (10, 5, clear),
+                    (30, 35, clear),
+                ],
+            ),
+            (
+                SvgScaling::Stretch,
+                [(10, 20, red), (30, 20, blue), (10, 5, red), (30, 35, blue)],
+            ),
+        ];
+
+        for (scaling, pixels) in cases {
+            let image = SvgImage::new(source.clone(), UVec2::new(40, 40)).with_scaling(scaling);
+            let image = assets.try_load(&image).unwrap().to_rgba8();
+
+            assert_eq!(image.dimensions(), (40, 40));
+            for (x, y, color) in pixels {
+                assert_eq!(image.get_pixel(x, y).0, color, "{scaling:?} at {x},{y}");
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+use futures::{channel::oneshot, Future};
+
+use crate::executor::Cancelled;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of worker threads for running blocking work off the UI thread.
+///
+/// Clones share the same workers. The workers exit once all clones are dropped.
+#[derive(Clone)]
+pub struct ThreadPool {
+    tx: flume::Sender<Job>,
+}
+
+impl std::fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadPool")
+            .field("queued", &self.tx.len())
+            .finish()
+    }
+}
+
+impl ThreadPool {
+    /// Starts a pool with `threads` workers
+    pub fn new(threads: usize) -> Self {
+        let (tx, rx) = flume::unbounded::<Job>();
+
+        for i in 0..threads.max(1) {
+            let rx = rx.clone();
+            let res = thread::Builder::new()
+                .name(format!("background-{i}"))
+                .spawn(move || {
+                    for job in rx.into_iter() {
+                        // A panicking job cancels its task, but must not take down the worker
+                        if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            tracing::error!("Background task panicked");
+                        }
+                    }
+                });
+
+            if let Err(err) = res {
+                tracing::error!("Failed to spawn background worker: {err:?}");
+            }
+        }
+
+        Self { tx }
+    }
+
+    /// Runs `func` on a worker thread.
+    ///
+    /// The returned task resolves to the result of `func`. Dropping the task before a worker has
+    /// picked up `func` cancels it, and `func` is never run.
+    pub fn spawn<F, T>(&self, func: F) -> BackgroundTask<T>
+    where
+        F: 'static + Send + FnOnce() -> T,
+        T: 'static + Send,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        self.execute(move || {
+            if tx.is_canceled() {
+                return;
+            }
+
+            tx.send(func()).ok();
+        });
+
+        BackgroundTask { rx }
+    }
+
+    /// Runs `func` on a worker thread without waiting for the result
+    pub fn execute<F>(&self, func: F)
+    where
+        F: 'static + Send + FnOnce(),
+    {
+        // The workers only exit once all senders are dropped
+        self.tx.send(Box::new(func)).ok();
+    }
+}
+
+impl Default for ThreadPool {
+    /// Starts a pool with one worker per available core
+    fn default() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|v| v.get())
+            .unwrap_or(4);
+
+        Self::new(threads)
+    }
+}
+
+/// The result of work running on a [`ThreadPool`].
+///
+/// Resolves to [`Cancelled`] if the work panicked.
+#[derive(Debug)]
+pub struct BackgroundTask<T> {
+    rx: oneshot::Receiver<T>,
+}
+
+impl<T> Future for BackgroundTask<T> {
+    type Output = Result<T, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map_err(|_| Cancelled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[test]
+    fn thread_pool() {
+        let pool = ThreadPool::new(2);
+
+        let tasks = (0..8)
+            .map(|i| pool.spawn(move || i * 2))
+            .collect::<Vec<_>>();
+
+        let results = futures::executor::block_on(futures::future::join_all(tasks));
+        assert_eq!(results, (0..8).map(|i| Ok(i * 2)).collect::<Vec<_>>());
+
+        let panicked = pool.spawn(|| panic!("Oh no"));
+        assert_eq!(
+            futures::executor::block_on(panicked),
+            Err::<(), _>(Cancelled)
+        );
+
+        // Occupy the only worker, then cancel the queued work
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = flume::bounded::<()>(0);
+        let blocker = pool.spawn(move || rx.recv().unwrap());
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let cancelled = pool.spawn({
+            let count = count.clone();
+            move || count.fetch_add(1, Ordering::Relaxed)
+        });
+
+        drop(cancelled);
+        tx.send(()).unwrap();
+        futures::executor::block_on(blocker).unwrap();
+
+        let last = pool.spawn(|| {});
+        futures::executor::block_on(last).unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// Provides access to a clipboard for copying and pasting text
+pub trait Clipboard: Send + Sync {
+    /// Returns the current contents of the clipboard, if it contains text
+    fn get(&self) -> Option<String>;
+    /// Replaces the contents of the clipboard
+    fn set(&self, text: String);
+}
+
+/// A clipboard which only exists within the process.
+///
+/// Clones share the same contents, which allows inspecting what was copied in tests.
+#[derive(Default, Debug, Clone)]
+pub struct MemoryClipboard {
+    contents: Arc<Mutex<Option<String>>>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&self) -> Option<String> {
+        self.contents.lock().clone()
+    }
+
+    fn set(&self, text: String) {
+        *self.contents.lock() = Some(text);
+    }
+}
+
+/// The clipboard of the operating system, shared with other applications
+#[cfg(feature = "clipboard")]
+pub struct SystemClipboard {
+    inner: Mutex<arboard::Clipboard>,
+}
+
+#[cfg(feature = "clipboard")]
+impl SystemClipboard {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Mutex::new(arboard::Clipboard::new()?),
+        })
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn get(&self) -> Option<String> {
+        self.inner.lock().get_text().ok()
+    }
+
+    fn set(&self, text: String) {
+        if let Err(err) = self.inner.lock().set_text(text) {
+            tracing::error!("Failed to copy to the clipboard: {err}");
+        }
+    }
+}
@@ -2,7 +2,13 @@ use flax::{component, Debuggable, Entity};
 use glam::{vec2, Vec2};
 use palette::Srgba;
 
-use crate::{layout::Layout, shapes::FilledRect, unit::Unit};
+use crate::{
+    animation::LayoutTransition,
+    layout::Layout,
+    shapes::{FilledRect, Shape},
+    text::{RichText, TextAlign, TextOverflow, TextSelection, VerticalAlign, WrapMode},
+    unit::Unit,
+};
 
 component! {
     /// Ordered list of children for an entity
@@ -36,6 +42,10 @@ component! {
     /// Manages the layout of the children
     pub layout: Layout => [ Debuggable ],
 
+    /// Animates changes to the computed [`rect`] and [`local_position`] of the widget, such as
+    /// when a list is reordered, rather than jumping to the new layout
+    pub layout_transition: LayoutTransition => [ Debuggable ],
+
     /// Spacing between a outer and inner bounds
     pub padding: Edges => [ Debuggable ],
     pub margin: Edges => [ Debuggable ],
@@ -43,11 +53,41 @@ component! {
 
     pub text: String => [ Debuggable ],
     pub font_size: f32 => [ Debuggable ],
+    /// Text consisting of individually styled spans
+    pub rich_text: RichText => [ Debuggable ],
+    pub text_align: TextAlign => [ Debuggable ],
+    /// Aligns the text vertically within the widget bounds
+    pub vertical_align: VerticalAlign => [ Debuggable ],
+    /// Multiplier of the natural line height of the font
+    pub line_height: f32 => [ Debuggable ],
+    pub text_wrap: WrapMode => [ Debuggable ],
+    pub text_overflow: TextOverflow => [ Debuggable ],
+    /// Limits the number of displayed lines of text
+    pub max_lines: usize => [ Debuggable ],
+
+    /// Allows the text to be selected with the mouse and copied
+    pub selectable: () => [ Debuggable ],
+    /// The currently selected text
+    pub text_selection: TextSelection => [ Debuggable ],
+    /// The color of the selection highlight
+    pub selection_color: Srgba => [ Debuggable ],
 
     /// The color of the widget
     pub color: Srgba => [ Debuggable ],
+    /// Multiplies the alpha of everything drawn by the widget, such as for fading in and out
+    pub opacity: f32 => [ Debuggable ],
 
     pub filled_rect: FilledRect => [ Debuggable ],
+
+    /// A vector shape sized to the widget
+    pub shape: Shape => [ Debuggable ],
+}
+
+#[cfg(feature = "svg")]
+component! {
+    /// An svg rasterized at the pixel size of the widget and used as the image of its
+    /// [`filled_rect`]
+    pub fill_svg: crate::assets::svg::SvgSource => [ Debuggable ],
 }
 
 /// Spacing between a outer and inner bounds
@@ -0,0 +1,189 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::Future;
+use pin_project::pin_project;
+
+use super::Effect;
+
+type Request<Data> = Box<dyn FnOnce(&mut Data)>;
+
+/// Grants an async block access to `Data` between awaits.
+///
+/// Cloning the access allows passing it to nested futures.
+pub struct Access<Data> {
+    requests: Rc<RefCell<VecDeque<Request<Data>>>>,
+}
+
+impl<Data> Clone for Access<Data> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+        }
+    }
+}
+
+impl<Data: 'static> Access<Data> {
+    /// Runs `func` with mutable access to the data of the effect, resolving to its result.
+    ///
+    /// The future must be awaited inside the [`AsyncEffect`] which created the access, and
+    /// completes during the same poll.
+    pub fn with<F, R>(&self, func: F) -> With<Data, F, R>
+    where
+        F: 'static + FnOnce(&mut Data) -> R,
+        R: 'static,
+    {
+        With {
+            requests: self.requests.clone(),
+            func: Some(func),
+            result: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+/// Future returned by [`Access::with`]
+pub struct With<Data, F, R> {
+    requests: Rc<RefCell<VecDeque<Request<Data>>>>,
+    func: Option<F>,
+    result: Rc<RefCell<Option<R>>>,
+}
+
+impl<Data, F, R> Unpin for With<Data, F, R> {}
+
+impl<Data, F, R> Future for With<Data, F, R>
+where
+    Data: 'static,
+    F: 'static + FnOnce(&mut Data) -> R,
+    R: 'static,
+{
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<R> {
+        if let Some(value) = self.result.borrow_mut().take() {
+            return Poll::Ready(value);
+        }
+
+        // The effect runs the request and polls again without waiting for a wakeup
+        if let Some(func) = self.func.take() {
+            let result = self.result.clone();
+            self.requests.borrow_mut().push_back(Box::new(move |data| {
+                *result.borrow_mut() = Some(func(data));
+            }));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// An effect written as an async block.
+///
+/// The block receives an [`Access`] which lends the data of the effect, such as the
+/// [`Frame`](crate::Frame), between awaits. This allows multi-step flows to be written
+/// sequentially rather than as a chain of callbacks.
+///
+/// See [`Scope::spawn_async`](crate::Scope::spawn_async) for access to the scope of a widget.
+///
+/// ```ignore
+/// frame.spawn(AsyncEffect::new(|access: Access<Frame>| async move {
+///     access.with(|frame| show_spinner(frame)).await;
+///     let data = fetch().await;
+///     access.with(move |frame| update(frame, data)).await;
+/// }));
+/// ```
+#[pin_project]
+pub struct AsyncEffect<Data, Fut> {
+    requests: Rc<RefCell<VecDeque<Request<Data>>>>,
+    #[pin]
+    fut: Fut,
+}
+
+impl<Data, Fut> AsyncEffect<Data, Fut>
+where
+    Fut: Future,
+{
+    pub fn new(func: impl FnOnce(Access<Data>) -> Fut) -> Self {
+        let requests = Rc::new(RefCell::new(VecDeque::new()));
+
+        let fut = func(Access {
+            requests: requests.clone(),
+        });
+
+        Self { requests, fut }
+    }
+}
+
+impl<Data, Fut> Effect<Data> for AsyncEffect<Data, Fut>
+where
+    Fut: Future,
+{
+    type Output = Fut::Output;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        data: &mut Data,
+    ) -> Poll<Self::Output> {
+        let mut p = self.project();
+
+        loop {
+            if let Poll::Ready(value) = p.fut.as_mut().poll(context) {
+                return Poll::Ready(value);
+            }
+
+            let requests = std::mem::take(&mut *p.requests.borrow_mut());
+            if requests.is_empty() {
+                return Poll::Pending;
+            }
+
+            for request in requests {
+                request(data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::Executor;
+
+    use super::*;
+
+    #[test]
+    fn async_effect() {
+        let (tx, rx) = flume::unbounded();
+
+        let mut ex = Executor::new();
+
+        let handle = ex.spawner().spawn(AsyncEffect::new(
+            |access: Access<Vec<&'static str>>| async move {
+                access.with(|data| data.push("loading")).await;
+
+                let value: i32 = rx.recv_async().await.unwrap();
+
+                let len = access
+                    .with(move |data| {
+                        data.push("loaded");
+                        data.len()
+                    })
+                    .await;
+
+                value + len as i32
+            },
+        ));
+
+        let mut data = Vec::new();
+        ex.tick(&mut data);
+        assert_eq!(data, ["loading"]);
+
+        tx.send(5).unwrap();
+        ex.tick(&mut data);
+        assert_eq!(data, ["loading", "loaded"]);
+
+        assert_eq!(futures::executor::block_on(handle), Ok(7));
+    }
+}
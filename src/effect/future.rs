@@ -8,7 +8,9 @@ use pin_project::pin_project;
 
 use super::Effect;
 
-/// Execute an effect upon the world when the provided future resolves
+/// Execute an effect upon the world when the provided future resolves.
+///
+/// Completes with the value returned by `func`
 #[pin_project]
 pub struct FutureEffect<Fut, F> {
     #[pin]
@@ -25,17 +27,18 @@ impl<Fut, F> FutureEffect<Fut, F> {
     }
 }
 
-impl<Fut, F, Data> Effect<Data> for FutureEffect<Fut, F>
+impl<Fut, F, Data, T> Effect<Data> for FutureEffect<Fut, F>
 where
     Fut: Future,
-    F: FnOnce(&mut Data, Fut::Output),
+    F: FnOnce(&mut Data, Fut::Output) -> T,
 {
-    fn poll(self: Pin<&mut Self>, context: &mut Context, frame: &mut Data) -> Poll<()> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context, frame: &mut Data) -> Poll<T> {
         let p = self.project();
 
         let val = ready!(p.fut.poll(context));
-        (p.func.take().unwrap())(frame, val);
 
-        Poll::Ready(())
+        Poll::Ready((p.func.take().unwrap())(frame, val))
     }
 }
@@ -0,0 +1,116 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Future, Stream};
+use pin_project::pin_project;
+
+use super::Effect;
+
+/// Starts a future for each item in the stream, and executes an effect with the output.
+///
+/// Only the future of the latest item is kept. When a new item arrives while the previous future
+/// is still in flight, the previous future is dropped, which cancels it. This prevents stale
+/// results from overwriting newer ones, such as when searching as the user types.
+///
+/// Completes once the stream ends and the last future has completed.
+#[pin_project]
+pub struct LatestEffect<S, M, Fut, F> {
+    #[pin]
+    stream: S,
+    map: M,
+    #[pin]
+    in_flight: Option<Fut>,
+    func: F,
+    done: bool,
+}
+
+impl<S, M, Fut, F> LatestEffect<S, M, Fut, F> {
+    pub fn new(stream: S, map: M, func: F) -> Self {
+        Self {
+            stream,
+            map,
+            in_flight: None,
+            func,
+            done: false,
+        }
+    }
+}
+
+impl<S, M, Fut, F, Data> Effect<Data> for LatestEffect<S, M, Fut, F>
+where
+    S: Stream,
+    M: FnMut(S::Item) -> Fut,
+    Fut: Future,
+    F: FnMut(&mut Data, Fut::Output),
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>, data: &mut Data) -> Poll<()> {
+        let mut p = self.project();
+
+        while !*p.done {
+            match p.stream.as_mut().poll_next(context) {
+                // Replaces and cancels the previous future
+                Poll::Ready(Some(item)) => p.in_flight.set(Some((p.map)(item))),
+                Poll::Ready(None) => *p.done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        if let Some(fut) = p.in_flight.as_mut().as_pin_mut() {
+            if let Poll::Ready(value) = fut.poll(context) {
+                p.in_flight.set(None);
+                (p.func)(data, value);
+            }
+        }
+
+        if *p.done && p.in_flight.is_none() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::channel::oneshot;
+
+    use crate::executor::Executor;
+
+    use super::*;
+
+    #[test]
+    fn latest() {
+        let (tx, rx) = flume::unbounded();
+
+        let mut ex = Executor::new();
+        let handle = ex.spawner().spawn(LatestEffect::new(
+            rx.into_stream(),
+            |rx: oneshot::Receiver<i32>| rx,
+            |data: &mut Vec<i32>, value: Result<i32, _>| data.push(value.unwrap()),
+        ));
+
+        let mut data = Vec::new();
+
+        let (tx_1, rx_1) = oneshot::channel();
+        tx.send(rx_1).unwrap();
+        ex.tick(&mut data);
+
+        // Cancels the first request
+        let (tx_2, rx_2) = oneshot::channel();
+        tx.send(rx_2).unwrap();
+        ex.tick(&mut data);
+
+        assert_eq!(tx_1.send(1), Err(1));
+        tx_2.send(2).unwrap();
+        ex.tick(&mut data);
+        assert_eq!(data, [2]);
+
+        drop(tx);
+        ex.tick(&mut data);
+        assert!(handle.is_finished());
+    }
+}
@@ -1,4 +1,7 @@
+mod access;
 mod future;
+mod latest;
+mod select;
 mod stream;
 
 use std::{
@@ -6,7 +9,10 @@ use std::{
     task::{Context, Poll},
 };
 
+pub use access::{Access, AsyncEffect, With};
 pub use future::FutureEffect;
+pub use latest::LatestEffect;
+pub use select::{select, Select};
 pub use stream::StreamEffect;
 
 /// An asynchronous computation which has access to `Data` when polled
@@ -14,6 +20,10 @@ pub use stream::StreamEffect;
 ///
 /// Similar to [`std::future::Future`] but provides mutable access to shared data during poll
 pub trait Effect<Data> {
+    /// The value the effect completes with
+    type Output;
+
     /// Polls the effect
-    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>, data: &mut Data) -> Poll<()>;
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>, data: &mut Data)
+        -> Poll<Self::Output>;
 }
@@ -0,0 +1,49 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::future::Either;
+use pin_project::pin_project;
+
+use super::Effect;
+
+/// Polls two effects, completing with the output of the first to complete.
+///
+/// The other effect is dropped along with the select, which cancels it.
+pub fn select<A, B>(a: A, b: B) -> Select<A, B> {
+    Select { a, b }
+}
+
+/// Completes with the output of whichever effect completes first.
+///
+/// See [`select`]
+#[pin_project]
+pub struct Select<A, B> {
+    #[pin]
+    a: A,
+    #[pin]
+    b: B,
+}
+
+impl<Data, A, B> Effect<Data> for Select<A, B>
+where
+    A: Effect<Data>,
+    B: Effect<Data>,
+{
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        data: &mut Data,
+    ) -> Poll<Self::Output> {
+        let p = self.project();
+
+        if let Poll::Ready(value) = p.a.poll(context, data) {
+            return Poll::Ready(Either::Left(value));
+        }
+
+        p.b.poll(context, data).map(Either::Right)
+    }
+}
@@ -24,6 +24,8 @@ where
     S: Stream,
     F: FnMut(&mut Data, S::Item),
 {
+    type Output = ();
+
     fn poll(self: Pin<&mut Self>, context: &mut std::task::Context, frame: &mut Data) -> Poll<()> {
         let p = self.project();
 
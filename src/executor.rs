@@ -1,17 +1,21 @@
 use std::{
     cell::RefCell,
+    fmt::Display,
+    future::Future,
     marker::PhantomData,
     pin::Pin,
     rc::Rc,
     sync::{
         atomic::{AtomicBool, AtomicU32, Ordering},
-        Arc,
+        Arc, Weak,
     },
     task::{Context, Poll, Waker},
 };
 
 use futures::task::{waker, ArcWake, AtomicWaker};
+use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
+use pin_project::pin_project;
 use slotmap::{new_key_type, SlotMap};
 
 use crate::{effect::Effect, Frame};
@@ -24,55 +28,210 @@ const STATE_FINISHED: u32 = 2;
 
 struct TaskState {
     state: AtomicU32,
+    /// Woken when the task finishes or is aborted
+    join_waker: AtomicWaker,
+    /// Key of the task in the executor, assigned once it is added on the next tick
+    id: OnceCell<TaskId>,
 }
 
-pub struct TaskHandle {
+impl TaskState {
+    fn is_aborted(&self) -> bool {
+        self.state.load(Ordering::Acquire) == STATE_ABORTED
+    }
+
+    /// Transitions from pending to `state`, waking the handle
+    fn complete(&self, state: u32) -> bool {
+        let res = self
+            .state
+            .compare_exchange(STATE_PENDING, state, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+
+        if res {
+            self.join_waker.wake();
+        }
+
+        res
+    }
+}
+
+/// The task was aborted, or dropped before it completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Task was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Handle to a spawned task.
+///
+/// Awaiting the handle resolves to the output of the task, or [`Cancelled`] if the task was
+/// aborted. Dropping the handle detaches the task unless [`TaskHandle::abort_on_drop`] is used.
+pub struct TaskHandle<T = ()> {
     join_state: Arc<TaskState>,
+    output: Arc<Mutex<Option<T>>>,
+    shared: Weak<Shared>,
+    abort_on_drop: bool,
 }
 
-impl TaskHandle {
+impl<T> TaskHandle<T> {
+    /// Aborts the task.
+    ///
+    /// The task is dropped without being polled again on the next tick of the executor.
     pub fn abort(&self) {
-        self.join_state.state.store(STATE_ABORTED, Ordering::SeqCst);
+        abort_task(&self.join_state, &self.shared)
+    }
+
+    /// Abort the task when the handle is dropped
+    pub fn abort_on_drop(mut self) -> Self {
+        self.abort_on_drop = true;
+        self
+    }
+
+    /// Returns true if the task has completed, or was aborted
+    pub fn is_finished(&self) -> bool {
+        self.join_state.state.load(Ordering::Acquire) != STATE_PENDING
+    }
+
+    /// Returns a guard which aborts the task when dropped, and can be stored apart from the handle
+    pub(crate) fn abort_guard(&self) -> AbortGuard {
+        AbortGuard {
+            join_state: self.join_state.clone(),
+            shared: self.shared.clone(),
+            abort_on_drop: true,
+        }
+    }
+}
+
+fn abort_task(join_state: &TaskState, shared: &Weak<Shared>) {
+    if !join_state.complete(STATE_ABORTED) {
+        return;
+    }
+
+    // Tasks which have not been added to the executor yet are discarded when they are
+    if let (Some(shared), Some(&id)) = (shared.upgrade(), join_state.id.get()) {
+        shared.aborted.lock().push(id);
+        shared.wake();
+    }
+}
+
+/// Aborts a task when dropped
+pub(crate) struct AbortGuard {
+    join_state: Arc<TaskState>,
+    shared: Weak<Shared>,
+    abort_on_drop: bool,
+}
+
+impl AbortGuard {
+    /// Drops the guard without aborting the task
+    pub(crate) fn detach(mut self) {
+        self.abort_on_drop = false;
+    }
+}
+
+impl Drop for AbortGuard {
+    fn drop(&mut self) {
+        if self.abort_on_drop {
+            abort_task(&self.join_state, &self.shared)
+        }
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = Result<T, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.join_state.join_waker.register(cx.waker());
+
+        match self.join_state.state.load(Ordering::Acquire) {
+            STATE_PENDING => Poll::Pending,
+            STATE_ABORTED => Poll::Ready(Err(Cancelled)),
+            _ => Poll::Ready(Ok(self
+                .output
+                .lock()
+                .take()
+                .expect("TaskHandle polled after completion"))),
+        }
+    }
+}
+
+impl<T> Drop for TaskHandle<T> {
+    fn drop(&mut self) {
+        if self.abort_on_drop {
+            self.abort();
+        }
+    }
+}
+
+/// Stores the output of the effect for the [`TaskHandle`]
+#[pin_project]
+struct JoinEffect<E, T> {
+    #[pin]
+    effect: E,
+    output: Arc<Mutex<Option<T>>>,
+}
+
+impl<Data, E> Effect<Data> for JoinEffect<E, E::Output>
+where
+    E: Effect<Data>,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>, data: &mut Data) -> Poll<()> {
+        let p = self.project();
+        let output = futures::ready!(p.effect.poll(context, data));
+        *p.output.lock() = Some(output);
+        Poll::Ready(())
     }
 }
 
 struct Task<Data> {
-    effect: Pin<Box<dyn Effect<Data>>>,
+    effect: Pin<Box<dyn Effect<Data, Output = ()>>>,
     join_state: Arc<TaskState>,
     _marker: PhantomData<Data>,
 }
 
 impl<Data> Task<Data> {
-    fn new(effect: Pin<Box<dyn Effect<Data>>>) -> (Self, TaskHandle) {
+    fn new<E>(effect: E, shared: Weak<Shared>) -> (Self, TaskHandle<E::Output>)
+    where
+        E: 'static + Effect<Data>,
+        E::Output: 'static,
+    {
         let state = Arc::new(TaskState {
             state: AtomicU32::new(STATE_PENDING),
+            join_waker: AtomicWaker::new(),
+            id: OnceCell::new(),
         });
 
+        let output = Arc::new(Mutex::new(None));
+
         let handle = TaskHandle {
             join_state: state.clone(),
+            output: output.clone(),
+            shared,
+            abort_on_drop: false,
         };
 
         (
             Self {
-                effect,
+                effect: Box::pin(JoinEffect { effect, output }),
                 _marker: PhantomData,
-                join_state: state.clone(),
+                join_state: state,
             },
             handle,
         )
     }
 
     pub fn poll(&mut self, context: &mut Context, data: &mut Data) -> Poll<()> {
-        let state = self.join_state.state.load(Ordering::Acquire);
-
-        if state == STATE_ABORTED {
+        if self.join_state.is_aborted() {
             return Poll::Ready(());
         }
 
         if self.effect.as_mut().poll(context, data).is_ready() {
-            self.join_state
-                .state
-                .store(STATE_FINISHED, Ordering::Release);
+            self.join_state.complete(STATE_FINISHED);
 
             Poll::Ready(())
         } else {
@@ -81,6 +240,13 @@ impl<Data> Task<Data> {
     }
 }
 
+impl<Data> Drop for Task<Data> {
+    fn drop(&mut self) {
+        // Dropped before completing, such as when the executor is dropped
+        self.join_state.complete(STATE_ABORTED);
+    }
+}
+
 struct TaskWaker {
     id: TaskId,
     shared: Arc<Shared>,
@@ -95,6 +261,8 @@ impl ArcWake for TaskWaker {
 /// Is Send + Sync
 struct Shared {
     woken: AtomicBool,
+    /// Tasks which were aborted, and are removed on the next tick
+    aborted: Mutex<Vec<TaskId>>,
     executor_waker: AtomicWaker,
     ready: Mutex<Vec<TaskId>>,
 }
@@ -102,6 +270,10 @@ struct Shared {
 impl Shared {
     fn push_ready(&self, id: TaskId) {
         self.ready.lock().push(id);
+        self.wake();
+    }
+
+    fn wake(&self) {
         self.woken.store(true, Ordering::Relaxed);
         self.executor_waker.wake();
     }
@@ -113,20 +285,30 @@ pub struct Executor<Data = Frame> {
     processing: Vec<TaskId>,
 
     shared: Arc<Shared>,
+    /// Woken when a task is ready to be polled
+    waker: Option<Waker>,
     /// New tasks
     incoming: Rc<RefCell<Vec<Task<Data>>>>,
 }
 
 pub struct Spawner<Data> {
+    shared: Arc<Shared>,
     incoming: std::rc::Weak<RefCell<Vec<Task<Data>>>>,
 }
 
 impl<Data> Spawner<Data> {
-    pub fn spawn(&self, effect: impl 'static + Effect<Data>) -> TaskHandle {
+    pub fn spawn<E>(&self, effect: E) -> TaskHandle<E::Output>
+    where
+        E: 'static + Effect<Data>,
+        E::Output: 'static,
+    {
         let incoming = self.incoming.upgrade().expect("Executor dropped");
-        let (task, handle) = Task::new(Box::pin(effect));
+        let (task, handle) = Task::new(effect, Arc::downgrade(&self.shared));
         incoming.borrow_mut().push(task);
 
+        // New tasks are polled on the next tick
+        self.shared.wake();
+
         handle
     }
 }
@@ -137,6 +319,7 @@ impl<Data> Executor<Data> {
             executor_waker: AtomicWaker::new(),
             ready: Default::default(),
             woken: AtomicBool::new(false),
+            aborted: Default::default(),
         });
 
         let incoming = Default::default();
@@ -144,6 +327,7 @@ impl<Data> Executor<Data> {
         Self {
             tasks: SlotMap::with_key(),
             shared,
+            waker: None,
             processing: Vec::new(),
             incoming,
         }
@@ -152,10 +336,20 @@ impl<Data> Executor<Data> {
     /// Returns a thread local spawner
     pub fn spawner(&self) -> Spawner<Data> {
         Spawner {
+            shared: self.shared.clone(),
             incoming: Rc::downgrade(&self.incoming),
         }
     }
 
+    /// Sets the waker which is woken whenever a task is spawned or woken, and the executor needs
+    /// to be ticked.
+    ///
+    /// This allows driving the executor from an event loop, rather than ticking it continuously.
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.shared.executor_waker.register(&waker);
+        self.waker = Some(waker);
+    }
+
     pub fn poll_tick(&mut self, data: &mut Data, cx: &mut Context<'_>) -> Poll<()> {
         self.shared.executor_waker.register(cx.waker());
 
@@ -174,26 +368,52 @@ impl<Data> Executor<Data> {
     }
 
     pub fn tick(&mut self, data: &mut Data) {
+        // The waker is consumed when woken, and is registered again before the ready tasks are
+        // taken so that no wakeup is missed
+        if let Some(waker) = &self.waker {
+            self.shared.executor_waker.register(waker);
+        }
+
+        self.shared.woken.store(false, Ordering::Relaxed);
+
         {
             assert!(self.processing.is_empty());
             core::mem::swap(&mut *self.shared.ready.lock(), &mut self.processing);
         }
 
+        // Dropping a task may abort others, which must not happen while the list is locked
+        let aborted = core::mem::take(&mut *self.shared.aborted.lock());
+        for id in aborted {
+            self.tasks.remove(id);
+        }
+
         // Add new tasks
-        self.processing
-            .extend(self.incoming.borrow_mut().drain(..).map(|task| {
-                self.tasks.insert_with_key(|id| {
-                    let waker = waker(Arc::new(TaskWaker {
-                        id,
-                        shared: self.shared.clone(),
-                    }));
-
-                    (task, waker)
-                })
-            }));
+        self.processing.extend(
+            self.incoming
+                .borrow_mut()
+                .drain(..)
+                .filter(|task| !task.join_state.is_aborted())
+                .map(|task| {
+                    self.tasks.insert_with_key(|id| {
+                        // A task aborted before this is set is removed when it is first polled
+                        // below
+                        task.join_state.id.set(id).unwrap();
+
+                        let waker = waker(Arc::new(TaskWaker {
+                            id,
+                            shared: self.shared.clone(),
+                        }));
+
+                        (task, waker)
+                    })
+                }),
+        );
 
         for id in self.processing.drain(..) {
-            let (task, waker) = self.tasks.get_mut(id).unwrap();
+            // Removed tasks may still have been woken
+            let Some((task, waker)) = self.tasks.get_mut(id) else {
+                continue;
+            };
             let mut context = Context::from_waker(&*waker);
             tracing::trace!(?id, "Polling task");
 
@@ -242,4 +462,118 @@ mod tests {
         ex.tick(&mut data);
         assert_eq!(data, Some(5));
     }
+
+    #[test]
+    fn wakeups() {
+        struct Counter(AtomicU32);
+
+        impl ArcWake for Counter {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let (tx, rx) = flume::unbounded();
+
+        let counter = Arc::new(Counter(AtomicU32::new(0)));
+        let mut ex = Executor::new();
+        ex.set_waker(waker(counter.clone()));
+
+        ex.spawner().spawn(FutureEffect::new(
+            rx.into_recv_async(),
+            |data: &mut Option<i32>, val: Result<i32, flume::RecvError>| {
+                *data = Some(val.unwrap());
+            },
+        ));
+
+        // Spawning requires a tick
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        let mut data = None;
+        ex.tick(&mut data);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        tx.send(5).unwrap();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+
+        ex.tick(&mut data);
+        assert_eq!(data, Some(5));
+    }
+
+    #[test]
+    fn join() {
+        let (tx, rx) = flume::unbounded();
+
+        let mut ex = Executor::new();
+        let spawner = ex.spawner();
+
+        let handle = spawner.spawn(FutureEffect::new(
+            rx.into_recv_async(),
+            |data: &mut i32, val: Result<i32, flume::RecvError>| {
+                *data += 1;
+                val.unwrap() * 2
+            },
+        ));
+
+        let result = Arc::new(Mutex::new(None));
+        spawner.spawn(FutureEffect::new(handle, {
+            let result = result.clone();
+            move |_: &mut i32, val| *result.lock() = Some(val)
+        }));
+
+        let mut data = 0;
+        ex.tick(&mut data);
+        tx.send(4).unwrap();
+
+        ex.tick(&mut data);
+        ex.tick(&mut data);
+        assert_eq!(data, 1);
+        assert_eq!(*result.lock(), Some(Ok(8)));
+        assert!(ex.tasks.is_empty());
+    }
+
+    #[test]
+    fn abort() {
+        let (_tx, rx) = flume::unbounded::<i32>();
+
+        let mut ex = Executor::new();
+        let spawner = ex.spawner();
+
+        let handle = spawner.spawn(FutureEffect::new(rx.into_recv_async(), |_: &mut (), _| {}));
+        let detached = spawner.spawn(FutureEffect::new(
+            futures::future::pending::<()>(),
+            |_: &mut (), _| {},
+        ));
+        let dropped = spawner
+            .spawn(FutureEffect::new(
+                futures::future::pending::<()>(),
+                |_: &mut (), _| {},
+            ))
+            .abort_on_drop();
+
+        ex.tick(&mut ());
+        assert_eq!(ex.tasks.len(), 3);
+
+        // The task is never woken, but is removed regardless
+        handle.abort();
+        drop(dropped);
+        drop(detached);
+
+        ex.tick(&mut ());
+        assert_eq!(ex.tasks.len(), 1);
+        assert!(ex.shared.aborted.lock().is_empty());
+
+        // Aborted before being added to the executor
+        let pending = spawner.spawn(FutureEffect::new(
+            futures::future::pending::<()>(),
+            |_: &mut (), _| {},
+        ));
+        pending.abort();
+
+        ex.tick(&mut ());
+        assert_eq!(ex.tasks.len(), 1);
+        assert!(ex.shared.aborted.lock().is_empty());
+
+        assert_eq!(futures::executor::block_on(handle), Err(Cancelled));
+    }
 }
@@ -0,0 +1,85 @@
+use crate::assets::fs::BytesFromFile;
+
+/// Loads a font from a file
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct FontFromFile {
+    pub path: BytesFromFile,
+}
+
+/// The weight of a font face, using the common `100..=900` scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FontWeight(pub u16);
+
+impl FontWeight {
+    pub const THIN: Self = Self(100);
+    pub const EXTRA_LIGHT: Self = Self(200);
+    pub const LIGHT: Self = Self(300);
+    pub const REGULAR: Self = Self(400);
+    pub const MEDIUM: Self = Self(500);
+    pub const SEMI_BOLD: Self = Self(600);
+    pub const BOLD: Self = Self(700);
+    pub const EXTRA_BOLD: Self = Self(800);
+    pub const BLACK: Self = Self(900);
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        Self::REGULAR
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+}
+
+/// Describes a single face of a font family
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct FontFaceDesc {
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    pub font: FontFromFile,
+}
+
+impl FontFaceDesc {
+    pub fn new(weight: FontWeight, style: FontStyle, font: FontFromFile) -> Self {
+        Self {
+            weight,
+            style,
+            font,
+        }
+    }
+}
+
+/// Loads a family of font faces and the families to fall back to for missing characters
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct FontFamilyDesc {
+    pub name: String,
+    pub faces: Vec<FontFaceDesc>,
+    /// Consulted in order for characters not covered by this family
+    pub fallback: Vec<FontFamilyDesc>,
+}
+
+impl FontFamilyDesc {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            faces: Vec::new(),
+            fallback: Vec::new(),
+        }
+    }
+
+    /// Add a face to the family
+    pub fn with_face(mut self, weight: FontWeight, style: FontStyle, font: FontFromFile) -> Self {
+        self.faces.push(FontFaceDesc::new(weight, style, font));
+        self
+    }
+
+    /// Append a family to the fallback chain
+    pub fn with_fallback(mut self, family: FontFamilyDesc) -> Self {
+        self.fallback.push(family);
+        self
+    }
+}
@@ -2,6 +2,7 @@ use flax::{Entity, World};
 
 use crate::{
     assets::AssetCache,
+    background::{BackgroundTask, ThreadPool},
     effect::Effect,
     executor::{Spawner, TaskHandle},
     Scope, Widget,
@@ -11,6 +12,7 @@ pub struct Frame {
     pub world: World,
     pub spawner: Spawner<Self>,
     pub assets: AssetCache,
+    pub pool: ThreadPool,
 }
 
 impl Frame {
@@ -31,10 +33,25 @@ impl Frame {
     }
 
     #[inline]
-    pub fn spawn(&self, effect: impl 'static + Effect<Frame>) -> TaskHandle {
+    pub fn spawn<E>(&self, effect: E) -> TaskHandle<E::Output>
+    where
+        E: 'static + Effect<Frame>,
+        E::Output: 'static,
+    {
         self.spawner.spawn(effect)
     }
 
+    /// Runs `func` on a worker of the thread pool, keeping the UI thread responsive.
+    ///
+    /// The returned task can be awaited in an effect to apply the result.
+    pub fn spawn_background<F, T>(&self, func: F) -> BackgroundTask<T>
+    where
+        F: 'static + Send + FnOnce() -> T,
+        T: 'static + Send,
+    {
+        self.pool.spawn(func)
+    }
+
     /// Scope the frame to a particular *existing* entity
     pub(crate) fn scoped(&mut self, id: Entity) -> Option<Scope<'_>> {
         Scope::try_from_id(self, id)
@@ -4,14 +4,27 @@ use flax::{
     filter::All,
     Component, Entity, EntityIds, EntityRef, Fetch, FetchExt, Query, Topo,
 };
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use glam::Vec2;
-use winit::event::{ElementState, KeyboardInput, MouseButton};
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode};
 
 use crate::{
-    components::{rect, screen_position, Rect},
+    clipboard::Clipboard,
+    components::{rect, rich_text, screen_position, selectable, text, text_selection, Rect},
+    text::{word_at, TextSelection},
+    wgpu::components::shaped_text,
     Frame, Scope,
 };
 
+/// Maximum time between clicks to count as a double or triple click
+const MULTI_CLICK_TIME: Duration = Duration::from_millis(500);
+/// Maximum distance the cursor may move between clicks of a double or triple click
+const MULTI_CLICK_DISTANCE: f32 = 4.0;
+
 pub struct Input {}
 
 #[derive(Fetch)]
@@ -39,23 +52,84 @@ struct FocusedEntity {
     sticky: bool,
 }
 
+#[derive(Debug, Clone, Copy)]
+struct Click {
+    time: Instant,
+    pos: Vec2,
+    /// Number of consecutive clicks
+    count: u32,
+}
+
 pub struct InputState {
     focused: Option<FocusedEntity>,
     pos: Vec2,
+    modifiers: ModifiersState,
     intersect_query: Query<IntersectQuery, All, Topo>,
+
+    clipboard: Arc<dyn Clipboard>,
+    last_click: Option<Click>,
+    /// The entity whose text is currently selected
+    selected: Option<Entity>,
+    /// The selection is being extended by dragging the cursor
+    dragging: bool,
 }
 
 impl InputState {
-    pub fn new(pos: Vec2) -> Self {
+    pub fn new(pos: Vec2, clipboard: Arc<dyn Clipboard>) -> Self {
         Self {
             focused: None,
             pos,
+            modifiers: ModifiersState::empty(),
             intersect_query: Query::new(IntersectQuery::new()).topo(child_of),
+            clipboard,
+            last_click: None,
+            selected: None,
+            dragging: false,
         }
     }
 
-    pub fn on_cursor_move(&mut self, pos: Vec2) {
+    /// Returns true if moving the cursor changed the selection, and the frame needs to be updated
+    pub fn on_cursor_move(&mut self, frame: &mut Frame, pos: Vec2) -> bool {
         self.pos = pos;
+
+        if !self.dragging {
+            return false;
+        }
+
+        let Some(id) = self.selected else {
+            return false;
+        };
+
+        let selection = {
+            let Ok(entity) = frame.world().entity(id) else {
+                return false;
+            };
+
+            let Some((_, index)) = text_index_at(&entity, pos) else {
+                return false;
+            };
+
+            let current = entity.get_copy(text_selection()).ok();
+            let anchor = current.map(|v| v.anchor).unwrap_or(index);
+
+            let selection = TextSelection::new(anchor, index);
+            if current == Some(selection) {
+                return false;
+            }
+
+            selection
+        };
+
+        frame
+            .world_mut()
+            .set(id, text_selection(), selection)
+            .unwrap();
+
+        true
+    }
+
+    pub fn on_modifiers_changed(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
     }
 
     pub fn on_mouse_input(&mut self, frame: &mut Frame, state: ElementState, input: MouseButton) {
@@ -88,6 +162,10 @@ impl InputState {
             (ElementState::Released, _, _) => {}
         }
 
+        if input == MouseButton::Left {
+            self.update_selection(frame, state, intersect);
+        }
+
         // Send the event to the intersected entity
 
         if let Some(id) = intersect {
@@ -101,6 +179,14 @@ impl InputState {
     }
 
     pub fn on_keyboard_input(&mut self, frame: &mut Frame, input: KeyboardInput) {
+        let copy = input.state == ElementState::Pressed
+            && input.virtual_keycode == Some(VirtualKeyCode::C)
+            && (self.modifiers.ctrl() || self.modifiers.logo());
+
+        if copy {
+            self.copy_selection(frame);
+        }
+
         if let Some(cur) = &self.focused {
             tracing::info!(?cur, "sending keyboard input event");
             // TODO
@@ -112,6 +198,113 @@ impl InputState {
         }
     }
 
+    /// Starts a new selection when pressing on selectable text.
+    ///
+    /// Double clicking selects a word, and triple clicking selects a line.
+    fn update_selection(&mut self, frame: &mut Frame, state: ElementState, target: Option<Entity>) {
+        if state == ElementState::Released {
+            self.dragging = false;
+            return;
+        }
+
+        let count = self.register_click();
+
+        // Pressing anywhere clears the current selection
+        if let Some(id) = self.selected.take() {
+            if frame.world().is_alive(id) {
+                frame
+                    .world_mut()
+                    .set(id, text_selection(), TextSelection::default())
+                    .unwrap();
+            }
+        }
+
+        let Some(id) = target else {
+            return;
+        };
+
+        let range = {
+            let entity = frame.world().entity(id).unwrap();
+            if !entity.has(selectable()) {
+                return;
+            }
+
+            let Some((text, index)) = text_index_at(&entity, self.pos) else {
+                return;
+            };
+
+            match count {
+                1 => index..index,
+                2 => word_at(&text, index),
+                _ => {
+                    let shaped = entity.get(shaped_text()).unwrap();
+                    shaped
+                        .line_of(index)
+                        .map(|v| v.text.clone())
+                        .unwrap_or(index..index)
+                }
+            }
+        };
+
+        frame
+            .world_mut()
+            .set(
+                id,
+                text_selection(),
+                TextSelection::new(range.start, range.end),
+            )
+            .unwrap();
+
+        self.selected = Some(id);
+        self.dragging = true;
+    }
+
+    /// Returns the number of consecutive clicks at the cursor, cycling after three
+    fn register_click(&mut self) -> u32 {
+        let now = Instant::now();
+
+        let count = match self.last_click {
+            Some(click)
+                if now.duration_since(click.time) < MULTI_CLICK_TIME
+                    && click.pos.distance(self.pos) < MULTI_CLICK_DISTANCE =>
+            {
+                click.count % 3 + 1
+            }
+            _ => 1,
+        };
+
+        self.last_click = Some(Click {
+            time: now,
+            pos: self.pos,
+            count,
+        });
+
+        count
+    }
+
+    /// Copies the selected text to the clipboard
+    fn copy_selection(&self, frame: &Frame) {
+        let Some(entity) = self.selected.and_then(|id| frame.world().entity(id).ok()) else {
+            return;
+        };
+
+        let Ok(selection) = entity.get_copy(text_selection()) else {
+            return;
+        };
+
+        let Some(text) = entity_text(&entity) else {
+            return;
+        };
+
+        match text.get(selection.range()) {
+            Some(selected) if !selected.is_empty() => {
+                tracing::info!(%entity, "Copied selection");
+                self.clipboard.set(selected.to_string());
+            }
+            _ => {}
+        }
+    }
+
     fn set_focused(&mut self, frame: &Frame, focused: Option<Entity>) {
         let cur = self.focused.as_ref().map(|v| v.id);
 
@@ -142,9 +335,205 @@ impl InputState {
     }
 }
 
+/// Returns the plain or rich text of the entity
+fn entity_text(entity: &EntityRef) -> Option<String> {
+    if let Ok(text) = entity.get(text()) {
+        return Some(text.clone());
+    }
+
+    entity.get(rich_text()).ok().map(|v| v.text())
+}
+
+/// Returns the text of the entity and the byte offset under the cursor
+fn text_index_at(entity: &EntityRef, cursor_pos: Vec2) -> Option<(String, usize)> {
+    let text = entity_text(entity)?;
+    let shaped = entity.get(shaped_text()).ok()?;
+
+    // Glyphs are relative to the rect of the widget
+    let local_pos =
+        cursor_pos - *entity.get(screen_position()).ok()? - entity.get(rect()).ok()?.pos();
+
+    let index = shaped.hit(&text, local_pos);
+    Some((text, index))
+}
+
 component! {
     pub focus_sticky: (),
     pub on_focus: Box<dyn FnMut(&Frame, &EntityRef, bool) + Send + Sync>,
     pub on_mouse_input: Box<dyn FnMut(&Frame, &EntityRef, ElementState,MouseButton) + Send + Sync>,
     pub on_keyboard_input: Box<dyn FnMut(&Frame, &EntityRef, KeyboardInput) + Send + Sync>,
 }
+
+#[cfg(test)]
+mod test {
+    use flax::World;
+    use glam::vec2;
+
+    use crate::{
+        assets::{fs::BytesFromFile, AssetCache},
+        background::ThreadPool,
+        clipboard::MemoryClipboard,
+        executor::Executor,
+        font::FontFromFile,
+        text::TextLayout,
+        wgpu::shaping::{shape_text, ShapedText},
+    };
+
+    use super::*;
+
+    const TEXT: &str = "lorem ipsum\ndolor sit";
+    /// Where the text is placed on the screen
+    const ORIGIN: Vec2 = Vec2::new(10.0, 20.0);
+
+    struct TestInput {
+        frame: Frame,
+        input: InputState,
+        clipboard: MemoryClipboard,
+        shaped: ShapedText,
+        id: Entity,
+    }
+
+    impl TestInput {
+        fn new() -> Self {
+            let assets = AssetCache::new();
+            let font = assets
+                .try_load(&FontFromFile {
+                    path: BytesFromFile("assets/fonts/Inter/static/Inter-Regular.ttf".into()),
+                })
+                .unwrap();
+
+            let shaped = shape_text(&[font], TEXT, 16.0, None, &TextLayout::default());
+
+            let mut frame = Frame {
+                world: World::new(),
+                spawner: Executor::new().spawner(),
+                assets,
+                pool: ThreadPool::new(1),
+            };
+
+            let id = Entity::builder()
+                .set(text(), TEXT.to_string())
+                .set(rect(), Rect::from_size_pos(shaped.size, Vec2::ZERO))
+                .set(screen_position(), ORIGIN)
+                .set(shaped_text(), shaped.clone())
+                .set(selectable(), ())
+                .spawn(frame.world_mut());
+
+            let clipboard = MemoryClipboard::new();
+            let input = InputState::new(Vec2::ZERO, Arc::new(clipboard.clone()));
+
+            Self {
+                frame,
+                input,
+                clipboard,
+                shaped,
+                id,
+            }
+        }
+
+        /// Returns the screen position of the leading edge of the character at `index`
+        fn pos_of(&self, index: usize) -> Vec2 {
+            let glyph = self
+                .shaped
+                .glyphs
+                .iter()
+                .find(|v| v.cluster == index)
+                .unwrap();
+
+            let line = self.shaped.line_of(index).unwrap();
+            ORIGIN + vec2(glyph.pos.x + 1.0, line.top + 1.0)
+        }
+
+        fn move_to(&mut self, pos: Vec2) -> bool {
+            self.input.on_cursor_move(&mut self.frame, pos)
+        }
+
+        fn mouse(&mut self, state: ElementState) {
+            self.input
+                .on_mouse_input(&mut self.frame, state, MouseButton::Left);
+        }
+
+        fn click(&mut self, pos: Vec2) {
+            self.move_to(pos);
+            self.mouse(ElementState::Pressed);
+            self.mouse(ElementState::Released);
+        }
+
+        #[allow(deprecated)]
+        fn copy(&mut self) {
+            self.input.on_modifiers_changed(ModifiersState::CTRL);
+            self.input.on_keyboard_input(
+                &mut self.frame,
+                KeyboardInput {
+                    scancode: 0,
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::C),
+                    modifiers: ModifiersState::CTRL,
+                },
+            );
+            self.input.on_modifiers_changed(ModifiersState::empty());
+        }
+
+        fn selection(&self) -> TextSelection {
+            *self.frame.world().get(self.id, text_selection()).unwrap()
+        }
+    }
+
+    #[test]
+    fn click_selection() {
+        let mut input = TestInput::new();
+        let pos = input.pos_of(8);
+
+        // Each consecutive click at the same position selects more
+        input.click(pos);
+        assert_eq!(input.selection(), TextSelection::new(8, 8));
+
+        input.click(pos);
+        assert_eq!(input.selection(), TextSelection::new(6, 11));
+
+        input.click(pos);
+        assert_eq!(input.selection(), TextSelection::new(0, 12));
+
+        input.copy();
+        assert_eq!(input.clipboard.get().as_deref(), Some("lorem ipsum\n"));
+
+        // Clicking outside the text clears the selection
+        input.input.last_click = None;
+        input.click(ORIGIN + input.shaped.size + 10.0);
+        assert_eq!(input.selection(), TextSelection::default());
+
+        // An empty selection leaves the clipboard as is
+        input.copy();
+        assert_eq!(input.clipboard.get().as_deref(), Some("lorem ipsum\n"));
+    }
+
+    #[test]
+    fn drag_selection() {
+        let mut input = TestInput::new();
+
+        // Moving without dragging does not change anything
+        assert!(!input.move_to(input.pos_of(6)));
+        input.mouse(ElementState::Pressed);
+        assert_eq!(input.selection(), TextSelection::new(6, 6));
+
+        // Dragging extends the selection from where it was started, across lines
+        assert!(input.move_to(input.pos_of(15)));
+        assert_eq!(input.selection(), TextSelection::new(6, 15));
+
+        // Moving within the same character keeps the selection
+        assert!(!input.move_to(input.pos_of(15) + vec2(0.5, 0.0)));
+
+        assert!(input.move_to(input.pos_of(2)));
+        assert_eq!(input.selection(), TextSelection::new(6, 2));
+
+        input.move_to(input.pos_of(15));
+        input.mouse(ElementState::Released);
+
+        // Moving after releasing leaves the selection as is
+        assert!(!input.move_to(input.pos_of(18)));
+        assert_eq!(input.selection(), TextSelection::new(6, 15));
+
+        input.copy();
+        assert_eq!(input.clipboard.get().as_deref(), Some("ipsum\ndol"));
+    }
+}
@@ -4,9 +4,25 @@ use itertools::Itertools;
 
 use crate::{
     components::{self, children, layout, margin, padding, Edges, Rect},
+    text::{TextLayout, TextOverflow},
     unit::Unit,
 };
 
+/// Measures the text of leaf widgets during layout.
+///
+/// Layout does not know about fonts, so measuring is left to the renderer, such as
+/// [`TextShaper`](crate::wgpu::shaping::TextShaper) for the wgpu backend.
+pub trait TextMeasure: Send + Sync {
+    /// Returns the size of the text of `entity` when wrapped to `max_width`, or `None` if the
+    /// entity has no text which can be measured
+    fn measure(
+        &self,
+        entity: &EntityRef,
+        max_width: Option<f32>,
+        layout: &TextLayout,
+    ) -> Option<Vec2>;
+}
+
 #[derive(Debug, Clone)]
 struct MarginCursor {
     pending_margin: f32,
@@ -126,13 +142,15 @@ impl Layout {
     fn apply(
         &self,
         world: &World,
+        measure: &dyn TextMeasure,
         entity: &EntityRef,
         content_area: Rect,
         constraints: LayoutLimits,
     ) -> Rect {
         let (axis, cross_axis) = self.direction.axis();
 
-        let (_, total_preferred_size, blocks) = self.query_size(world, entity, content_area);
+        let (_, total_preferred_size, blocks) =
+            self.query_size(world, measure, entity, content_area);
 
         // Size remaining if everything got at least its preferred size
         let total_preferred_size = total_preferred_size.size().dot(axis);
@@ -192,6 +210,7 @@ impl Layout {
                 // let local_rect = widget_outer_bounds(world, &child, size);
                 let block = update_subtree(
                     world,
+                    measure,
                     &entity,
                     // Supply our whole inner content area
                     content_area,
@@ -237,6 +256,7 @@ impl Layout {
     pub(crate) fn query_size<'a>(
         &self,
         world: &'a World,
+        measure: &dyn TextMeasure,
         entity: &EntityRef,
         inner_rect: Rect,
     ) -> (Rect, Rect, Vec<(EntityRef<'a>, SizeQuery)>) {
@@ -266,7 +286,7 @@ impl Layout {
                 let entity = world.entity(child).expect("Invalid child");
 
                 // let local_rect = widget_outer_bounds(world, &child, size);
-                let query = query_size(world, &entity, content_area);
+                let query = query_size(world, measure, &entity, content_area);
 
                 min_cursor.put(&Block::new(query.min, query.margin));
                 preferred_cursor.put(&Block::new(query.preferred, query.margin));
@@ -284,7 +304,12 @@ pub struct SizeQuery {
     margin: Edges,
 }
 
-pub fn query_size(world: &World, entity: &EntityRef, content_area: Rect) -> SizeQuery {
+pub fn query_size(
+    world: &World,
+    measure: &dyn TextMeasure,
+    entity: &EntityRef,
+    content_area: Rect,
+) -> SizeQuery {
     let margin = entity
         .get(components::margin())
         .ok()
@@ -304,7 +329,8 @@ pub fn query_size(world: &World, entity: &EntityRef, content_area: Rect) -> Size
         // For a given layout use the largest size that fits within the constraints and then
         // potentially shrink it down.
 
-        let (min, preferred, _) = layout.query_size(world, entity, content_area.inset(&padding));
+        let (min, preferred, _) =
+            layout.query_size(world, measure, entity, content_area.inset(&padding));
 
         SizeQuery {
             min: min.pad(&padding),
@@ -316,7 +342,7 @@ pub fn query_size(world: &World, entity: &EntityRef, content_area: Rect) -> Size
     else if let Ok(children) = entity.get(children()) {
         todo!()
     } else {
-        let (min_size, preferred_size) = resolve_size(entity, content_area);
+        let (min_size, preferred_size) = resolve_size(measure, entity, content_area);
 
         let min_offset = resolve_pos(entity, content_area, min_size);
         let preferred_offset = resolve_pos(entity, content_area, preferred_size);
@@ -359,6 +385,7 @@ impl Block {
 #[must_use = "This function does not mutate the entity"]
 pub(crate) fn update_subtree(
     world: &World,
+    measure: &dyn TextMeasure,
     entity: &EntityRef,
     // The area in which children can be placed without clipping
     content_area: Rect,
@@ -387,6 +414,7 @@ pub(crate) fn update_subtree(
         let rect = layout
             .apply(
                 world,
+                measure,
                 entity,
                 content_area.inset(&padding),
                 LayoutLimits {
@@ -430,7 +458,7 @@ pub(crate) fn update_subtree(
             .inset(&padding);
             assert_eq!(content_area.size(), constraints.max);
 
-            let res = update_subtree(world, &entity, content_area, constraints);
+            let res = update_subtree(world, measure, &entity, content_area, constraints);
 
             entity.update_dedup(components::rect(), res.rect);
         }
@@ -439,7 +467,7 @@ pub(crate) fn update_subtree(
             margin,
         }
     } else {
-        let size = resolve_size(entity, content_area)
+        let size = resolve_size(measure, entity, content_area)
             .1
             .clamp(limits.min, limits.max);
 
@@ -452,7 +480,7 @@ pub(crate) fn update_subtree(
     }
 }
 
-fn resolve_size(entity: &EntityRef, content_area: Rect) -> (Vec2, Vec2) {
+fn resolve_size(measure: &dyn TextMeasure, entity: &EntityRef, content_area: Rect) -> (Vec2, Vec2) {
     let parent_size = content_area.size();
     let min_size = entity
         .get(components::min_size())
@@ -467,6 +495,22 @@ fn resolve_size(entity: &EntityRef, content_area: Rect) -> (Vec2, Vec2) {
         .resolve(parent_size)
         .max(min_size);
 
+    // Text is sized to fit its contents, unless it is truncated to an explicit size
+    let text_layout = TextLayout::from_entity(entity);
+    let bounded = text_layout.overflow != TextOverflow::Visible;
+
+    let max_width = if bounded && size.x > 0.0 {
+        Some(size.x)
+    } else {
+        (parent_size.x > 0.0).then_some(parent_size.x)
+    };
+
+    let size = match measure.measure(entity, max_width, &text_layout) {
+        Some(text_size) if bounded => Vec2::select(size.cmpgt(Vec2::ZERO), size, text_size),
+        Some(text_size) => size.max(text_size),
+        None => size,
+    };
+
     (min_size, size)
 }
 
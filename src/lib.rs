@@ -1,21 +1,26 @@
+pub mod animation;
 mod app;
 pub mod assets;
+pub mod background;
+pub mod clipboard;
 pub mod components;
 pub mod effect;
 pub mod executor;
+pub mod font;
 mod frame;
 pub mod input;
 pub mod layout;
 mod scope;
 pub mod shapes;
 pub mod systems;
+pub mod text;
 pub mod time;
 pub mod unit;
 pub mod wgpu;
 mod widget;
 
 pub use app::App;
-pub use effect::{FutureEffect, StreamEffect};
+pub use effect::{AsyncEffect, FutureEffect, StreamEffect};
 pub use frame::Frame;
-pub use scope::Scope;
+pub use scope::{Scope, ScopeAccess};
 pub use widget::{Widget, WidgetCollection};
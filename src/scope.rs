@@ -1,12 +1,30 @@
 use std::{
+    future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use flax::{child_of, Component, ComponentValue, Entity, EntityBuilder, EntityRef, EntityRefMut};
+use flax::{
+    child_of, component, Component, ComponentValue, Entity, EntityBuilder, EntityRef, EntityRefMut,
+};
 use pin_project::pin_project;
+use slotmap::{new_key_type, SlotMap};
+
+use crate::{
+    animation::{Animation, Tween, Tweenable},
+    assets::AssetCache,
+    components::children,
+    effect::{Access, AsyncEffect, Effect, FutureEffect},
+    executor::{AbortGuard, TaskHandle},
+    Frame, Widget,
+};
 
-use crate::{assets::AssetCache, components::children, effect::Effect, Frame, Widget};
+new_key_type! { struct BackgroundTaskId; }
+
+component! {
+    /// Background work of the widget, cancelled when the widget is despawned
+    background_tasks: SlotMap<BackgroundTaskId, AbortGuard>,
+}
 
 /// The scope within a [`Widget`][crate::Widget] is mounted or modified
 pub struct Scope<'a> {
@@ -123,17 +141,115 @@ impl<'a> Scope<'a> {
         id
     }
 
-    /// Spawns an effect scoped to the lifetime of this entity and scope
-    pub fn spawn(&mut self, effect: impl 'static + for<'x> Effect<Scope<'x>>) {
+    /// Spawns an effect scoped to the lifetime of this entity and scope.
+    ///
+    /// The output of the effect is discarded, as it may borrow from the scope.
+    pub fn spawn(&mut self, effect: impl 'static + for<'x> Effect<Scope<'x>>) -> TaskHandle {
         self.frame.spawn(ScopedEffect {
             id: self.id,
             effect,
+        })
+    }
+
+    /// Spawns an effect written as an async block, scoped to the lifetime of this entity.
+    ///
+    /// The block receives a [`ScopeAccess`] which grants access to the scope between awaits.
+    /// Resolves to `None` if the entity is despawned before the block completes.
+    ///
+    /// ```ignore
+    /// scope.spawn_async(|scope| async move {
+    ///     scope.with(|scope| scope.set(text(), "Loading...".into())).await;
+    ///     let data = fetch().await;
+    ///     scope.with(move |scope| scope.set(text(), data)).await;
+    /// });
+    /// ```
+    pub fn spawn_async<F, Fut>(&mut self, func: F) -> TaskHandle<Option<Fut::Output>>
+    where
+        F: FnOnce(ScopeAccess) -> Fut,
+        Fut: 'static + Future,
+        Fut::Output: 'static,
+    {
+        let id = self.id;
+        self.frame.spawn(ScopedAsyncEffect {
+            id,
+            effect: AsyncEffect::new(|access| func(ScopeAccess { id, access })),
+        })
+    }
+
+    /// Animates `component` towards the target of `tween`, updating it each frame.
+    ///
+    /// The animation stops when the entity is despawned. The returned handle can be awaited for
+    /// the completion of the animation.
+    pub fn animate<T>(&mut self, component: Component<T>, tween: Tween<T>) -> TaskHandle
+    where
+        T: ComponentValue + Tweenable,
+    {
+        self.spawn(Animation::new(component, tween))
+    }
+
+    /// Runs `func` on a worker of the thread pool, and applies the result to the scope using
+    /// `apply`.
+    ///
+    /// The work is cancelled if the entity is despawned before a worker has picked it up, and the
+    /// result is discarded if it is despawned before the work completes. In both cases the
+    /// returned handle resolves to [`Cancelled`](crate::executor::Cancelled).
+    pub fn spawn_background<F, T>(
+        &mut self,
+        func: F,
+        apply: impl 'static + FnOnce(&mut Scope<'_>, T),
+    ) -> TaskHandle
+    where
+        F: 'static + Send + FnOnce() -> T,
+        T: 'static + Send,
+    {
+        let task = self.frame.spawn_background(func);
+
+        // The effect is not polled again once the entity is despawned, so the task is aborted
+        // along with the entity to drop the queued work
+        self.flush();
+        let mut tasks = self
+            .frame
+            .world
+            .entry(self.id, background_tasks())
+            .unwrap()
+            .or_default();
+
+        let mut handle = None;
+        tasks.insert_with_key(|key| {
+            let effect = FutureEffect::new(task, move |scope: &mut Scope<'_>, result| {
+                // Nothing is left to cancel once the result is delivered
+                if let Ok(mut tasks) = scope.frame.world().get_mut(scope.id, background_tasks()) {
+                    if let Some(guard) = tasks.remove(key) {
+                        guard.detach();
+                    }
+                }
+
+                match result {
+                    Ok(value) => apply(scope, value),
+                    Err(err) => tracing::error!("Background task failed: {err}"),
+                }
+            });
+
+            let spawned = self.frame.spawner.spawn(ScopedEffect {
+                id: self.id,
+                effect,
+            });
+
+            let guard = spawned.abort_guard();
+            handle = Some(spawned);
+            guard
         });
+
+        handle.unwrap()
     }
 
     /// Spawns an effect which is *not* scoped to the widget
-    pub fn spawn_unscoped(&mut self, effect: impl 'static + for<'x> Effect<Frame>) {
-        self.frame.spawn(effect);
+    pub fn spawn_unscoped<E>(&mut self, effect: E) -> TaskHandle<E::Output>
+    where
+        E: 'static + Effect<Frame>,
+        E::Output: 'static,
+    {
+        self.frame.spawn(effect)
     }
 
     pub fn id(&self) -> Entity {
@@ -151,6 +267,54 @@ impl Drop for Scope<'_> {
     }
 }
 
+/// Grants an async block access to the [`Scope`] of a widget between awaits.
+///
+/// See [`Scope::spawn_async`]
+#[derive(Clone)]
+pub struct ScopeAccess {
+    id: Entity,
+    access: Access<Frame>,
+}
+
+impl ScopeAccess {
+    pub fn id(&self) -> Entity {
+        self.id
+    }
+
+    /// Runs `func` with the scope of the widget, resolving to its result
+    pub fn with<R>(
+        &self,
+        func: impl 'static + FnOnce(&mut Scope<'_>) -> R,
+    ) -> impl Future<Output = R>
+    where
+        R: 'static,
+    {
+        let id = self.id;
+        let scope = self
+            .access
+            .with(move |frame| Scope::try_from_id(frame, id).map(|mut scope| func(&mut scope)));
+
+        async move {
+            match scope.await {
+                Some(value) => value,
+                // The effect completes once it sees that the entity was despawned
+                None => futures::future::pending().await,
+            }
+        }
+    }
+
+    /// Runs `func` with the frame
+    pub fn with_frame<R>(
+        &self,
+        func: impl 'static + FnOnce(&mut Frame) -> R,
+    ) -> impl Future<Output = R>
+    where
+        R: 'static,
+    {
+        self.access.with(func)
+    }
+}
+
 #[pin_project]
 struct ScopedEffect<E> {
     id: Entity,
@@ -159,13 +323,149 @@ struct ScopedEffect<E> {
 }
 
 impl<E: for<'x> Effect<Scope<'x>>> Effect<Frame> for ScopedEffect<E> {
+    type Output = ();
+
     fn poll(self: Pin<&mut Self>, context: &mut Context<'_>, frame: &mut Frame) -> Poll<()> {
         let p = self.project();
 
         if let Some(mut scope) = Scope::try_from_id(frame, *p.id) {
-            p.effect.poll(context, &mut scope)
+            p.effect.poll(context, &mut scope).map(|_| ())
         } else {
             Poll::Ready(())
         }
     }
 }
+
+#[pin_project]
+struct ScopedAsyncEffect<Fut> {
+    id: Entity,
+    #[pin]
+    effect: AsyncEffect<Frame, Fut>,
+}
+
+impl<Fut: Future> Effect<Frame> for ScopedAsyncEffect<Fut> {
+    type Output = Option<Fut::Output>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        frame: &mut Frame,
+    ) -> Poll<Self::Output> {
+        let p = self.project();
+
+        if !frame.world().is_alive(*p.id) {
+            return Poll::Ready(None);
+        }
+
+        match p.effect.poll(context, frame) {
+            Poll::Ready(value) => Poll::Ready(Some(value)),
+            // The entity was despawned by the effect itself
+            Poll::Pending if !frame.world().is_alive(*p.id) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use flax::World;
+
+    use crate::{
+        background::ThreadPool,
+        executor::{Cancelled, Executor},
+    };
+
+    use super::*;
+
+    #[test]
+    fn background_cancelled_on_despawn() {
+        let mut ex = Executor::new();
+        let pool = ThreadPool::new(1);
+
+        let mut frame = Frame {
+            world: World::new(),
+            spawner: ex.spawner(),
+            assets: AssetCache::new(),
+            pool: pool.clone(),
+        };
+
+        // Occupy the only worker so the work stays queued
+        let (tx, rx) = flume::bounded::<()>(0);
+        let blocker = pool.spawn(move || rx.recv().unwrap());
+
+        let ran = Arc::new(AtomicBool::new(false));
+        let applied = Arc::new(AtomicBool::new(false));
+
+        let (id, handle) = {
+            let mut scope = Scope::new(&mut frame);
+            let handle = scope.spawn_background(
+                {
+                    let ran = ran.clone();
+                    move || ran.store(true, Ordering::Relaxed)
+                },
+                {
+                    let applied = applied.clone();
+                    move |_, _| applied.store(true, Ordering::Relaxed)
+                },
+            );
+
+            (scope.id(), handle)
+        };
+
+        ex.tick(&mut frame);
+        assert!(!handle.is_finished());
+
+        frame.world.despawn(id).unwrap();
+        ex.tick(&mut frame);
+
+        tx.send(()).unwrap();
+        futures::executor::block_on(blocker).unwrap();
+        // Runs after the cancelled work would have
+        futures::executor::block_on(pool.spawn(|| {})).unwrap();
+
+        assert!(!ran.load(Ordering::Relaxed));
+        assert!(!applied.load(Ordering::Relaxed));
+        assert_eq!(futures::executor::block_on(handle), Err(Cancelled));
+    }
+
+    #[test]
+    fn background_guard_dropped_on_completion() {
+        let mut ex = Executor::new();
+        let pool = ThreadPool::new(1);
+
+        let mut frame = Frame {
+            world: World::new(),
+            spawner: ex.spawner(),
+            assets: AssetCache::new(),
+            pool: pool.clone(),
+        };
+
+        let applied = Arc::new(AtomicBool::new(false));
+
+        let (id, handle) = {
+            let mut scope = Scope::new(&mut frame);
+            let handle = scope.spawn_background(|| {}, {
+                let applied = applied.clone();
+                move |_, _| applied.store(true, Ordering::Relaxed)
+            });
+
+            (scope.id(), handle)
+        };
+
+        assert_eq!(frame.world.get(id, background_tasks()).unwrap().len(), 1);
+
+        // Runs after the work
+        futures::executor::block_on(pool.spawn(|| {})).unwrap();
+        ex.tick(&mut frame);
+
+        assert!(applied.load(Ordering::Relaxed));
+        assert!(frame.world.get(id, background_tasks()).unwrap().is_empty());
+        // Dropping the guard does not cancel the task it belonged to
+        assert_eq!(futures::executor::block_on(handle), Ok(()));
+    }
+}
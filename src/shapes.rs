@@ -1,13 +1,16 @@
+use glam::Vec2;
 use image::DynamicImage;
-use palette::Srgba;
+use palette::{FromColor, IntoColor, LinSrgba, Mix, Oklaba, Srgba};
 
-use crate::assets::Handle;
+use crate::{assets::Handle, unit::Unit};
 
 /// A rectangle sized to the widget
 #[derive(Clone)]
 pub struct FilledRect {
     pub color: Srgba,
     pub fill_image: Option<Handle<DynamicImage>>,
+    /// Gradient multiplied with the color and fill image
+    pub gradient: Option<Gradient>,
 }
 
 impl std::fmt::Debug for FilledRect {
@@ -15,6 +18,360 @@ impl std::fmt::Debug for FilledRect {
         f.debug_struct("FilledRect")
             .field("color", &self.color)
             .field("fill_image", &self.fill_image.as_ref().map(Handle::id))
+            .field("gradient", &self.gradient)
             .finish()
     }
 }
+
+/// The color space in which gradient stops are interpolated
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Interpolate in linear sRGB
+    #[default]
+    Linear,
+    /// Interpolate in the perceptually uniform Oklab space
+    Oklab,
+}
+
+/// Describes how the gradient parameter is derived from a position within the rect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    /// Interpolates along a line through the center of the rect.
+    ///
+    /// An angle of `0` goes from left to right, increasing clockwise. The angle is given in
+    /// radians.
+    Linear { angle: f32 },
+    /// Interpolates outward from `center` until `radius`.
+    ///
+    /// Both are relative to the size of the rect. The radius is at least [`MIN_GRADIENT_RADIUS`].
+    Radial { center: Vec2, radius: f32 },
+}
+
+/// The smallest radius of a radial gradient, which avoids dividing by zero
+pub const MIN_GRADIENT_RADIUS: f32 = 1e-4;
+
+/// A color at a specific offset along a gradient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    /// Position of the stop in `0..=1`
+    pub offset: f32,
+    pub color: Srgba,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: Srgba) -> Self {
+        Self { offset, color }
+    }
+}
+
+/// A gradient fill consisting of a list of color stops
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    pub space: ColorSpace,
+}
+
+impl Gradient {
+    /// Creates a linear gradient with the given angle in radians
+    pub fn linear(angle: f32, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        Self::new(GradientKind::Linear { angle }, stops)
+    }
+
+    /// Creates a radial gradient centered at `center`
+    pub fn radial(center: Vec2, radius: f32, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        Self::new(GradientKind::Radial { center, radius }, stops)
+    }
+
+    fn new(mut kind: GradientKind, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        if let GradientKind::Radial { radius, .. } = &mut kind {
+            *radius = radius.max(MIN_GRADIENT_RADIUS);
+        }
+
+        let mut stops = stops.into_iter().collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+
+        Self {
+            kind,
+            stops,
+            space: ColorSpace::default(),
+        }
+    }
+
+    /// Set the color space used for interpolation
+    pub fn with_space(mut self, space: ColorSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Returns the color of the gradient at `t`
+    pub fn sample(&self, t: f32) -> Srgba {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Srgba::new(1.0, 1.0, 1.0, 1.0),
+        };
+
+        if t <= first.offset {
+            return first.color;
+        } else if t >= last.offset {
+            return last.color;
+        }
+
+        let (a, b) = self
+            .stops
+            .windows(2)
+            .map(|v| (&v[0], &v[1]))
+            .find(|(_, b)| t <= b.offset)
+            .unwrap();
+
+        let span = b.offset - a.offset;
+        let factor = if span > 0.0 {
+            (t - a.offset) / span
+        } else {
+            1.0
+        };
+
+        match self.space {
+            ColorSpace::Linear => {
+                let a: LinSrgba = a.color.into_linear();
+                let b: LinSrgba = b.color.into_linear();
+                Srgba::from_linear(a.mix(b, factor))
+            }
+            ColorSpace::Oklab => {
+                let a: Oklaba = a.color.into_color();
+                let b: Oklaba = b.color.into_color();
+                Srgba::from_color(a.mix(b, factor))
+            }
+        }
+    }
+
+    /// Samples the gradient into a lookup table of `width` colors spanning `0..=1`
+    pub(crate) fn lookup_table(&self, width: u32) -> Vec<Srgba<u8>> {
+        (0..width)
+            .map(|i| {
+                let t = i as f32 / (width - 1).max(1) as f32;
+                self.sample(t).into_format()
+            })
+            .collect()
+    }
+}
+
+/// A vector shape which is tessellated to the size of the widget
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    pub path: ShapePath,
+    /// Fills the interior of the path
+    pub fill: Option<Srgba>,
+    /// Strokes the outline of the path
+    pub stroke: Option<Stroke>,
+}
+
+impl Shape {
+    pub fn new(path: ShapePath) -> Self {
+        Self {
+            path,
+            fill: None,
+            stroke: None,
+        }
+    }
+
+    /// Set the fill color of the shape
+    pub fn with_fill(mut self, fill: Srgba) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// Set the stroke of the shape
+    pub fn with_stroke(mut self, stroke: Stroke) -> Self {
+        self.stroke = Some(stroke);
+        self
+    }
+}
+
+/// The geometry of a [`Shape`].
+///
+/// Positions and radii are resolved relative to the size of the widget rect
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapePath {
+    Line {
+        from: Unit<Vec2>,
+        to: Unit<Vec2>,
+    },
+    /// A circle, relative sizes are resolved against the smallest side of the rect
+    Circle {
+        center: Unit<Vec2>,
+        radius: Unit<f32>,
+    },
+    Ellipse {
+        center: Unit<Vec2>,
+        radii: Unit<Vec2>,
+    },
+    /// An elliptic arc, with angles in radians
+    Arc {
+        center: Unit<Vec2>,
+        radii: Unit<Vec2>,
+        start_angle: f32,
+        sweep_angle: f32,
+    },
+    Polyline {
+        points: Vec<Unit<Vec2>>,
+        closed: bool,
+    },
+    /// An arbitrary path of lines and bezier curves
+    Path(Vec<PathCommand>),
+}
+
+/// A single command used to build up a [`ShapePath::Path`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand {
+    /// Begins a new sub-path
+    MoveTo(Unit<Vec2>),
+    LineTo(Unit<Vec2>),
+    QuadraticTo {
+        ctrl: Unit<Vec2>,
+        to: Unit<Vec2>,
+    },
+    CubicTo {
+        ctrl1: Unit<Vec2>,
+        ctrl2: Unit<Vec2>,
+        to: Unit<Vec2>,
+    },
+    /// Closes the current sub-path
+    Close,
+}
+
+/// Outline of a shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub color: Srgba,
+    /// Width of the stroke in pixels
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl Stroke {
+    pub fn new(color: Srgba, width: f32) -> Self {
+        Self {
+            color,
+            width,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+        }
+    }
+
+    /// Set the shape at the ends of open sub-paths
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    /// Set the shape used where two segments meet
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// Ends the stroke exactly at the end point
+    #[default]
+    Butt,
+    /// Extends the stroke by half the width with a square end
+    Square,
+    /// Extends the stroke by half the width with a rounded end
+    Round,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet
+    #[default]
+    Miter,
+    /// Rounds the outer corner
+    Round,
+    /// Cuts off the outer corner
+    Bevel,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rgb(r: f32, g: f32, b: f32) -> Srgba {
+        Srgba::new(r, g, b, 1.0)
+    }
+
+    #[test]
+    fn gradient_stops() {
+        let red = rgb(1.0, 0.0, 0.0);
+        let green = rgb(0.0, 1.0, 0.0);
+        let blue = rgb(0.0, 0.0, 1.0);
+
+        let gradient = Gradient::linear(
+            0.0,
+            [
+                ColorStop::new(1.0, blue),
+                ColorStop::new(0.25, red),
+                ColorStop::new(0.5, green),
+            ],
+        );
+
+        // Stops are sorted by offset
+        assert_eq!(
+            gradient.stops.iter().map(|v| v.offset).collect::<Vec<_>>(),
+            [0.25, 0.5, 1.0]
+        );
+
+        // Outside the stops the first and last colors are extended
+        assert_eq!(gradient.sample(-1.0), red);
+        assert_eq!(gradient.sample(0.0), red);
+        assert_eq!(gradient.sample(0.25), red);
+        assert_eq!(
+            gradient.sample(0.5).into_format::<u8, u8>(),
+            green.into_format()
+        );
+        assert_eq!(gradient.sample(1.0), blue);
+        assert_eq!(gradient.sample(2.0), blue);
+
+        // Halfway between red and green in linear space
+        let mid: LinSrgba = gradient.sample(0.375).into_linear();
+        assert!((mid.red - 0.5).abs() < 1e-3 && (mid.green - 0.5).abs() < 1e-3);
+
+        // Coinciding stops produce a hard edge
+        let hard = Gradient::linear(
+            0.0,
+            [
+                ColorStop::new(0.5, red),
+                ColorStop::new(0.5, blue),
+                ColorStop::new(1.0, green),
+            ],
+        );
+        assert_eq!(hard.sample(0.5), red);
+        let after = hard.sample(0.5001);
+        assert!(after.blue > 0.99 && after.red < 0.01);
+
+        assert_eq!(Gradient::linear(0.0, []).sample(0.5), rgb(1.0, 1.0, 1.0));
+
+        let lut = gradient.lookup_table(5);
+        assert_eq!(lut.len(), 5);
+        assert_eq!(lut[0], red.into_format());
+        assert_eq!(lut[4], blue.into_format());
+    }
+
+    #[test]
+    fn radial_radius() {
+        let gradient = Gradient::radial(Vec2::splat(0.5), 0.0, []);
+        assert_eq!(
+            gradient.kind,
+            GradientKind::Radial {
+                center: Vec2::splat(0.5),
+                radius: MIN_GRADIENT_RADIUS
+            }
+        );
+
+        let gradient = Gradient::radial(Vec2::ZERO, -1.0, []);
+        assert!(matches!(gradient.kind, GradientKind::Radial { radius, .. } if radius > 0.0));
+    }
+}
@@ -1,16 +1,44 @@
+use std::{
+    sync::Arc,
+    task::{Context, Waker},
+    time::Instant,
+};
+
 use flax::{
-    child_of, entity_ids, BoxedSystem, CommandBuffer, ComponentValue, Dfs, DfsBorrow, Entity,
-    Fetch, FetchItem, Query, QueryBorrow, System, World,
+    child_of, component, entity_ids, BoxedSystem, CommandBuffer, ComponentValue, Dfs, DfsBorrow,
+    Entity, Fetch, FetchItem, Query, QueryBorrow, System, World,
 };
 use glam::Vec2;
+use itertools::Itertools;
 
 use crate::{
-    components::{self, children, local_position, rect, screen_position, Rect},
-    layout::{update_subtree, LayoutLimits},
+    animation::Tweenable,
+    components::{self, children, layout_transition, local_position, rect, screen_position, Rect},
+    layout::{update_subtree, LayoutLimits, TextMeasure},
+    time::FrameClock,
 };
 
-/// Updates the layout for entities using the given constraints
-pub fn layout_system() -> BoxedSystem {
+#[cfg(feature = "svg")]
+use {
+    crate::{
+        assets::{
+            loader::AsyncHandle,
+            svg::{SvgImage, SvgSource},
+            AssetCache,
+        },
+        shapes::FilledRect,
+    },
+    flax::{fetch::Modified, Component, EntityIds, FetchExt, Opt},
+    glam::UVec2,
+    image::DynamicImage,
+    palette::Srgba,
+    std::{future::Future, pin::Pin, task::Poll},
+};
+
+/// Updates the layout for entities using the given constraints.
+///
+/// Text is sized using `measure`
+pub fn layout_system(measure: Arc<dyn TextMeasure>) -> BoxedSystem {
     System::builder()
         .with_world()
         .with_query(Query::new((rect(), children())).without_relation(child_of))
@@ -23,6 +51,7 @@ pub fn layout_system() -> BoxedSystem {
 
                         let res = update_subtree(
                             world,
+                            &*measure,
                             &entity,
                             *canvas_rect,
                             LayoutLimits {
@@ -38,6 +67,112 @@ pub fn layout_system() -> BoxedSystem {
         .boxed()
 }
 
+struct LayoutTransitionState {
+    start: Instant,
+    from: (Rect, Vec2),
+    /// The latest layout result
+    to: (Rect, Vec2),
+    current: (Rect, Vec2),
+}
+
+component! {
+    layout_transition_state: LayoutTransitionState,
+}
+
+#[cfg(feature = "svg")]
+component! {
+    /// The svg fill of the entity rasterized at its current size, while it is loading
+    svg_fill_loading: AsyncHandle<DynamicImage>,
+}
+
+/// Animates the layout of entities with a [`layout_transition`] towards the result of the layout.
+///
+/// Runs between [`layout_system`] and [`transform_system`], and replaces the layout result with
+/// the animated one. `waker` is woken on the next frame of `frames` while any transition is in
+/// progress.
+pub fn layout_transition_system(frames: FrameClock, waker: Waker) -> BoxedSystem {
+    let mut query = Query::new((entity_ids(), layout_transition(), rect(), local_position()));
+    let mut removed = Query::new(entity_ids())
+        .with(layout_transition_state())
+        .without(layout_transition());
+    let mut interval = frames.interval();
+
+    System::builder()
+        .with_world()
+        .with_cmd_mut()
+        .build(move |world: &World, cmd: &mut CommandBuffer| {
+            let now = frames.time();
+
+            // A transition added again later starts from the layout at that time
+            for id in removed.borrow(world).iter() {
+                cmd.remove(id, layout_transition_state());
+            }
+
+            let targets = query
+                .borrow(world)
+                .iter()
+                .map(|(id, &transition, &rect, &pos)| (id, transition, (rect, pos)))
+                .collect_vec();
+
+            let mut in_progress = false;
+
+            for (id, transition, target) in targets {
+                let entity = world.entity(id).unwrap();
+
+                let Ok(mut state) = entity.get_mut(layout_transition_state()) else {
+                    // The initial layout is not animated
+                    cmd.set(
+                        id,
+                        layout_transition_state(),
+                        LayoutTransitionState {
+                            start: now,
+                            from: target,
+                            to: target,
+                            current: target,
+                        },
+                    );
+                    continue;
+                };
+
+                // The layout is recomputed every frame and overwrites the animated value, so a
+                // differing target means the layout changed. Start from what is currently displayed
+                if target != state.to {
+                    state.start = now;
+                    state.from = state.current;
+                    state.to = target;
+                }
+
+                let elapsed = now.saturating_duration_since(state.start).as_secs_f32();
+                let t = elapsed / transition.duration.as_secs_f32();
+
+                state.current = if t < 1.0 {
+                    in_progress = true;
+
+                    let t = transition.easing.apply(t);
+                    (
+                        state.from.0.lerp(&state.to.0, t),
+                        state.from.1.lerp(&state.to.1, t),
+                    )
+                } else {
+                    state.to
+                };
+
+                let (current_rect, current_pos) = state.current;
+                drop(state);
+
+                entity.update_dedup(rect(), current_rect);
+                entity.update_dedup(local_position(), current_pos);
+            }
+
+            if in_progress {
+                // Request another update once the next frame starts
+                let mut cx = Context::from_waker(&waker);
+                while interval.poll_tick(&mut cx).is_ready() {}
+            }
+        })
+        .boxed()
+}
+
 pub fn transform_system() -> BoxedSystem {
     System::builder()
         .with_query(
@@ -72,3 +207,201 @@ where
         )
         .boxed();
 }
+
+#[cfg(feature = "svg")]
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+struct SvgFillQuery {
+    #[fetch(ignore)]
+    id: EntityIds,
+    svg: Component<SvgSource>,
+    rect: Component<Rect>,
+    #[fetch(ignore)]
+    filled_rect: Opt<Component<FilledRect>>,
+    #[fetch(ignore)]
+    loading: Opt<Component<AsyncHandle<DynamicImage>>>,
+}
+
+/// Rasterizes the [`fill_svg`](components::fill_svg) of each widget at the size of its rect, and
+/// uses it as the image of its [`filled_rect`](components::filled_rect).
+///
+/// Rects are in physical pixels, so the svg is rasterized at the resolution of the device and
+/// again whenever the widget is resized. Rasterizing happens on the thread pool of the asset
+/// cache, and the previous image is displayed until it completes. `waker` is woken once a pending
+/// image has loaded.
+#[cfg(feature = "svg")]
+pub fn svg_fill_system(assets: AssetCache, waker: Waker) -> BoxedSystem {
+    let query = SvgFillQuery {
+        id: entity_ids(),
+        svg: components::fill_svg(),
+        rect: rect(),
+        filled_rect: components::filled_rect().opt(),
+        loading: svg_fill_loading().opt(),
+    };
+
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new(query.transform_fetch(Modified)))
+        .with_query(Query::new((
+            entity_ids(),
+            svg_fill_loading(),
+            components::filled_rect().opt(),
+        )))
+        .build(
+            move |cmd: &mut CommandBuffer,
+                  mut query: QueryBorrow<_, _>,
+                  mut loading: QueryBorrow<_, _>| {
+                let mut restarted = Vec::new();
+
+                for item in &mut query {
+                    let size = item.rect.size().ceil().as_uvec2();
+                    if size.cmpeq(UVec2::ZERO).any() {
+                        continue;
+                    }
+
+                    restarted.push(item.id);
+
+                    // Sizes which were rasterized before are available right away
+                    let handle = assets.load_async(&SvgImage::new(item.svg.clone(), size));
+                    if !poll_svg_fill(cmd, item.id, &handle, item.filled_rect, &waker) {
+                        cmd.set(item.id, svg_fill_loading(), handle);
+                    } else if item.loading.is_some() {
+                        // Supersedes the load for the previous size
+                        cmd.remove(item.id, svg_fill_loading());
+                    }
+                }
+
+                for (id, handle, filled_rect) in &mut loading {
+                    if !restarted.contains(&id)
+                        && poll_svg_fill(cmd, id, handle, filled_rect, &waker)
+                    {
+                        cmd.remove(id, svg_fill_loading());
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+/// Uses the rasterized svg as the image of the filled rect once it has loaded.
+///
+/// Returns false while the image is loading, and `waker` is woken once it completes.
+#[cfg(feature = "svg")]
+fn poll_svg_fill(
+    cmd: &mut CommandBuffer,
+    id: Entity,
+    handle: &AsyncHandle<DynamicImage>,
+    filled_rect: Option<&FilledRect>,
+    waker: &Waker,
+) -> bool {
+    let image = match Pin::new(&mut handle.loaded()).poll(&mut Context::from_waker(waker)) {
+        Poll::Pending => return false,
+        Poll::Ready(Ok(image)) => image,
+        Poll::Ready(Err(err)) => {
+            tracing::error!(?id, "Failed to rasterize svg: {err:?}");
+            return true;
+        }
+    };
+
+    let filled_rect = match filled_rect {
+        Some(v) => FilledRect {
+            fill_image: Some(image),
+            ..v.clone()
+        },
+        None => FilledRect {
+            color: Srgba::new(1.0, 1.0, 1.0, 1.0),
+            fill_image: Some(image),
+            gradient: None,
+        },
+    };
+
+    cmd.set(id, components::filled_rect(), filled_rect);
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use flax::Schedule;
+    use glam::vec2;
+
+    use crate::animation::{Easing, LayoutTransition};
+
+    use super::*;
+
+    #[test]
+    fn layout_transition_settles() {
+        let clock = FrameClock::new();
+        let start = clock.time();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut world = World::new();
+        let mut schedule = Schedule::new().with_system(layout_transition_system(
+            clock.clone(),
+            futures::task::noop_waker(),
+        ));
+
+        let small = (
+            Rect::from_size_pos(vec2(100.0, 50.0), Vec2::ZERO),
+            Vec2::ZERO,
+        );
+        let large = (
+            Rect::from_size_pos(vec2(200.0, 50.0), Vec2::ZERO),
+            vec2(0.0, 100.0),
+        );
+
+        let id = Entity::builder()
+            .set(
+                layout_transition(),
+                LayoutTransition::new(Duration::from_millis(100)).with_easing(Easing::Linear),
+            )
+            .set(rect(), small.0)
+            .set(local_position(), small.1)
+            .spawn(&mut world);
+
+        // Stands in for the layout system, which writes the layout result every frame
+        let mut frame = |world: &mut World, time, layout: (Rect, Vec2)| {
+            clock.advance(time);
+            world.set(id, rect(), layout.0).unwrap();
+            world.set(id, local_position(), layout.1).unwrap();
+            schedule.execute_seq(world).unwrap();
+
+            (
+                *world.get(id, rect()).unwrap(),
+                *world.get(id, local_position()).unwrap(),
+            )
+        };
+
+        // The initial layout is not animated
+        assert_eq!(frame(&mut world, at(0), small), small);
+
+        // The layout changes
+        assert_eq!(frame(&mut world, at(10), large), small);
+        assert_eq!(
+            frame(&mut world, at(60), large),
+            (
+                Rect::from_size_pos(vec2(150.0, 50.0), Vec2::ZERO),
+                vec2(0.0, 50.0)
+            )
+        );
+        assert_eq!(frame(&mut world, at(110), large), large);
+        assert_eq!(frame(&mut world, at(120), large), large);
+
+        // Removing the transition removes its state
+        world.remove(id, layout_transition()).unwrap();
+        assert_eq!(frame(&mut world, at(130), large), large);
+        assert!(!world.has(id, layout_transition_state()));
+
+        // Adding it again does not animate from the stale state
+        world
+            .set(
+                id,
+                layout_transition(),
+                LayoutTransition::new(Duration::from_millis(100)),
+            )
+            .unwrap();
+        assert_eq!(frame(&mut world, at(140), small), small);
+        assert_eq!(frame(&mut world, at(150), small), small);
+    }
+}
@@ -0,0 +1,334 @@
+use std::ops::Range;
+
+use flax::EntityRef;
+use palette::Srgba;
+
+use crate::{
+    components,
+    font::{FontFamilyDesc, FontStyle, FontWeight},
+};
+
+/// A run of text with its own style.
+///
+/// Properties which are not set are inherited from the text entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub font: Option<FontFamilyDesc>,
+    pub font_size: Option<f32>,
+    pub weight: Option<FontWeight>,
+    pub style: Option<FontStyle>,
+    pub color: Option<Srgba>,
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// Arbitrary payload for spans acting as links
+    pub link: Option<String>,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            font: None,
+            font_size: None,
+            weight: None,
+            style: None,
+            color: None,
+            underline: false,
+            strikethrough: false,
+            link: None,
+        }
+    }
+
+    /// Set the font family of the span
+    pub fn with_font(mut self, font: FontFamilyDesc) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = Some(font_size);
+        self
+    }
+
+    pub fn with_weight(mut self, weight: FontWeight) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    pub fn with_style(mut self, style: FontStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn with_color(mut self, color: Srgba) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn with_strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+
+    pub fn with_link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+}
+
+/// Text made up of differently styled spans which are laid out as a single flow
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RichText {
+    pub spans: Vec<TextSpan>,
+}
+
+impl RichText {
+    pub fn new(spans: impl IntoIterator<Item = TextSpan>) -> Self {
+        Self {
+            spans: spans.into_iter().collect(),
+        }
+    }
+
+    /// Append a span
+    pub fn with_span(mut self, span: TextSpan) -> Self {
+        self.spans.push(span);
+        self
+    }
+
+    /// Returns the text of all spans joined together
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|v| v.text.as_str()).collect()
+    }
+
+    /// Returns the byte range of each span in the joined text
+    pub fn span_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.spans.iter().scan(0, |offset, span| {
+            let start = *offset;
+            *offset += span.text.len();
+            Some(start..*offset)
+        })
+    }
+
+    /// Returns the span containing the byte `index` of the joined text
+    pub fn span_at(&self, index: usize) -> Option<&TextSpan> {
+        self.span_ranges()
+            .zip(&self.spans)
+            .find(|(range, _)| range.contains(&index))
+            .map(|(_, span)| span)
+    }
+
+    /// Returns the link payload at byte `index` of the joined text
+    pub fn link_at(&self, index: usize) -> Option<&str> {
+        self.span_at(index)?.link.as_deref()
+    }
+}
+
+/// A selected range of text, as byte offsets into the text of the entity
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextSelection {
+    /// Where the selection was started
+    pub anchor: usize,
+    /// Where the selection was extended to, which may be before the anchor
+    pub focus: usize,
+}
+
+impl TextSelection {
+    pub fn new(anchor: usize, focus: usize) -> Self {
+        Self { anchor, focus }
+    }
+
+    /// Returns the selected byte range
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.focus)..self.anchor.max(self.focus)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.focus
+    }
+}
+
+/// Returns the byte range of the word at `index`.
+///
+/// Characters which are not part of a word are returned by themselves.
+pub(crate) fn word_at(text: &str, index: usize) -> Range<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+
+    // Past the end of the text the last character is used
+    if index == text.len() {
+        match text.char_indices().next_back() {
+            Some((i, _)) => index = i,
+            None => return index..index,
+        }
+    }
+
+    let c = text[index..].chars().next().unwrap();
+    if !is_word(c) {
+        return index..index + c.len_utf8();
+    }
+
+    let start = text[..index]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(index, |(i, _)| i);
+
+    let end = text[index..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(text.len(), |(i, _)| index + i);
+
+    start..end
+}
+
+/// Horizontal alignment of each line of text
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    pub(crate) fn factor(&self) -> f32 {
+        match self {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => 0.5,
+            TextAlign::Right => 1.0,
+        }
+    }
+}
+
+/// Vertical alignment of the text within the widget
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+impl VerticalAlign {
+    pub(crate) fn factor(&self) -> f32 {
+        match self {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Center => 0.5,
+            VerticalAlign::Bottom => 1.0,
+        }
+    }
+}
+
+/// Where lines are broken when text exceeds the width of the widget
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Break at whitespace, and within words which do not fit on a line by themselves
+    #[default]
+    Word,
+    /// Break at any character
+    Char,
+    /// Only break at explicit line breaks
+    None,
+}
+
+/// What to do with text which does not fit within the widget
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOverflow {
+    /// Draw the text outside the widget bounds
+    #[default]
+    Visible,
+    /// Cut off the text at the widget bounds
+    Clip,
+    /// Truncate the text with an ellipsis, both horizontally and after `max_lines`
+    Ellipsis,
+}
+
+/// Options for laying out text, collected from the text style components
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayout {
+    pub align: TextAlign,
+    pub vertical_align: VerticalAlign,
+    /// Multiplier of the natural line height of the font
+    pub line_height: f32,
+    pub wrap: WrapMode,
+    pub overflow: TextOverflow,
+    pub max_lines: Option<usize>,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self {
+            align: TextAlign::default(),
+            vertical_align: VerticalAlign::default(),
+            line_height: 1.0,
+            wrap: WrapMode::default(),
+            overflow: TextOverflow::default(),
+            max_lines: None,
+        }
+    }
+}
+
+impl TextLayout {
+    /// Collects the text style components of an entity, using the defaults for those not set.
+    ///
+    /// Used by both layout and rendering so that measured and displayed text agree
+    pub fn from_entity(entity: &EntityRef) -> Self {
+        let default = Self::default();
+
+        Self {
+            align: entity
+                .get_copy(components::text_align())
+                .unwrap_or(default.align),
+            vertical_align: entity
+                .get_copy(components::vertical_align())
+                .unwrap_or(default.vertical_align),
+            line_height: entity
+                .get_copy(components::line_height())
+                .unwrap_or(default.line_height),
+            wrap: entity
+                .get_copy(components::text_wrap())
+                .unwrap_or(default.wrap),
+            overflow: entity
+                .get_copy(components::text_overflow())
+                .unwrap_or(default.overflow),
+            max_lines: entity.get_copy(components::max_lines()).ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words() {
+        let text = "Hello, wörld_1  end";
+
+        assert_eq!(&text[word_at(text, 0)], "Hello");
+        assert_eq!(&text[word_at(text, 3)], "Hello");
+        assert_eq!(&text[word_at(text, 5)], ",");
+        assert_eq!(&text[word_at(text, 6)], " ");
+        // Inside a multibyte character
+        assert_eq!(&text[word_at(text, 9)], "wörld_1");
+        assert_eq!(&text[word_at(text, text.len())], "end");
+        assert_eq!(word_at("", 0), 0..0);
+    }
+
+    #[test]
+    fn selection_range() {
+        assert_eq!(TextSelection::new(4, 1).range(), 1..4);
+        assert_eq!(TextSelection::new(1, 4).range(), 1..4);
+        assert!(TextSelection::new(2, 2).is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{Future, Stream};
+use pin_project::pin_project;
+
+use crate::time::{Sleep, TimersHandle, GLOBAL_TIMER};
+
+/// Yields the latest item of `stream` once no new item has arrived for `period`.
+///
+/// Useful for reacting to input once the user stops typing.
+pub fn debounce<S: Stream>(stream: S, period: Duration) -> Debounce<S> {
+    Debounce::new(&GLOBAL_TIMER, stream, period)
+}
+
+/// Yields the latest item once the inner stream has been quiet for a period.
+///
+/// A pending item is yielded immediately when the inner stream ends.
+#[pin_project]
+pub struct Debounce<S: Stream> {
+    #[pin]
+    stream: S,
+    sleep: Pin<Box<Sleep>>,
+    period: Duration,
+    pending: Option<S::Item>,
+    done: bool,
+}
+
+impl<S: Stream> Debounce<S> {
+    pub fn new(handle: &TimersHandle, stream: S, period: Duration) -> Self {
+        Self {
+            stream,
+            sleep: Box::pin(Sleep::new(handle, handle.now())),
+            period,
+            pending: None,
+            done: false,
+        }
+    }
+}
+
+impl<S: Stream> Stream for Debounce<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut p = self.project();
+
+        while !*p.done {
+            match p.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    // Restart the quiet period
+                    *p.pending = Some(item);
+                    let deadline = p.sleep.now() + *p.period;
+                    p.sleep.as_mut().reset(deadline);
+                }
+                Poll::Ready(None) => *p.done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        if *p.done {
+            return Poll::Ready(p.pending.take());
+        }
+
+        if p.pending.is_some() && p.sleep.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(p.pending.take());
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{FutureExt, StreamExt};
+
+    use crate::time::Timers;
+
+    use super::*;
+
+    #[test]
+    fn debounce() {
+        let (mut timers, handle) = Timers::manual();
+        let (tx, rx) = flume::unbounded();
+
+        let mut stream = Debounce::new(&handle, rx.into_stream(), Duration::from_millis(100));
+
+        tx.send(1).unwrap();
+        assert_eq!(stream.next().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(50));
+        tx.send(2).unwrap();
+        assert_eq!(stream.next().now_or_never(), None);
+
+        // The quiet period restarted with the second item
+        timers.advance(Duration::from_millis(99));
+        assert_eq!(stream.next().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(1));
+        assert_eq!(stream.next().now_or_never(), Some(Some(2)));
+        assert_eq!(stream.next().now_or_never(), None);
+
+        // Pending items are flushed when the stream ends
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(stream.next().now_or_never(), Some(Some(3)));
+        assert_eq!(stream.next().now_or_never(), Some(None));
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+use futures::Stream;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// Advanced by the [`App`](crate::App) for each rendered frame
+pub static GLOBAL_FRAMES: Lazy<FrameClock> = Lazy::new(FrameClock::new);
+
+/// Ticks once per rendered frame, see [`FrameInterval`]
+pub fn frame_interval() -> FrameInterval {
+    GLOBAL_FRAMES.interval()
+}
+
+struct Inner {
+    frame: AtomicU64,
+    time: Mutex<Instant>,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// Signals the start of each frame.
+///
+/// Clones share the same frames.
+#[derive(Clone)]
+pub struct FrameClock {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for FrameClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameClock")
+            .field("frame", &self.frame())
+            .finish()
+    }
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                frame: AtomicU64::new(0),
+                time: Mutex::new(Instant::now()),
+                wakers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Starts a new frame at `time`, waking the frame intervals
+    pub fn advance(&self, time: Instant) {
+        *self.inner.time.lock() = time;
+        self.inner.frame.fetch_add(1, Ordering::Release);
+
+        for waker in self.inner.wakers.lock().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Returns the number of frames so far
+    pub fn frame(&self) -> u64 {
+        self.inner.frame.load(Ordering::Acquire)
+    }
+
+    /// Returns the start time of the current frame
+    pub fn time(&self) -> Instant {
+        *self.inner.time.lock()
+    }
+
+    /// Ticks on the next frame, and each frame thereafter
+    pub fn interval(&self) -> FrameInterval {
+        FrameInterval {
+            clock: self.clone(),
+            last_frame: self.frame(),
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ticks in sync with rendering rather than wall clock time, yielding the start time of each
+/// frame.
+///
+/// Frames which were missed are skipped, which makes the interval suitable for driving
+/// animations. The event loop keeps rendering while a frame interval is waiting, and idles
+/// once all are dropped.
+#[derive(Debug)]
+pub struct FrameInterval {
+    clock: FrameClock,
+    last_frame: u64,
+}
+
+impl FrameInterval {
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        let inner = &self.clock.inner;
+
+        // Holding the lock ensures a frame starting in between wakes this interval
+        let mut wakers = inner.wakers.lock();
+
+        let frame = inner.frame.load(Ordering::Acquire);
+        if frame > self.last_frame {
+            self.last_frame = frame;
+            return Poll::Ready(*inner.time.lock());
+        }
+
+        if !wakers.iter().any(|v| v.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    pub async fn tick(&mut self) -> Instant {
+        futures::future::poll_fn(move |cx| self.poll_tick(cx)).await
+    }
+}
+
+impl Stream for FrameInterval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_tick(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures::{FutureExt, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn frame_interval() {
+        let clock = FrameClock::new();
+        let start = clock.time();
+
+        let mut interval = clock.interval();
+        assert_eq!(interval.next().now_or_never(), None);
+
+        clock.advance(start + Duration::from_millis(16));
+        assert_eq!(
+            interval.next().now_or_never(),
+            Some(Some(start + Duration::from_millis(16)))
+        );
+        assert_eq!(interval.next().now_or_never(), None);
+
+        // Missed frames are skipped
+        clock.advance(start + Duration::from_millis(32));
+        clock.advance(start + Duration::from_millis(48));
+        assert_eq!(
+            interval.next().now_or_never(),
+            Some(Some(start + Duration::from_millis(48)))
+        );
+        assert_eq!(interval.next().now_or_never(), None);
+    }
+}
@@ -10,19 +10,47 @@ use pin_project::pin_project;
 use crate::time::{Sleep, TimersHandle, GLOBAL_TIMER};
 
 pub fn interval(period: Duration) -> Interval {
-    Interval::new(&GLOBAL_TIMER, Instant::now(), period)
+    Interval::new(&GLOBAL_TIMER, GLOBAL_TIMER.now(), period)
 }
 
 pub fn interval_at(start: Instant, period: Duration) -> Interval {
     Interval::new(&GLOBAL_TIMER, start, period)
 }
 
+/// Determines how an [`Interval`] catches up after ticks were missed, such as when the thread
+/// was stalled
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Ticks immediately until caught up, keeping the original schedule
+    #[default]
+    Burst,
+    /// Ticks once, and continues a full period after the late tick
+    Delay,
+    /// Ticks once, and skips the missed ticks to continue on the original schedule
+    Skip,
+}
+
+impl MissedTickBehavior {
+    fn next_deadline(self, deadline: Instant, now: Instant, period: Duration) -> Instant {
+        match self {
+            MissedTickBehavior::Burst => deadline + period,
+            MissedTickBehavior::Delay => now + period,
+            MissedTickBehavior::Skip => {
+                let missed = (now - deadline).as_nanos() / period.as_nanos().max(1);
+                deadline + period * (missed as u32 + 1)
+            }
+        }
+    }
+}
+
 /// Ticks at a fixed interval.
 #[pin_project]
 #[derive(Debug)]
 pub struct Interval {
     sleep: Pin<Box<Sleep>>,
     period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+    last_tick: Option<Instant>,
 }
 
 impl Interval {
@@ -31,9 +59,50 @@ impl Interval {
         Self {
             sleep: Box::pin(Sleep::new(handle, start)),
             period,
+            missed_tick_behavior: MissedTickBehavior::default(),
+            last_tick: None,
+        }
+    }
+
+    pub fn with_missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.missed_tick_behavior = behavior;
+        self
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Changes the period of the interval.
+    ///
+    /// The next tick is rescheduled to one new period after the previous tick.
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period;
+
+        if let Some(last_tick) = self.last_tick {
+            self.sleep.as_mut().reset(last_tick + period);
         }
     }
 
+    /// Restarts the interval, with the next tick one period from now
+    pub fn reset(&mut self) {
+        let deadline = self.sleep.now() + self.period;
+        self.reset_at(deadline);
+    }
+
+    /// Restarts the interval, with the next tick at `deadline`
+    pub fn reset_at(&mut self, deadline: Instant) {
+        self.sleep.as_mut().reset(deadline);
+    }
+
     pub async fn tick(&mut self) -> Instant {
         futures::future::poll_fn(move |cx| self.poll_tick(cx)).await
     }
@@ -44,8 +113,16 @@ impl Interval {
         // Wait until the next tick
         ready!(self.sleep.as_mut().poll(cx));
 
-        // Calculate the next deadline
-        let new_deadline = deadline + self.period;
+        // Calculate the next deadline, catching up if the next tick is already due
+        let now = self.sleep.now();
+        let new_deadline = if now >= deadline + self.period {
+            self.missed_tick_behavior
+                .next_deadline(deadline, now, self.period)
+        } else {
+            deadline + self.period
+        };
+
+        self.last_tick = Some(deadline);
 
         // Reset the timer
         // Note: will not be registered until the interval is polled again
@@ -191,4 +268,60 @@ mod test {
         drop(handle);
         j.join().unwrap();
     }
+
+    #[test]
+    fn missed_ticks() {
+        use futures::FutureExt;
+
+        use crate::time::Timers;
+
+        let (mut timers, handle) = Timers::manual();
+        let start = handle.now();
+        let ms = Duration::from_millis;
+
+        let ticks = |behavior| {
+            let mut interval =
+                Interval::new(&handle, start, ms(100)).with_missed_tick_behavior(behavior);
+
+            assert_eq!(interval.next().now_or_never(), Some(Some(start)));
+            interval
+        };
+
+        let mut burst = ticks(MissedTickBehavior::Burst);
+        let mut delay = ticks(MissedTickBehavior::Delay);
+        let mut skip = ticks(MissedTickBehavior::Skip);
+
+        // Stall for two and a half periods
+        timers.advance(ms(250));
+        let now = handle.now();
+
+        for interval in [&mut burst, &mut delay, &mut skip] {
+            assert_eq!(interval.next().now_or_never(), Some(Some(start + ms(100))));
+        }
+
+        assert_eq!(burst.next().now_or_never(), Some(Some(start + ms(200))));
+        assert_eq!(burst.next().now_or_never(), None);
+        assert_eq!(delay.next().now_or_never(), None);
+        assert_eq!(skip.next().now_or_never(), None);
+
+        timers.advance(ms(50));
+        assert_eq!(burst.next().now_or_never(), Some(Some(start + ms(300))));
+        assert_eq!(skip.next().now_or_never(), Some(Some(start + ms(300))));
+        assert_eq!(delay.next().now_or_never(), None);
+
+        timers.advance(ms(50));
+        assert_eq!(delay.next().now_or_never(), Some(Some(now + ms(100))));
+
+        // Change the period and restart
+        skip.set_period(ms(200));
+        assert_eq!(skip.next().now_or_never(), None);
+        timers.advance(ms(200));
+        assert_eq!(skip.next().now_or_never(), Some(Some(start + ms(500))));
+
+        delay.reset();
+        timers.advance(ms(99));
+        assert_eq!(delay.next().now_or_never(), None);
+        timers.advance(ms(1));
+        assert_eq!(delay.next().now_or_never(), Some(Some(handle.now())));
+    }
 }
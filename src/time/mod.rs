@@ -1,6 +1,5 @@
 use std::{
     collections::BTreeSet,
-    marker::PhantomPinned,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
@@ -17,40 +16,106 @@ use futures::{
 };
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use pin_project::{pin_project, pinned_drop};
-use slotmap::new_key_type;
+use slotmap::{new_key_type, SlotMap};
+mod debounce;
+mod frame;
 mod interval;
+mod throttle;
+mod timeout;
 
-pub use interval::{interval, interval_at, Interval};
+pub use debounce::{debounce, Debounce};
+pub use frame::{frame_interval, FrameClock, FrameInterval, GLOBAL_FRAMES};
+pub use interval::{interval, interval_at, Interval, MissedTickBehavior};
+pub use throttle::{throttle, Throttle};
+pub use timeout::{timeout, timeout_at, Elapsed, Timeout};
 
 pub static GLOBAL_TIMER: Lazy<TimersHandle> = Lazy::new(Timers::start);
 
 pub fn sleep_until(deadline: Instant) -> Sleep {
-    Sleep::new(&GLOBAL_TIMER, deadline)
+    GLOBAL_TIMER.sleep_until(deadline)
 }
 
 pub fn sleep(duration: Duration) -> Sleep {
-    Sleep::new(&GLOBAL_TIMER, Instant::now() + duration)
+    GLOBAL_TIMER.sleep(duration)
 }
 
-struct TimerEntry {
+/// The source of the current time for a set of timers
+enum Clock {
+    System,
+    /// Only advances through [`Timers::advance`]
+    Manual(Mutex<Instant>),
+}
+
+impl Clock {
+    fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Manual(now) => *now.lock(),
+        }
+    }
+}
+
+/// Shared between a [`Sleep`] and its registered timer
+#[derive(Default)]
+struct TimerState {
     waker: AtomicWaker,
     finished: AtomicBool,
-    _pinned: PhantomPinned,
 }
 
 new_key_type! {
+    /// Identifies a registered timer.
+    ///
+    /// Keys are generation checked, so a stale key of a fired or removed timer never refers to a
+    /// newer timer.
     pub struct TimerKey;
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
-struct Entry {
+struct TimerEntry {
     deadline: Instant,
-    timer: *const TimerEntry,
+    state: Arc<TimerState>,
 }
 
-unsafe impl Send for Entry {}
-unsafe impl Sync for Entry {}
+/// Number of independently locked timer queues
+const SHARD_COUNT: usize = 8;
+
+/// A shard of the registered timers, ordered by deadline
+#[derive(Default)]
+struct TimerQueue {
+    entries: SlotMap<TimerKey, TimerEntry>,
+    queue: BTreeSet<(Instant, TimerKey)>,
+}
+
+impl TimerQueue {
+    fn insert(&mut self, deadline: Instant, state: Arc<TimerState>) -> TimerKey {
+        let key = self.entries.insert(TimerEntry { deadline, state });
+        self.queue.insert((deadline, key));
+        key
+    }
+
+    fn remove(&mut self, key: TimerKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.queue.remove(&(entry.deadline, key));
+        }
+    }
+
+    /// Removes the timers which expired at `time`
+    fn pop_expired(&mut self, time: Instant, expired: &mut Vec<Arc<TimerState>>) {
+        while let Some(&(deadline, key)) = self.queue.first() {
+            if deadline > time {
+                break;
+            }
+
+            self.queue.pop_first();
+            if let Some(entry) = self.entries.remove(key) {
+                expired.push(entry.state);
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.queue.first().map(|v| v.0)
+    }
+}
 
 struct ThreadWaker {
     thread_id: Thread,
@@ -63,21 +128,27 @@ impl ArcWake for ThreadWaker {
 }
 
 struct Inner {
+    clock: Clock,
     /// Invoked when there is a new timer
     waker: AtomicWaker,
-    heap: Mutex<BTreeSet<Entry>>,
+    /// Timers are spread over several queues, so that registering and removing timers from
+    /// different threads rarely contend on the same lock
+    timers: [Mutex<TimerQueue>; SHARD_COUNT],
+    next_shard: AtomicUsize,
     handle_count: AtomicUsize,
 }
 
 impl Inner {
-    pub fn register(&self, deadline: Instant, timer: *const TimerEntry) {
-        self.heap.lock().insert(Entry { deadline, timer });
+    fn register(&self, deadline: Instant, state: Arc<TimerState>) -> (usize, TimerKey) {
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % SHARD_COUNT;
+        let key = self.timers[shard].lock().insert(deadline, state);
 
         self.waker.wake();
+        (shard, key)
     }
 
-    fn remove(&self, deadline: Instant, timer: *const TimerEntry) {
-        self.heap.lock().remove(&Entry { deadline, timer });
+    fn remove(&self, (shard, key): (usize, TimerKey)) {
+        self.timers[shard].lock().remove(key);
     }
 }
 
@@ -85,6 +156,21 @@ pub struct TimersHandle {
     inner: Arc<Inner>,
 }
 
+impl TimersHandle {
+    /// Returns the current time according to the clock of the timers
+    pub fn now(&self) -> Instant {
+        self.inner.clock.now()
+    }
+
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        Sleep::new(self, self.now() + duration)
+    }
+
+    pub fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Sleep::new(self, deadline)
+    }
+}
+
 impl Clone for TimersHandle {
     fn clone(&self) -> Self {
         self.inner.handle_count.fetch_add(1, Ordering::Relaxed);
@@ -111,9 +197,24 @@ pub struct Timers {
 pub struct TimersFinished;
 
 impl Timers {
+    /// Creates timers driven by the system clock
     pub fn new() -> (Self, TimersHandle) {
+        Self::with_clock(Clock::System)
+    }
+
+    /// Creates timers with a clock which only advances through [`Timers::advance`].
+    ///
+    /// This allows testing code using [`Sleep`] and [`Interval`] deterministically, without a
+    /// background thread.
+    pub fn manual() -> (Self, TimersHandle) {
+        Self::with_clock(Clock::Manual(Mutex::new(Instant::now())))
+    }
+
+    fn with_clock(clock: Clock) -> (Self, TimersHandle) {
         let inner = Arc::new(Inner {
-            heap: Mutex::new(BTreeSet::new()),
+            clock,
+            timers: std::array::from_fn(|_| Mutex::new(TimerQueue::default())),
+            next_shard: AtomicUsize::new(0),
             waker: AtomicWaker::new(),
             handle_count: AtomicUsize::new(1),
         });
@@ -130,26 +231,29 @@ impl Timers {
     fn tick(&mut self, time: Instant, waker: &Waker) -> Result<Option<Instant>, TimersFinished> {
         self.inner.waker.register(waker);
 
-        let mut heap = self.inner.heap.lock();
-
-        while let Some(entry) = heap.first() {
-            // All deadlines before now have been handled
-            if entry.deadline > time {
-                return Ok(Some(entry.deadline));
-            }
+        let mut expired = Vec::new();
 
-            let entry = heap.pop_first().unwrap();
-            // Fire and wake the timer
-            // # Safety
-            // Sleep removes the timer when dropped
-            // Drop is guaranteed due to Sleep being pinned when registered
-            let timer = unsafe { &*(entry.timer) };
+        let next = self
+            .inner
+            .timers
+            .iter()
+            .filter_map(|timers| {
+                let mut timers = timers.lock();
+                timers.pop_expired(time, &mut expired);
+                timers.next_deadline()
+            })
+            .min();
 
-            // Wake the future waiting on the timer
+        // Wake outside of the lock, as the woken futures may register new timers
+        for timer in expired {
             timer.finished.store(true, Ordering::SeqCst);
             timer.waker.wake();
         }
 
+        if next.is_some() {
+            return Ok(next);
+        }
+
         if self.inner.handle_count.load(Ordering::SeqCst) == 0 {
             return Err(TimersFinished);
         }
@@ -157,6 +261,32 @@ impl Timers {
         Ok(None)
     }
 
+    /// Returns the current time according to the clock of the timers
+    pub fn now(&self) -> Instant {
+        self.inner.clock.now()
+    }
+
+    /// Advances a manual clock by `duration`, and fires the timers which have expired.
+    ///
+    /// The futures of the fired timers are woken before this returns.
+    ///
+    /// # Panics
+    /// If the timers are not created using [`Timers::manual`]
+    pub fn advance(&mut self, duration: Duration) {
+        let Clock::Manual(now) = &self.inner.clock else {
+            panic!("Only timers with a manual clock can be advanced");
+        };
+
+        let now = {
+            let mut now = now.lock();
+            *now += duration;
+            *now
+        };
+
+        // The finished state is irrelevant, as the timers are driven by the caller
+        self.tick(now, futures::task::noop_waker_ref()).ok();
+    }
+
     /// Starts executing the timers in the background
     pub fn start() -> TimersHandle {
         let (timers, handle) = Timers::new();
@@ -172,7 +302,7 @@ impl Timers {
         let waker = futures::task::waker(waker);
 
         loop {
-            let now = Instant::now();
+            let now = self.now();
             let next = match self.tick(now, &waker) {
                 Ok(v) => v,
                 Err(_) => {
@@ -190,13 +320,13 @@ impl Timers {
     }
 }
 
-#[pin_project(PinnedDrop)]
 /// Sleep future
 pub struct Sleep {
     shared: Arc<Inner>,
-    timer: Box<TimerEntry>,
+    state: Arc<TimerState>,
     deadline: Instant,
-    registered: bool,
+    /// Set while the timer is registered
+    key: Option<(usize, TimerKey)>,
 }
 
 impl std::fmt::Debug for Sleep {
@@ -211,75 +341,71 @@ impl Sleep {
     pub(crate) fn new(handle: &TimersHandle, deadline: Instant) -> Self {
         Self {
             shared: handle.inner.clone(),
-            timer: Box::new(TimerEntry {
-                waker: AtomicWaker::new(),
-                finished: AtomicBool::new(false),
-                _pinned: PhantomPinned,
-            }),
+            state: Default::default(),
             deadline,
-            registered: false,
+            key: None,
         }
     }
 
-    pub fn reset(self: Pin<&mut Self>, deadline: Instant) {
-        let (timer, cur_deadline) = self.unregister();
-        *cur_deadline = deadline;
-        timer.finished.store(false, Ordering::SeqCst);
+    pub fn reset(mut self: Pin<&mut Self>, deadline: Instant) {
+        self.unregister();
+        self.deadline = deadline;
+        // A new state ensures the previous timer can not complete the sleep if it is firing
+        // concurrently
+        self.state = Default::default();
     }
 
     pub fn deadline(&self) -> Instant {
         self.deadline
     }
 
-    /// Removes the timer entry from the timers queue.
-    ///
-    /// The TimerEntry is no longer aliased and is safe to modify.
-    fn unregister(self: Pin<&mut Self>) -> (&mut TimerEntry, &mut Instant) {
-        let p = self.project();
-        // This removes any existing reference to the TimerEntry pointer
-        let shared = p.shared;
-        shared.remove(*p.deadline, &**p.timer);
-
-        *p.registered = false;
-        (p.timer, p.deadline)
+    /// Returns the current time according to the clock of the timers
+    pub(crate) fn now(&self) -> Instant {
+        self.shared.clock.now()
     }
 
-    fn register_deadline(self: Pin<&mut Self>) {
-        let p = self.project();
-        p.shared.register(*p.deadline, &**p.timer);
-        *p.registered = true;
+    /// Removes the timer from the timers queue
+    fn unregister(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.shared.remove(key);
+        }
     }
 }
 
 impl Future for Sleep {
     type Output = ();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         if self
-            .timer
+            .state
             .finished
             .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
             .is_ok()
         {
+            // The timer was removed when it fired
+            self.key = None;
             Poll::Ready(())
-        } else if !self.registered {
-            self.timer.waker.register(cx.waker());
-            self.register_deadline();
+        } else if self.key.is_none() {
+            // Expired deadlines complete without waiting for the timers to fire
+            if self.deadline <= self.now() {
+                return Poll::Ready(());
+            }
+
+            self.state.waker.register(cx.waker());
+            let key = self.shared.register(self.deadline, self.state.clone());
+            self.key = Some(key);
 
             Poll::Pending
         } else {
-            self.timer.waker.register(cx.waker());
+            self.state.waker.register(cx.waker());
             Poll::Pending
         }
     }
 }
 
-#[pinned_drop]
-impl PinnedDrop for Sleep {
-    fn drop(self: Pin<&mut Self>) {
-        if self.registered {
-            self.unregister();
-        }
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.unregister();
     }
 }
 
@@ -433,4 +559,100 @@ mod test {
         assert_dur(now.elapsed(), Duration::from_millis(1500), "seq");
         j.join().unwrap();
     }
+
+    #[test]
+    fn manual_clock() {
+        let (mut timers, handle) = Timers::manual();
+        let start = handle.now();
+
+        let sleep = handle.sleep(Duration::from_secs(1));
+        futures::pin_mut!(sleep);
+        assert_eq!(sleep.as_mut().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(999));
+        assert_eq!(sleep.as_mut().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(1));
+        assert_eq!(sleep.as_mut().now_or_never(), Some(()));
+        assert_eq!(handle.now() - start, Duration::from_secs(1));
+
+        let mut interval = Interval::new(&handle, handle.now(), Duration::from_millis(100));
+        assert_eq!(interval.next().now_or_never(), Some(Some(handle.now())));
+        assert_eq!(interval.next().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(100));
+        assert_eq!(interval.next().now_or_never(), Some(Some(handle.now())));
+    }
+
+    #[test]
+    fn many_timers() {
+        let (mut timers, handle) = Timers::manual();
+
+        let count = if cfg!(miri) { 100 } else { 20_000 };
+
+        let mut sleeps = (0..count)
+            .map(|i| Box::pin(handle.sleep(Duration::from_millis(i % 100 + 1))))
+            .collect::<Vec<_>>();
+
+        for sleep in &mut sleeps {
+            assert_eq!(sleep.as_mut().now_or_never(), None);
+        }
+
+        let registered = |timers: &Timers| {
+            timers
+                .inner
+                .timers
+                .iter()
+                .map(|v| v.lock().entries.len())
+                .sum::<usize>()
+        };
+
+        // Dropping a sleep removes its timer
+        let kept = sleeps.split_off(count as usize / 2);
+        drop(sleeps);
+        assert_eq!(registered(&timers), kept.len());
+
+        timers.advance(Duration::from_millis(100));
+        assert_eq!(registered(&timers), 0);
+
+        for sleep in kept {
+            assert_eq!(sleep.now_or_never(), Some(()));
+        }
+    }
+
+    #[test]
+    fn concurrent_timers() {
+        let (mut timers, handle) = Timers::manual();
+        let handle = Arc::new(handle);
+
+        let count = if cfg!(miri) { 10 } else { 2_000 };
+
+        // Registers and removes timers from several threads at once
+        let threads = (0..4)
+            .map(|_| {
+                let handle = handle.clone();
+                thread::spawn(move || {
+                    (0..count)
+                        .filter_map(|i| {
+                            let mut sleep = Box::pin(handle.sleep(Duration::from_millis(10)));
+                            assert_eq!(sleep.as_mut().now_or_never(), None);
+                            (i % 2 == 0).then_some(sleep)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let sleeps = threads
+            .into_iter()
+            .flat_map(|v| v.join().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(sleeps.len(), 4 * count / 2);
+
+        timers.advance(Duration::from_millis(10));
+        for sleep in sleeps {
+            assert_eq!(sleep.now_or_never(), Some(()));
+        }
+    }
 }
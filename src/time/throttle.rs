@@ -0,0 +1,124 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{Future, Stream};
+use pin_project::pin_project;
+
+use crate::time::{Sleep, TimersHandle, GLOBAL_TIMER};
+
+/// Yields at most one item of `stream` per `period`.
+///
+/// Useful for rate limiting work such as autosaving.
+pub fn throttle<S: Stream>(stream: S, period: Duration) -> Throttle<S> {
+    Throttle::new(&GLOBAL_TIMER, stream, period)
+}
+
+/// Yields at most one item per period.
+///
+/// An item is yielded immediately if the period has passed since the last item. Otherwise the
+/// latest item is held back until the end of the period, and earlier items are dropped.
+#[pin_project]
+pub struct Throttle<S: Stream> {
+    #[pin]
+    stream: S,
+    sleep: Pin<Box<Sleep>>,
+    period: Duration,
+    cooling: bool,
+    pending: Option<S::Item>,
+    done: bool,
+}
+
+impl<S: Stream> Throttle<S> {
+    pub fn new(handle: &TimersHandle, stream: S, period: Duration) -> Self {
+        Self {
+            stream,
+            sleep: Box::pin(Sleep::new(handle, handle.now())),
+            period,
+            cooling: false,
+            pending: None,
+            done: false,
+        }
+    }
+}
+
+impl<S: Stream> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut p = self.project();
+
+        while !*p.done {
+            match p.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) if !*p.cooling => {
+                    *p.cooling = true;
+                    let deadline = p.sleep.now() + *p.period;
+                    p.sleep.as_mut().reset(deadline);
+                    return Poll::Ready(Some(item));
+                }
+                Poll::Ready(Some(item)) => *p.pending = Some(item),
+                Poll::Ready(None) => *p.done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        if *p.cooling && p.sleep.as_mut().poll(cx).is_ready() {
+            if let Some(item) = p.pending.take() {
+                let deadline = p.sleep.now() + *p.period;
+                p.sleep.as_mut().reset(deadline);
+                return Poll::Ready(Some(item));
+            }
+
+            *p.cooling = false;
+        }
+
+        if *p.done && p.pending.is_none() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{FutureExt, StreamExt};
+
+    use crate::time::Timers;
+
+    use super::*;
+
+    #[test]
+    fn throttle() {
+        let (mut timers, handle) = Timers::manual();
+        let (tx, rx) = flume::unbounded();
+
+        let mut stream = Throttle::new(&handle, rx.into_stream(), Duration::from_millis(100));
+
+        tx.send(0).unwrap();
+        assert_eq!(stream.next().now_or_never(), Some(Some(0)));
+
+        for i in 1..4 {
+            timers.advance(Duration::from_millis(20));
+            tx.send(i).unwrap();
+            assert_eq!(stream.next().now_or_never(), None);
+        }
+
+        // The latest item is held back until the end of the period
+        timers.advance(Duration::from_millis(40));
+        assert_eq!(stream.next().now_or_never(), Some(Some(3)));
+
+        timers.advance(Duration::from_millis(100));
+        assert_eq!(stream.next().now_or_never(), None);
+
+        timers.advance(Duration::from_millis(60));
+        tx.send(4).unwrap();
+        assert_eq!(stream.next().now_or_never(), Some(Some(4)));
+
+        drop(tx);
+        timers.advance(Duration::from_millis(100));
+        assert_eq!(stream.next().now_or_never(), Some(None));
+    }
+}
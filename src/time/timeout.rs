@@ -0,0 +1,107 @@
+use std::{
+    fmt::Display,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::Future;
+use pin_project::pin_project;
+
+use crate::time::{Sleep, TimersHandle, GLOBAL_TIMER};
+
+/// Requires `fut` to complete within `duration`
+pub fn timeout<F: Future>(duration: Duration, fut: F) -> Timeout<F> {
+    Timeout::new(&GLOBAL_TIMER, GLOBAL_TIMER.now() + duration, fut)
+}
+
+/// Requires `fut` to complete before `deadline`
+pub fn timeout_at<F: Future>(deadline: Instant, fut: F) -> Timeout<F> {
+    Timeout::new(&GLOBAL_TIMER, deadline, fut)
+}
+
+/// The deadline of a [`Timeout`] elapsed before the future completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Deadline elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Resolves to the output of the future, or [`Elapsed`] if the deadline is reached first.
+///
+/// The inner future is dropped along with the timeout.
+#[pin_project]
+#[derive(Debug)]
+pub struct Timeout<F> {
+    #[pin]
+    fut: F,
+    #[pin]
+    sleep: Sleep,
+}
+
+impl<F: Future> Timeout<F> {
+    pub fn new(handle: &TimersHandle, deadline: Instant, fut: F) -> Self {
+        Self {
+            fut,
+            sleep: Sleep::new(handle, deadline),
+        }
+    }
+
+    pub fn get_ref(&self) -> &F {
+        &self.fut
+    }
+
+    pub fn into_inner(self) -> F {
+        self.fut
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let p = self.project();
+
+        if let Poll::Ready(value) = p.fut.poll(cx) {
+            return Poll::Ready(Ok(value));
+        }
+
+        p.sleep.poll(cx).map(|_| Err(Elapsed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::time::{assert_dur, setup_timers};
+
+    use super::*;
+
+    #[test]
+    fn timeout() {
+        let (handle, j) = setup_timers();
+
+        let now = Instant::now();
+        futures::executor::block_on(async {
+            let fast = Sleep::new(&handle, Instant::now() + Duration::from_millis(100));
+            let res =
+                Timeout::new(&handle, Instant::now() + Duration::from_millis(200), fast).await;
+            assert_eq!(res, Ok(()));
+
+            let slow = Sleep::new(&handle, Instant::now() + Duration::from_millis(400));
+            let res =
+                Timeout::new(&handle, Instant::now() + Duration::from_millis(200), slow).await;
+            assert_eq!(res, Err(Elapsed));
+        });
+
+        #[cfg(not(miri))]
+        assert_dur(now.elapsed(), Duration::from_millis(300), "timeout");
+
+        drop(handle);
+        j.join().unwrap();
+    }
+}
@@ -2,6 +2,8 @@ use std::ops;
 
 use glam::Vec2;
 
+use crate::animation::Tweenable;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Unit<T> {
     px: T,
@@ -38,6 +40,15 @@ where
     }
 }
 
+impl<T: Tweenable> Tweenable for Unit<T> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            px: self.px.lerp(&other.px, t),
+            rel: self.rel.lerp(&other.rel, t),
+        }
+    }
+}
+
 pub trait Zero {
     const ZERO: Self;
 }
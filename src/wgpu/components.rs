@@ -2,10 +2,9 @@ use flax::{component, Debuggable};
 
 use crate::{
     assets::Handle,
+    font::{FontFamilyDesc, FontFromFile, FontStyle, FontWeight},
     wgpu::{
-        font::{Font, FontFromFile},
-        graphics::texture::Texture,
-        shape_renderer::DrawCommand,
+        font::Font, graphics::texture::Texture, shape_renderer::DrawCommand, shaping::ShapedText,
     },
 };
 
@@ -19,6 +18,20 @@ component! {
 
     pub font_from_file: FontFromFile => [ Debuggable ],
 
+    /// Fonts consulted in order for characters missing from `font`
+    pub(crate) font_fallback: Vec<Handle<Font>>,
+
+    /// Selects the font and fallback chain from a family of faces
+    pub font_family: FontFamilyDesc => [ Debuggable ],
+    pub font_weight: FontWeight => [ Debuggable ],
+    pub font_style: FontStyle => [ Debuggable ],
+
+    /// The font chain of each span of rich text. Empty chains inherit the entity font
+    pub(crate) rich_text_fonts: Vec<Vec<Handle<Font>>>,
+
+    /// The laid out glyphs of the text, relative to the widget
+    pub(crate) shaped_text: ShapedText,
+
     /// Renderer specific data for drawing a shape
     pub(crate) draw_cmd: DrawCommand => [ Debuggable ],
 
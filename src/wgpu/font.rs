@@ -1,13 +1,9 @@
-use std::collections::{BTreeMap, BTreeSet};
+use rustybuzz::Face;
 
-use glam::{uvec2, UVec2};
-use guillotiere::{size2, AtlasAllocator};
-use image::{ImageBuffer, Luma};
-use wgpu::{util::DeviceExt, Extent3d, TextureDescriptor, TextureDimension, TextureUsages};
-
-use crate::assets::{fs::BytesFromFile, AssetCache, AssetKey, Handle};
-
-use super::{graphics::texture::Texture, Gpu};
+use crate::{
+    assets::{AssetCache, Handle, TryAssetKey},
+    font::FontFromFile,
+};
 
 /// Loads a font from memory
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
@@ -15,146 +11,61 @@ pub struct FontFromBytes {
     pub bytes: Handle<Vec<u8>>,
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
-pub struct FontFromFile {
-    pub path: BytesFromFile,
-}
-
-impl AssetKey for FontFromFile {
+impl TryAssetKey for FontFromFile {
     type Output = Font;
 
-    fn load(&self, assets: &AssetCache) -> Self::Output {
-        let bytes = assets.load(&self.path);
+    fn try_load(&self, assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        let bytes = assets.try_load(&self.path)?;
 
-        FontFromBytes { bytes }.load(assets)
+        FontFromBytes { bytes }.try_load(assets)
     }
 }
 
+self_cell::self_cell!(
+    /// A font parsed for shaping, borrowing from the raw font data
+    struct ShapingFace {
+        owner: Handle<Vec<u8>>,
+
+        #[covariant]
+        dependent: Face,
+    }
+);
+
 pub struct Font {
     pub(crate) font: fontdue::Font,
+    /// Parsed once when loading, as parsing the shaping tables is expensive.
+    ///
+    /// `None` if the font can be rasterized but not shaped
+    face: Option<ShapingFace>,
 }
 
-impl AssetKey for FontFromBytes {
-    type Output = Font;
-
-    fn load(&self, _assets: &crate::assets::AssetCache) -> Self::Output {
-        let bytes = &*self.bytes;
-        let font = fontdue::Font::from_bytes(bytes.as_ref(), fontdue::FontSettings::default())
-            .map_err(|v| anyhow::anyhow!("Error loading font: {v:?}"))
-            .unwrap();
-
-        Font { font }
+impl Font {
+    /// Returns the font prepared for shaping
+    pub(crate) fn face(&self) -> Option<&Face<'_>> {
+        self.face.as_ref().map(ShapingFace::borrow_dependent)
     }
 }
 
-pub struct GlyphLocation {
-    pub min: UVec2,
-    pub max: UVec2,
-}
+impl TryAssetKey for FontFromBytes {
+    type Output = Font;
 
-pub struct FontAtlas {
-    pub texture: Texture,
-    pub glyphs: BTreeMap<u16, GlyphLocation>,
-    pub chars: BTreeSet<char>,
-}
+    fn try_load(&self, _assets: &AssetCache) -> anyhow::Result<Self::Output> {
+        let bytes = &*self.bytes;
+        let font = fontdue::Font::from_bytes(bytes.as_ref(), fontdue::FontSettings::default())
+            .map_err(|v| anyhow::anyhow!("Error loading font: {v:?}"))?;
 
-impl FontAtlas {
-    pub fn new(
-        _assets: &AssetCache,
-        gpu: &Gpu,
-        font: &Font,
-        px: f32,
-        glyphs: impl IntoIterator<Item = char>,
-    ) -> anyhow::Result<Self> {
-        let mut atlas = AtlasAllocator::new(size2(128, 128));
-
-        let chars = glyphs.into_iter().collect::<BTreeSet<_>>();
-
-        let glyphs = chars
-            .iter()
-            .map(|&c| {
-                let index = font.font.lookup_glyph_index(c);
-
-                let metrics = font.font.metrics_indexed(index, px);
-                let padding = 10;
-
-                let requested_size = size2(
-                    metrics.width as i32 + padding * 2,
-                    metrics.height as i32 + padding * 2,
-                );
-                let v = loop {
-                    if let Some(v) = atlas.allocate(requested_size) {
-                        break v;
-                    } else {
-                        atlas.grow(atlas.size() * 2)
-                    }
-                };
-                let min = uvec2(
-                    (v.rectangle.min.x + padding) as u32,
-                    (v.rectangle.min.y + padding) as u32,
-                );
-                let max = uvec2(
-                    (v.rectangle.max.x - padding) as u32,
-                    (v.rectangle.max.y - padding) as u32,
-                );
-
-                (index, GlyphLocation { min, max })
-            })
-            .collect::<BTreeMap<_, _>>();
-
-        let size = atlas.size();
-        let size = uvec2(size.width as _, size.height as _);
-        let mut image = ImageBuffer::from_pixel(size.x, size.y, Luma([0]));
-
-        // Rasterize and blit without storing all the small bitmaps in memory at the same time
-        glyphs.iter().for_each(|(&glyph, loc)| {
-            let (metrics, pixels) = font.font.rasterize_indexed(glyph, px);
-
-            if metrics.width > 0 {
-                blit_to_image(
-                    &pixels,
-                    &mut image,
-                    loc.min.x as i32,
-                    loc.min.y as i32,
-                    metrics.width as u32,
-                    size.x,
-                );
-            }
-        });
-
-        let texture = gpu.device.create_texture_with_data(
-            &gpu.queue,
-            &TextureDescriptor {
-                label: Some("FontAtlas"),
-                size: Extent3d {
-                    width: size.x,
-                    height: size.y,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Unorm,
-                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                view_formats: &[],
-            },
-            &image,
-        );
-
-        Ok(Self {
-            texture: Texture::from_texture(texture),
-            glyphs,
-            chars,
+        let face = ShapingFace::try_new(self.bytes.clone(), |bytes| {
+            Face::from_slice(bytes, 0).ok_or(())
         })
-    }
+        .ok();
 
-    pub(crate) fn size(&self) -> Extent3d {
-        self.texture.size()
+        Ok(Font { font, face })
     }
+}
 
-    pub(crate) fn contains_char(&self, glyph: char) -> bool {
-        self.chars.contains(&glyph)
-    }
+/// Returns the primary font followed by its fallbacks
+pub(crate) fn font_chain(font: &Handle<Font>, fallback: &[Handle<Font>]) -> Vec<Handle<Font>> {
+    std::iter::once(font).chain(fallback).cloned().collect()
 }
 
 pub fn blit_to_image(src: &[u8], dst: &mut [u8], x: i32, y: i32, src_stride: u32, dst_stride: u32) {
@@ -0,0 +1,209 @@
+use crate::{
+    assets::{AssetCache, AssetKey, Handle},
+    font::{FontFamilyDesc, FontStyle, FontWeight},
+};
+
+use super::font::Font;
+
+impl AssetKey for FontFamilyDesc {
+    type Output = FontFamily;
+
+    fn load(&self, assets: &AssetCache) -> Self::Output {
+        FontFamily {
+            name: self.name.clone(),
+            faces: self
+                .faces
+                .iter()
+                .filter_map(|face| match assets.try_load(&face.font) {
+                    Ok(font) => Some(FontFace {
+                        weight: face.weight,
+                        style: face.style,
+                        font,
+                    }),
+                    Err(err) => {
+                        tracing::error!(family = %self.name, "Failed to load font face: {err:?}");
+                        None
+                    }
+                })
+                .collect(),
+            fallback: self.fallback.iter().map(|v| assets.load(v)).collect(),
+        }
+    }
+}
+
+pub struct FontFace {
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    pub font: Handle<Font>,
+}
+
+/// A set of faces of the same typeface in different weights and styles
+pub struct FontFamily {
+    pub name: String,
+    pub faces: Vec<FontFace>,
+    pub fallback: Vec<Handle<FontFamily>>,
+}
+
+impl FontFamily {
+    /// Returns the face which most closely matches the requested weight and style.
+    ///
+    /// A face of the requested style is always preferred over one with a closer weight.
+    pub fn select(&self, weight: FontWeight, style: FontStyle) -> Option<&Handle<Font>> {
+        self.faces
+            .iter()
+            .min_by_key(|face| (face.style != style, face.weight.0.abs_diff(weight.0)))
+            .map(|face| &face.font)
+    }
+
+    /// Returns the fonts to consult for each character, in order of preference.
+    ///
+    /// The first font is the face selected from this family, followed by the selected faces of
+    /// each fallback family.
+    pub fn chain(&self, weight: FontWeight, style: FontStyle) -> Vec<Handle<Font>> {
+        let mut fonts = Vec::new();
+        self.collect_chain(weight, style, &mut fonts);
+        fonts
+    }
+
+    fn collect_chain(&self, weight: FontWeight, style: FontStyle, fonts: &mut Vec<Handle<Font>>) {
+        if let Some(font) = self.select(weight, style) {
+            if !fonts.contains(font) {
+                fonts.push(font.clone());
+            }
+        }
+
+        for family in &self.fallback {
+            family.collect_chain(weight, style, fonts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assets::{
+            fs::{BytesFromFile, MemorySource},
+            AssetId,
+        },
+        font::FontFromFile,
+    };
+
+    use super::*;
+
+    fn face(name: &str) -> FontFromFile {
+        FontFromFile {
+            path: BytesFromFile(format!("fonts/{name}.ttf").into()),
+        }
+    }
+
+    #[test]
+    fn select_and_chain() {
+        let assets = AssetCache::new();
+
+        // Each face is a separate file, which is enough to tell them apart
+        let inter: &[u8] = include_bytes!("../../assets/fonts/Inter/static/Inter-Regular.ttf");
+        let source = [
+            "regular",
+            "bold",
+            "light_italic",
+            "fallback",
+            "fallback_bold",
+        ]
+        .into_iter()
+        .fold(MemorySource::new(), |source, name| {
+            source.with_file(format!("{name}.ttf"), inter)
+        });
+
+        assets.fs().mount("fonts", source);
+
+        let fallback = FontFamilyDesc::new("Fallback")
+            .with_face(FontWeight::REGULAR, FontStyle::Normal, face("fallback"))
+            .with_face(FontWeight::BOLD, FontStyle::Normal, face("fallback_bold"));
+
+        let desc = FontFamilyDesc::new("Family")
+            .with_face(FontWeight::REGULAR, FontStyle::Normal, face("regular"))
+            .with_face(FontWeight::BOLD, FontStyle::Normal, face("bold"))
+            .with_face(FontWeight::LIGHT, FontStyle::Italic, face("light_italic"))
+            .with_fallback(fallback.clone())
+            .with_fallback(FontFamilyDesc::new("Duplicate").with_face(
+                FontWeight::REGULAR,
+                FontStyle::Normal,
+                face("regular"),
+            ));
+
+        let family = assets.load(&desc);
+        let fallback = assets.load(&fallback);
+
+        let font = |name| assets.try_load(&face(name)).unwrap().id();
+        let select =
+            |family: &FontFamily, weight, style| family.select(weight, style).map(Handle::id);
+        let chain = |weight, style| {
+            family
+                .chain(weight, style)
+                .iter()
+                .map(Handle::id)
+                .collect::<Vec<AssetId>>()
+        };
+
+        assert_eq!(family.faces.len(), 3);
+
+        // The nearest weight is used for missing weights
+        assert_eq!(
+            select(&family, FontWeight::REGULAR, FontStyle::Normal),
+            Some(font("regular"))
+        );
+        assert_eq!(
+            select(&family, FontWeight::SEMI_BOLD, FontStyle::Normal),
+            Some(font("bold"))
+        );
+        assert_eq!(
+            select(&family, FontWeight::THIN, FontStyle::Normal),
+            Some(font("regular"))
+        );
+
+        // The requested style is preferred over a closer weight
+        assert_eq!(
+            select(&family, FontWeight::BLACK, FontStyle::Italic),
+            Some(font("light_italic"))
+        );
+
+        // A missing style falls back to the nearest weight of another style
+        assert_eq!(
+            select(&fallback, FontWeight::BLACK, FontStyle::Italic),
+            Some(font("fallback_bold"))
+        );
+
+        // The face of this family comes first, followed by the fallback families in order.
+        //
+        // Fonts already in the chain are not repeated
+        assert_eq!(
+            chain(FontWeight::REGULAR, FontStyle::Normal),
+            [font("regular"), font("fallback")]
+        );
+        assert_eq!(
+            chain(FontWeight::BOLD, FontStyle::Normal),
+            [font("bold"), font("fallback_bold"), font("regular")]
+        );
+        assert_eq!(
+            chain(FontWeight::LIGHT, FontStyle::Italic),
+            [font("light_italic"), font("fallback"), font("regular")]
+        );
+
+        // Families without any faces are skipped
+        let empty = assets.load(&FontFamilyDesc::new("Empty").with_fallback(desc.clone()));
+        assert_eq!(
+            empty
+                .select(FontWeight::REGULAR, FontStyle::Normal)
+                .map(Handle::id),
+            None
+        );
+        assert_eq!(
+            empty
+                .chain(FontWeight::REGULAR, FontStyle::Normal)
+                .iter()
+                .map(Handle::id)
+                .collect::<Vec<_>>(),
+            chain(FontWeight::REGULAR, FontStyle::Normal)
+        );
+    }
+}
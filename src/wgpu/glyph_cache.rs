@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+
+use glam::{uvec2, vec2, UVec2, Vec2};
+use guillotiere::{size2, AllocId, AtlasAllocator};
+use wgpu::{Extent3d, TextureDescriptor, TextureDimension, TextureUsages};
+
+use crate::assets::{AssetId, Handle};
+
+use super::{
+    font::{blit_to_image, Font},
+    graphics::texture::Texture,
+    Gpu,
+};
+
+/// Identifies a rasterized glyph of a font at a specific size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    font: AssetId,
+    glyph: u16,
+    /// Bit representation of the font size in pixels
+    px: u32,
+}
+
+impl GlyphKey {
+    pub fn new(font: &Handle<Font>, glyph: u16, px: f32) -> Self {
+        Self {
+            font: font.id(),
+            glyph,
+            px: px.to_bits(),
+        }
+    }
+}
+
+/// Location of a glyph within the atlas texture, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphLocation {
+    pub min: UVec2,
+    pub max: UVec2,
+    /// Offset from the glyph origin on the baseline to the top left corner of the bitmap
+    pub offset: Vec2,
+}
+
+struct CachedGlyph {
+    alloc: Option<AllocId>,
+    location: GlyphLocation,
+    last_used: u64,
+}
+
+/// Space around each glyph to prevent bleeding when sampling
+const GLYPH_PADDING: i32 = 1;
+
+/// Size of the solid region, large enough for linear filtering to remain fully covered
+const SOLID_SIZE: u32 = 4;
+
+/// Tracks where glyphs are placed within the atlas, independently of the texture
+struct GlyphAtlas {
+    allocator: AtlasAllocator,
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
+    max_size: u32,
+
+    /// Incremented for every rebuild of the text meshes
+    tick: u64,
+    /// Incremented when the atlas grows or glyphs are evicted.
+    ///
+    /// Meshes built using an older generation must be rebuilt.
+    generation: u32,
+}
+
+impl GlyphAtlas {
+    fn new(initial_size: u32, max_size: u32) -> Self {
+        Self {
+            allocator: AtlasAllocator::new(size2(initial_size as i32, initial_size as i32)),
+            glyphs: HashMap::new(),
+            max_size,
+            tick: 0,
+            generation: 0,
+        }
+    }
+
+    fn size(&self) -> u32 {
+        self.allocator.size().width as u32
+    }
+
+    /// Returns the location of a cached glyph and marks it as used
+    fn get(&mut self, key: &GlyphKey) -> Option<GlyphLocation> {
+        let cached = self.glyphs.get_mut(key)?;
+        cached.last_used = self.tick;
+        Some(cached.location)
+    }
+
+    fn insert(&mut self, key: GlyphKey, alloc: Option<AllocId>, location: GlyphLocation) {
+        self.glyphs.insert(
+            key,
+            CachedGlyph {
+                alloc,
+                location,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    /// Allocates space in the atlas, growing it or evicting unused glyphs if full
+    fn allocate(&mut self, size: guillotiere::Size) -> Option<guillotiere::Allocation> {
+        loop {
+            if let Some(alloc) = self.allocator.allocate(size) {
+                return Some(alloc);
+            }
+
+            let cur_size = self.size();
+            if cur_size < self.max_size {
+                let new_size = (cur_size * 2).min(self.max_size);
+                tracing::debug!(new_size, "Growing glyph atlas");
+
+                self.allocator.grow(size2(new_size as i32, new_size as i32));
+                self.generation = self.generation.wrapping_add(1);
+            } else if !self.evict_unused() {
+                tracing::warn!(?size, "Glyph atlas is full");
+                return None;
+            }
+        }
+    }
+
+    /// Evicts the least recently used half of the glyphs which were not used during the current
+    /// tick.
+    ///
+    /// Returns false if there was nothing to evict
+    fn evict_unused(&mut self) -> bool {
+        let mut unused = self
+            .glyphs
+            .iter()
+            .filter(|(_, v)| v.last_used < self.tick)
+            .map(|(&k, v)| (v.last_used, k))
+            .collect::<Vec<_>>();
+
+        if unused.is_empty() {
+            return false;
+        }
+
+        unused.sort_unstable_by_key(|v| v.0);
+
+        let count = (unused.len() / 2).max(1);
+        tracing::debug!(count, "Evicting glyphs");
+
+        for (_, key) in &unused[..count] {
+            if let Some(alloc) = self.glyphs.remove(key).and_then(|v| v.alloc) {
+                self.allocator.deallocate(alloc);
+            }
+        }
+
+        self.generation = self.generation.wrapping_add(1);
+        true
+    }
+}
+
+/// A persistent glyph atlas shared by all text.
+///
+/// Glyphs are rasterized on demand and uploaded individually. The atlas grows when it runs out of
+/// space, and when it can no longer grow the least recently used glyphs are evicted.
+pub struct GlyphCache {
+    texture: Texture,
+    atlas: GlyphAtlas,
+    /// Center of a fully covered region used for drawing solid quads
+    solid: Vec2,
+}
+
+impl GlyphCache {
+    pub fn new(gpu: &Gpu, initial_size: u32, max_size: u32) -> Self {
+        let texture = create_texture(gpu, initial_size);
+        let mut atlas = GlyphAtlas::new(
+            initial_size,
+            max_size.min(gpu.device.limits().max_texture_dimension_2d),
+        );
+
+        // Reserve a permanently covered region for underlines and other solid quads
+        let solid = atlas
+            .allocator
+            .allocate(size2(SOLID_SIZE as i32, SOLID_SIZE as i32))
+            .expect("Glyph atlas is too small")
+            .rectangle
+            .min;
+
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &*texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: solid.x as u32,
+                    y: solid.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &[255; (SOLID_SIZE * SOLID_SIZE) as usize],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(SOLID_SIZE),
+                rows_per_image: Some(SOLID_SIZE),
+            },
+            Extent3d {
+                width: SOLID_SIZE,
+                height: SOLID_SIZE,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            texture,
+            atlas,
+            solid: vec2(solid.x as f32, solid.y as f32) + SOLID_SIZE as f32 / 2.0,
+        }
+    }
+
+    /// Returns a texel position inside a fully covered region of the atlas.
+    ///
+    /// Used for drawing decorations such as underlines.
+    pub fn solid(&self) -> Vec2 {
+        self.solid
+    }
+
+    /// Advances the cache clock.
+    ///
+    /// Glyphs used since the last tick are never evicted.
+    pub fn tick(&mut self) {
+        self.atlas.tick += 1;
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.atlas.generation
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn len(&self) -> usize {
+        self.atlas.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.atlas.glyphs.is_empty()
+    }
+
+    /// Returns the location of the glyph in the atlas, rasterizing it if necessary.
+    ///
+    /// Returns `None` if the glyph does not fit in the atlas
+    pub fn get(
+        &mut self,
+        gpu: &Gpu,
+        font: &Handle<Font>,
+        glyph: u16,
+        px: f32,
+    ) -> Option<GlyphLocation> {
+        let key = GlyphKey::new(font, glyph, px);
+
+        if let Some(location) = self.atlas.get(&key) {
+            return Some(location);
+        }
+
+        let (metrics, pixels) = font.font.rasterize_indexed(glyph, px);
+
+        // Whitespace has nothing to upload
+        if metrics.width == 0 || metrics.height == 0 {
+            let location = GlyphLocation {
+                min: UVec2::ZERO,
+                max: UVec2::ZERO,
+                offset: Vec2::ZERO,
+            };
+
+            self.atlas.insert(key, None, location);
+            return Some(location);
+        }
+
+        let requested_size = size2(
+            metrics.width as i32 + GLYPH_PADDING * 2,
+            metrics.height as i32 + GLYPH_PADDING * 2,
+        );
+
+        let alloc = self.atlas.allocate(requested_size);
+
+        // The atlas may have grown even if the glyph did not fit
+        if self.atlas.size() != self.texture.size().width {
+            self.grow(gpu, self.atlas.size());
+        }
+
+        let alloc = alloc?;
+
+        let min = uvec2(
+            (alloc.rectangle.min.x + GLYPH_PADDING) as u32,
+            (alloc.rectangle.min.y + GLYPH_PADDING) as u32,
+        );
+        let max = min + uvec2(metrics.width as u32, metrics.height as u32);
+
+        // Upload the padding as well to clear out any previously evicted glyph
+        let padded_width = requested_size.width as u32;
+        let padded_height = requested_size.height as u32;
+        let mut padded = vec![0; (padded_width * padded_height) as usize];
+        blit_to_image(
+            &pixels,
+            &mut padded,
+            GLYPH_PADDING,
+            GLYPH_PADDING,
+            metrics.width as u32,
+            padded_width,
+        );
+
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &*self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: alloc.rectangle.min.x as u32,
+                    y: alloc.rectangle.min.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &padded,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_width),
+                rows_per_image: Some(padded_height),
+            },
+            Extent3d {
+                width: padded_width,
+                height: padded_height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let location = GlyphLocation {
+            min,
+            max,
+            offset: vec2(
+                metrics.xmin as f32,
+                -(metrics.ymin as f32 + metrics.height as f32),
+            ),
+        };
+
+        self.atlas.insert(key, Some(alloc.id), location);
+
+        Some(location)
+    }
+
+    /// Replaces the texture with a larger one, keeping the uploaded glyphs
+    fn grow(&mut self, gpu: &Gpu, new_size: u32) {
+        let old_size = self.texture.size();
+        let texture = create_texture(gpu, new_size);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("GlyphCache::grow"),
+            });
+
+        encoder.copy_texture_to_texture(
+            wgpu::ImageCopyTexture {
+                texture: &*self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyTexture {
+                texture: &*texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            old_size,
+        );
+
+        gpu.queue.submit([encoder.finish()]);
+
+        self.texture = texture;
+    }
+}
+
+fn create_texture(gpu: &Gpu, size: u32) -> Texture {
+    let texture = gpu.device.create_texture(&TextureDescriptor {
+        label: Some("GlyphCache"),
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+
+    Texture::from_texture(texture)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(glyph: u16) -> GlyphKey {
+        GlyphKey {
+            font: AssetId::default(),
+            glyph,
+            px: 16f32.to_bits(),
+        }
+    }
+
+    /// Allocates and caches a glyph of `size` pixels
+    fn insert(atlas: &mut GlyphAtlas, glyph: u16, size: i32) -> Option<GlyphLocation> {
+        let alloc = atlas.allocate(size2(size, size))?;
+        let min = uvec2(alloc.rectangle.min.x as u32, alloc.rectangle.min.y as u32);
+
+        let location = GlyphLocation {
+            min,
+            max: min + size as u32,
+            offset: Vec2::ZERO,
+        };
+
+        atlas.insert(key(glyph), Some(alloc.id), location);
+        Some(location)
+    }
+
+    #[test]
+    fn allocate_until_full() {
+        let mut atlas = GlyphAtlas::new(16, 32);
+
+        for glyph in 0..4 {
+            assert!(insert(&mut atlas, glyph, 8).is_some());
+        }
+
+        assert_eq!(atlas.size(), 16);
+        assert_eq!(atlas.generation, 0);
+
+        // Grows once the initial size is exhausted
+        for glyph in 4..16 {
+            assert!(insert(&mut atlas, glyph, 8).is_some());
+        }
+
+        assert_eq!(atlas.size(), 32);
+        assert_eq!(atlas.generation, 1);
+
+        // All glyphs are in use during the current tick, so nothing can be evicted
+        assert_eq!(insert(&mut atlas, 16, 8), None);
+        assert_eq!(atlas.glyphs.len(), 16);
+        assert_eq!(atlas.generation, 1);
+    }
+
+    #[test]
+    fn evict_unused() {
+        let mut atlas = GlyphAtlas::new(32, 32);
+
+        for glyph in 0..16 {
+            insert(&mut atlas, glyph, 8).unwrap();
+        }
+
+        atlas.tick += 1;
+
+        // Glyphs used during the current tick are kept
+        for glyph in 0..4 {
+            assert!(atlas.get(&key(glyph)).is_some());
+        }
+
+        let location = insert(&mut atlas, 16, 8).unwrap();
+
+        // Half of the 12 unused glyphs were evicted to make room
+        assert_eq!(atlas.generation, 1);
+        assert_eq!(atlas.glyphs.len(), 16 - 6 + 1);
+        assert!((0..4).all(|v| atlas.glyphs.contains_key(&key(v))));
+        assert_eq!(atlas.get(&key(16)), Some(location));
+
+        let evicted = (4..16)
+            .filter(|&v| !atlas.glyphs.contains_key(&key(v)))
+            .collect::<Vec<_>>();
+        assert_eq!(evicted.len(), 6);
+
+        // Evicted glyphs are allocated again on their next use
+        let glyph = evicted[0];
+        assert_eq!(atlas.get(&key(glyph)), None);
+
+        let location = insert(&mut atlas, glyph, 8).unwrap();
+        assert_eq!(atlas.get(&key(glyph)), Some(location));
+        assert_eq!(atlas.generation, 1);
+    }
+}
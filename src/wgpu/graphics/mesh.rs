@@ -1,4 +1,4 @@
-use glam::{vec2, vec3, Vec2, Vec3};
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
 use wgpu::{
     util::DeviceExt, vertex_attr_array, Buffer, RenderPass, VertexAttribute, VertexBufferLayout,
 };
@@ -10,6 +10,8 @@ use super::Gpu;
 pub struct Vertex {
     pos: Vec3,
     tex_coord: Vec2,
+    /// Multiplied with the object color
+    color: [f32; 4],
 }
 
 pub trait VertexDesc {
@@ -18,12 +20,31 @@ pub trait VertexDesc {
 
 impl Vertex {
     pub const fn new(pos: Vec3, tex_coord: Vec2) -> Self {
-        Self { pos, tex_coord }
+        Self {
+            pos,
+            tex_coord,
+            color: [1.0; 4],
+        }
+    }
+
+    /// Sets the linear vertex color
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color.to_array();
+        self
+    }
+
+    pub fn pos(&self) -> Vec3 {
+        self.pos
+    }
+
+    pub fn color(&self) -> Vec4 {
+        Vec4::from_array(self.color)
     }
 }
 impl VertexDesc for Vertex {
     fn layout() -> VertexBufferLayout<'static> {
-        static ATTRIBUTES: &[VertexAttribute] = &vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+        static ATTRIBUTES: &[VertexAttribute] =
+            &vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x4];
 
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
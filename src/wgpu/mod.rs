@@ -1,9 +1,13 @@
 pub mod components;
 pub mod font;
+pub mod font_family;
+pub mod glyph_cache;
 pub mod graphics;
 pub mod mesh_buffer;
+pub mod path_renderer;
 pub mod rect_renderer;
 mod shape_renderer;
+pub mod shaping;
 pub mod systems;
 pub mod text_renderer;
 mod texture;
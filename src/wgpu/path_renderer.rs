@@ -0,0 +1,510 @@
+use flax::{
+    entity_ids,
+    fetch::{Modified, TransformFetch},
+    filter::{All, With},
+    CommandBuffer, Component, EntityIds, Fetch, FetchExt, Mutable, Opt, Query,
+};
+use glam::{vec3, Mat4, Quat, Vec2, Vec3};
+use lyon::{
+    geom::Arc,
+    math::{point, vector, Angle, Point},
+    path::{path::Builder, Path, Winding},
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex, TessellationError, VertexBuffers,
+    },
+};
+use wgpu::{BindGroup, BindGroupLayout, TextureFormat};
+
+use crate::{
+    assets::Handle,
+    components::{rect, screen_position, shape, Rect},
+    shapes::{LineCap, LineJoin, PathCommand, Shape, ShapePath, Stroke},
+    unit::Unit,
+    Frame,
+};
+
+use super::{
+    components::{draw_cmd, mesh_handle, model_matrix},
+    graphics::{
+        shader::ShaderDesc, BindGroupBuilder, BindGroupLayoutBuilder, Shader, Vertex, VertexDesc,
+    },
+    mesh_buffer::MeshHandle,
+    renderer::RendererContext,
+    shape_renderer::{srgba_to_vec4, DrawCommand},
+    Gpu,
+};
+
+#[derive(Fetch)]
+struct ObjectQuery {
+    rect: Component<Rect>,
+    pos: Component<Vec2>,
+    model_matrix: Mutable<Mat4>,
+}
+
+impl ObjectQuery {
+    pub fn new() -> Self {
+        Self {
+            rect: rect(),
+            pos: screen_position(),
+            model_matrix: model_matrix().as_mut(),
+        }
+    }
+}
+
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+/// Query shape entities which need to be tessellated
+pub struct ShapeMeshQuery {
+    #[fetch(ignore)]
+    id: EntityIds,
+    #[fetch(ignore)]
+    mesh: Opt<Mutable<MeshHandle>>,
+
+    rect: Component<Rect>,
+    shape: Component<Shape>,
+}
+
+impl ShapeMeshQuery {
+    fn new() -> Self {
+        Self {
+            id: entity_ids(),
+            mesh: mesh_handle().as_mut().opt(),
+            rect: rect(),
+            shape: shape(),
+        }
+    }
+}
+
+/// Tessellates and draws vector shapes
+pub struct PathRenderer {
+    shader: Handle<Shader>,
+    bind_group: Handle<BindGroup>,
+
+    fill_tessellator: FillTessellator,
+    stroke_tessellator: StrokeTessellator,
+
+    object_query: Query<ObjectQuery, (All, With)>,
+    mesh_query: Query<<ShapeMeshQuery as TransformFetch<Modified>>::Output, All>,
+}
+
+impl PathRenderer {
+    pub fn new(
+        ctx: &mut RendererContext,
+        frame: &mut Frame,
+        color_format: TextureFormat,
+        object_layout: &BindGroupLayout,
+    ) -> Self {
+        // Shapes are colored per vertex and do not need any additional resources
+        let layout = BindGroupLayoutBuilder::new("PathRenderer::layout").build(&ctx.gpu);
+
+        let bind_group = frame
+            .assets
+            .insert(BindGroupBuilder::new("PathRenderer::bind_group").build(&ctx.gpu, &layout));
+
+        let shader = frame.assets.insert(Shader::new(
+            &ctx.gpu,
+            &ShaderDesc {
+                label: "PathRenderer::shader",
+                source: include_str!("../../assets/shaders/path.wgsl"),
+                format: color_format,
+                vertex_layouts: &[Vertex::layout()],
+                layouts: &[&ctx.globals_layout, object_layout, &layout],
+            },
+        ));
+
+        Self {
+            shader,
+            bind_group,
+            fill_tessellator: FillTessellator::new(),
+            stroke_tessellator: StrokeTessellator::new(),
+            object_query: Query::new(ObjectQuery::new()).with(shape()),
+            mesh_query: Query::new(ShapeMeshQuery::new().transform_fetch(Modified)),
+        }
+    }
+
+    pub fn update_meshes(&mut self, ctx: &mut RendererContext, frame: &mut Frame) {
+        let mut cmd = CommandBuffer::new();
+
+        (self.mesh_query.borrow(&frame.world)).for_each(|item| {
+            let geometry = match tessellate(
+                &mut self.fill_tessellator,
+                &mut self.stroke_tessellator,
+                item.shape,
+                item.rect.size(),
+            ) {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::error!(%item.id, "Failed to tessellate shape: {err:?}");
+                    return;
+                }
+            };
+
+            let vertices = &geometry.vertices;
+            let indices = &geometry.indices;
+
+            // Nothing to draw, such as a shape without fill or stroke. An existing mesh is kept
+            // for when the shape becomes visible again
+            if indices.is_empty() {
+                if frame.world.has(item.id, draw_cmd()) {
+                    cmd.remove(item.id, draw_cmd());
+                }
+                return;
+            }
+
+            let mesh = match item.mesh {
+                Some(mesh) => {
+                    ctx.mesh_buffer
+                        .reallocate(&ctx.gpu, mesh, vertices.len(), indices.len());
+
+                    *mesh
+                }
+                None => {
+                    let mesh = ctx
+                        .mesh_buffer
+                        .allocate(&ctx.gpu, vertices.len(), indices.len());
+                    cmd.set(item.id, mesh_handle(), mesh);
+                    mesh
+                }
+            };
+
+            ctx.mesh_buffer.write(&ctx.gpu, &mesh, vertices, indices);
+
+            cmd.set(
+                item.id,
+                draw_cmd(),
+                DrawCommand {
+                    mesh,
+                    bind_group: self.bind_group.clone(),
+                    shader: self.shader.clone(),
+                    index_count: indices.len() as u32,
+                    vertex_offset: mesh.vb().start() as i32,
+                },
+            );
+        });
+
+        cmd.apply(&mut frame.world).unwrap();
+    }
+
+    pub fn update(&mut self, _: &Gpu, frame: &Frame) {
+        self.object_query
+            .borrow(&frame.world)
+            .iter()
+            .for_each(|item| {
+                let pos = *item.pos + item.rect.pos();
+                *item.model_matrix = Mat4::from_scale_rotation_translation(
+                    Vec3::ONE,
+                    Quat::IDENTITY,
+                    pos.extend(0.1),
+                );
+            })
+    }
+}
+
+/// Tessellates the shape into vertices local to a rect of `size`
+fn tessellate(
+    fill_tessellator: &mut FillTessellator,
+    stroke_tessellator: &mut StrokeTessellator,
+    shape: &Shape,
+    size: Vec2,
+) -> Result<VertexBuffers<Vertex, u32>, TessellationError> {
+    let path = build_path(&shape.path, size);
+
+    let mut geometry = VertexBuffers::new();
+
+    if let Some(fill) = shape.fill {
+        let color = srgba_to_vec4(fill);
+        fill_tessellator.tessellate_path(
+            &path,
+            &FillOptions::default(),
+            &mut BuffersBuilder::new(&mut geometry, |v: FillVertex| {
+                let pos = v.position();
+                Vertex::new(vec3(pos.x, pos.y, 0.0), Vec2::ZERO).with_color(color)
+            }),
+        )?;
+    }
+
+    if let Some(stroke) = &shape.stroke {
+        let color = srgba_to_vec4(stroke.color);
+        stroke_tessellator.tessellate_path(
+            &path,
+            &stroke_options(stroke),
+            &mut BuffersBuilder::new(&mut geometry, |v: StrokeVertex| {
+                let pos = v.position();
+                Vertex::new(vec3(pos.x, pos.y, 0.0), Vec2::ZERO).with_color(color)
+            }),
+        )?;
+    }
+
+    Ok(geometry)
+}
+
+fn stroke_options(stroke: &Stroke) -> StrokeOptions {
+    let cap = match stroke.cap {
+        LineCap::Butt => lyon::tessellation::LineCap::Butt,
+        LineCap::Square => lyon::tessellation::LineCap::Square,
+        LineCap::Round => lyon::tessellation::LineCap::Round,
+    };
+
+    let join = match stroke.join {
+        LineJoin::Miter => lyon::tessellation::LineJoin::Miter,
+        LineJoin::Round => lyon::tessellation::LineJoin::Round,
+        LineJoin::Bevel => lyon::tessellation::LineJoin::Bevel,
+    };
+
+    StrokeOptions::default()
+        .with_line_width(stroke.width)
+        .with_line_cap(cap)
+        .with_line_join(join)
+}
+
+fn build_path(path: &ShapePath, size: Vec2) -> Path {
+    let resolve = |v: &Unit<Vec2>| -> Point {
+        let v = v.resolve(size);
+        point(v.x, v.y)
+    };
+
+    let mut builder = Path::builder();
+
+    match path {
+        ShapePath::Line { from, to } => {
+            builder.begin(resolve(from));
+            builder.line_to(resolve(to));
+            builder.end(false);
+        }
+        ShapePath::Circle { center, radius } => {
+            builder.add_circle(
+                resolve(center),
+                radius.resolve(size.min_element()),
+                Winding::Positive,
+            );
+        }
+        ShapePath::Ellipse { center, radii } => {
+            let radii = radii.resolve(size);
+            builder.add_ellipse(
+                resolve(center),
+                vector(radii.x, radii.y),
+                Angle::zero(),
+                Winding::Positive,
+            );
+        }
+        ShapePath::Arc {
+            center,
+            radii,
+            start_angle,
+            sweep_angle,
+        } => {
+            let radii = radii.resolve(size);
+            let arc = Arc {
+                center: resolve(center),
+                radii: vector(radii.x, radii.y),
+                start_angle: Angle::radians(*start_angle),
+                sweep_angle: Angle::radians(*sweep_angle),
+                x_rotation: Angle::zero(),
+            };
+
+            builder.begin(arc.from());
+            arc.for_each_cubic_bezier(&mut |segment| {
+                builder.cubic_bezier_to(segment.ctrl1, segment.ctrl2, segment.to);
+            });
+            builder.end(false);
+        }
+        ShapePath::Polyline { points, closed } => {
+            let mut points = points.iter();
+            if let Some(first) = points.next() {
+                builder.begin(resolve(first));
+                for point in points {
+                    builder.line_to(resolve(point));
+                }
+                builder.end(*closed);
+            }
+        }
+        ShapePath::Path(commands) => {
+            let mut in_progress = false;
+            for command in commands {
+                match command {
+                    PathCommand::MoveTo(to) => {
+                        if in_progress {
+                            builder.end(false);
+                        }
+                        builder.begin(resolve(to));
+                        in_progress = true;
+                    }
+                    PathCommand::Close => {
+                        if in_progress {
+                            builder.end(true);
+                            in_progress = false;
+                        }
+                    }
+                    command => {
+                        // Drawing without a starting point implicitly starts at the origin
+                        if !in_progress {
+                            builder.begin(point(0.0, 0.0));
+                            in_progress = true;
+                        }
+
+                        add_command(&mut builder, command, resolve)
+                    }
+                }
+            }
+
+            if in_progress {
+                builder.end(false);
+            }
+        }
+    }
+
+    builder.build()
+}
+
+fn add_command(
+    builder: &mut Builder,
+    command: &PathCommand,
+    resolve: impl Fn(&Unit<Vec2>) -> Point,
+) {
+    match command {
+        PathCommand::LineTo(to) => {
+            builder.line_to(resolve(to));
+        }
+        PathCommand::QuadraticTo { ctrl, to } => {
+            builder.quadratic_bezier_to(resolve(ctrl), resolve(to));
+        }
+        PathCommand::CubicTo { ctrl1, ctrl2, to } => {
+            builder.cubic_bezier_to(resolve(ctrl1), resolve(ctrl2), resolve(to));
+        }
+        PathCommand::MoveTo(_) | PathCommand::Close => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, Vec4};
+    use palette::Srgba;
+
+    use super::*;
+
+    fn tessellate_shape(shape: &Shape, size: Vec2) -> VertexBuffers<Vertex, u32> {
+        tessellate(
+            &mut FillTessellator::new(),
+            &mut StrokeTessellator::new(),
+            shape,
+            size,
+        )
+        .unwrap()
+    }
+
+    fn assert_valid(geometry: &VertexBuffers<Vertex, u32>) {
+        assert_eq!(geometry.indices.len() % 3, 0);
+        assert!(geometry
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < geometry.vertices.len()));
+    }
+
+    fn rect_path() -> ShapePath {
+        ShapePath::Polyline {
+            points: vec![
+                Unit::rel(vec2(0.0, 0.0)),
+                Unit::rel(vec2(1.0, 0.0)),
+                Unit::rel(vec2(1.0, 1.0)),
+                Unit::rel(vec2(0.0, 1.0)),
+            ],
+            closed: true,
+        }
+    }
+
+    #[test]
+    fn fill_rect() {
+        let color = Srgba::new(1.0, 0.0, 0.0, 1.0);
+        let shape = Shape::new(rect_path()).with_fill(color);
+
+        let geometry = tessellate_shape(&shape, vec2(100.0, 50.0));
+        assert_valid(&geometry);
+
+        // Two triangles
+        assert_eq!(geometry.vertices.len(), 4);
+        assert_eq!(geometry.indices.len(), 6);
+
+        // Resolved against the size of the rect
+        for v in &geometry.vertices {
+            let pos = v.pos();
+            assert!(pos.x == 0.0 || pos.x == 100.0, "{pos}");
+            assert!(pos.y == 0.0 || pos.y == 50.0, "{pos}");
+            assert_eq!(v.color(), Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn stroke_line() {
+        let color = Srgba::new(0.0, 0.0, 1.0, 0.5);
+        let shape = Shape::new(ShapePath::Line {
+            from: Unit::px(vec2(10.0, 10.0)),
+            to: Unit::px(vec2(90.0, 10.0)),
+        })
+        .with_stroke(Stroke::new(color, 4.0));
+
+        let geometry = tessellate_shape(&shape, vec2(100.0, 100.0));
+        assert_valid(&geometry);
+
+        // A quad along the line, with butt caps ending exactly at the end points
+        assert_eq!(geometry.vertices.len(), 4);
+        assert_eq!(geometry.indices.len(), 6);
+
+        for v in &geometry.vertices {
+            let pos = v.pos();
+            assert!(
+                (pos.x - 10.0).abs() < 1e-3 || (pos.x - 90.0).abs() < 1e-3,
+                "{pos}"
+            );
+            assert!(
+                (pos.y - 8.0).abs() < 1e-3 || (pos.y - 12.0).abs() < 1e-3,
+                "{pos}"
+            );
+            assert_eq!(v.color(), srgba_to_vec4(color));
+        }
+    }
+
+    #[test]
+    fn fill_and_stroke() {
+        let fill = Srgba::new(1.0, 1.0, 1.0, 1.0);
+        let stroke = Srgba::new(0.0, 0.0, 0.0, 1.0);
+
+        let filled = tessellate_shape(&Shape::new(rect_path()).with_fill(fill), Vec2::splat(10.0));
+        let stroked = tessellate_shape(
+            &Shape::new(rect_path()).with_stroke(Stroke::new(stroke, 1.0)),
+            Vec2::splat(10.0),
+        );
+        let both = tessellate_shape(
+            &Shape::new(rect_path())
+                .with_fill(fill)
+                .with_stroke(Stroke::new(stroke, 1.0)),
+            Vec2::splat(10.0),
+        );
+
+        assert_valid(&stroked);
+        assert_valid(&both);
+        assert_eq!(
+            both.vertices.len(),
+            filled.vertices.len() + stroked.vertices.len()
+        );
+        assert_eq!(
+            both.indices.len(),
+            filled.indices.len() + stroked.indices.len()
+        );
+
+        // The stroke is drawn on top of the fill
+        let (fill_vertices, stroke_vertices) = both.vertices.split_at(filled.vertices.len());
+        assert!(fill_vertices
+            .iter()
+            .all(|v| v.color() == srgba_to_vec4(fill)));
+        assert!(stroke_vertices
+            .iter()
+            .all(|v| v.color() == srgba_to_vec4(stroke)));
+    }
+
+    #[test]
+    fn empty_shape() {
+        let geometry = tessellate_shape(&Shape::new(rect_path()), Vec2::splat(10.0));
+        assert!(geometry.vertices.is_empty() && geometry.indices.is_empty());
+    }
+}
@@ -2,16 +2,18 @@ use flax::{
     entity_ids,
     fetch::{Modified, TransformFetch},
     filter::{All, With},
-    CommandBuffer, Component, EntityIds, Fetch, FetchExt, Mutable, Query,
+    CommandBuffer, Component, EntityIds, Fetch, FetchExt, Mutable, Opt, Query,
 };
 use glam::{vec2, vec3, Mat4, Quat, Vec2};
 use image::{DynamicImage, ImageBuffer};
-use wgpu::{BindGroup, BindGroupLayout, SamplerDescriptor, ShaderStages, TextureFormat};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, SamplerDescriptor, ShaderStages, TextureFormat,
+};
 
 use crate::{
     assets::{map::HandleMap, Handle},
     components::{filled_rect, rect, screen_position, Rect},
-    shapes::FilledRect,
+    shapes::{FilledRect, Gradient, GradientKind},
     Frame,
 };
 
@@ -19,7 +21,7 @@ use super::{
     components::{draw_cmd, model_matrix},
     graphics::{
         shader::ShaderDesc, texture::Texture, BindGroupBuilder, BindGroupLayoutBuilder, Shader,
-        Vertex, VertexDesc,
+        TypedBuffer, Vertex, VertexDesc,
     },
     mesh_buffer::MeshHandle,
     renderer::RendererContext,
@@ -44,8 +46,60 @@ impl RectQuery {
     }
 }
 
+/// Width of the lookup texture a gradient is baked into
+const GRADIENT_LUT_WIDTH: u32 = 256;
+
+const GRADIENT_NONE: u32 = 0;
+const GRADIENT_LINEAR: u32 = 1;
+const GRADIENT_RADIAL: u32 = 2;
+
+/// Gradient parameters as seen by the shader
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GradientData {
+    kind: u32,
+    radius: f32,
+    direction: Vec2,
+    center: Vec2,
+    _padding: Vec2,
+}
+
+impl GradientData {
+    fn new(gradient: &Gradient) -> Self {
+        match gradient.kind {
+            GradientKind::Linear { angle } => Self {
+                kind: GRADIENT_LINEAR,
+                direction: vec2(angle.cos(), angle.sin()),
+                ..Default::default()
+            },
+            GradientKind::Radial { center, radius } => Self {
+                kind: GRADIENT_RADIAL,
+                radius,
+                center,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The bind group of a gradient fill.
+///
+/// Kept on the entity so that the lookup table is only rebuilt when the gradient or image changes
+struct GradientBindGroup {
+    gradient: Gradient,
+    image: Handle<DynamicImage>,
+    bind_group: Handle<BindGroup>,
+}
+
+flax::component! {
+    gradient_bind_group: GradientBindGroup,
+}
+
 pub struct RectRenderer {
     white_image: Handle<DynamicImage>,
+    /// Used in place of a gradient for solid fills
+    white_lut: Texture,
+    no_gradient: TypedBuffer<GradientData>,
 
     layout: BindGroupLayout,
     sampler: wgpu::Sampler,
@@ -53,10 +107,12 @@ pub struct RectRenderer {
     rect_query: Query<(
         EntityIds,
         <Component<FilledRect> as TransformFetch<Modified>>::Output,
+        Opt<Component<GradientBindGroup>>,
     )>,
 
     object_query: Query<RectQuery, (All, With)>,
 
+    textures: HandleMap<DynamicImage, Handle<Texture>>,
     bind_groups: HandleMap<DynamicImage, Handle<BindGroup>>,
 
     mesh: MeshHandle,
@@ -74,8 +130,29 @@ impl RectRenderer {
         let layout = BindGroupLayoutBuilder::new("RectRenderer::layout")
             .bind_sampler(ShaderStages::FRAGMENT)
             .bind_texture(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
             .build(&ctx.gpu);
 
+        let white_lut = Texture::from_image(
+            &ctx.gpu,
+            &DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
+                1,
+                1,
+                image::Rgba([255, 255, 255, 255]),
+            )),
+        );
+
+        let no_gradient = TypedBuffer::new(
+            &ctx.gpu,
+            "RectRenderer::no_gradient",
+            BufferUsages::UNIFORM,
+            &[GradientData {
+                kind: GRADIENT_NONE,
+                ..Default::default()
+            }],
+        );
+
         let white_image = frame
             .assets
             .insert(DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
@@ -117,10 +194,17 @@ impl RectRenderer {
 
         Self {
             white_image,
+            white_lut,
+            no_gradient,
             layout,
             sampler,
-            rect_query: Query::new((entity_ids(), filled_rect().modified())),
+            rect_query: Query::new((
+                entity_ids(),
+                filled_rect().modified(),
+                gradient_bind_group().opt(),
+            )),
             object_query: Query::new(RectQuery::new()).with(filled_rect()),
+            textures: HandleMap::new(),
             bind_groups: HandleMap::new(),
             mesh,
             shader,
@@ -132,26 +216,94 @@ impl RectRenderer {
         self.rect_query
             .borrow(&frame.world)
             .iter()
-            .for_each(|(id, rect)| {
+            .for_each(|(id, rect, cached)| {
                 let image = rect.fill_image.as_ref().unwrap_or(&self.white_image);
 
-                let bind_group = self.bind_groups.entry(&image.clone()).or_insert_with(|| {
-                    let texture = Texture::from_image(gpu, image);
+                let texture = self
+                    .textures
+                    .entry(image)
+                    .or_insert_with(|| frame.assets.insert(Texture::from_image(gpu, image)))
+                    .clone();
+
+                let bind_group = match (&rect.gradient, cached) {
+                    (Some(gradient), Some(cached))
+                        if cached.gradient == *gradient && cached.image == *image =>
+                    {
+                        cached.bind_group.clone()
+                    }
+                    // Gradients are rarely shared, so each one gets its own bind group
+                    (Some(gradient), _) => {
+                        let lut = gradient.lookup_table(GRADIENT_LUT_WIDTH);
+                        let lut = Texture::from_image(
+                            gpu,
+                            &DynamicImage::ImageRgba8(
+                                ImageBuffer::from_raw(
+                                    GRADIENT_LUT_WIDTH,
+                                    1,
+                                    lut.iter()
+                                        .flat_map(|v| [v.red, v.green, v.blue, v.alpha])
+                                        .collect(),
+                                )
+                                .unwrap(),
+                            ),
+                        );
+
+                        let data = TypedBuffer::new(
+                            gpu,
+                            "RectRenderer::gradient",
+                            BufferUsages::UNIFORM,
+                            &[GradientData::new(gradient)],
+                        );
+
+                        let bind_group = BindGroupBuilder::new("RectRenderer::gradient_bind_group")
+                            .bind_sampler(&self.sampler)
+                            .bind_texture(&texture.view(&Default::default()))
+                            .bind_texture(&lut.view(&Default::default()))
+                            .bind_buffer(data.buffer())
+                            .build(gpu, &self.layout);
+
+                        let bind_group = frame.assets.insert(bind_group);
+
+                        cmd.set(
+                            id,
+                            gradient_bind_group(),
+                            GradientBindGroup {
+                                gradient: gradient.clone(),
+                                image: image.clone(),
+                                bind_group: bind_group.clone(),
+                            },
+                        );
+
+                        bind_group
+                    }
+                    (None, cached) => {
+                        if cached.is_some() {
+                            cmd.remove(id, gradient_bind_group());
+                        }
 
-                    let bind_group = BindGroupBuilder::new("ShapeRenderer::textured_bind_group")
-                        .bind_sampler(&self.sampler)
-                        .bind_texture(&texture.view(&Default::default()))
-                        .build(gpu, &self.layout);
+                        self.bind_groups
+                            .entry(image)
+                            .or_insert_with(|| {
+                                let bind_group =
+                                    BindGroupBuilder::new("ShapeRenderer::textured_bind_group")
+                                        .bind_sampler(&self.sampler)
+                                        .bind_texture(&texture.view(&Default::default()))
+                                        .bind_texture(&self.white_lut.view(&Default::default()))
+                                        .bind_buffer(self.no_gradient.buffer())
+                                        .build(gpu, &self.layout);
 
-                    frame.assets.insert(bind_group)
-                });
+                                frame.assets.insert(bind_group)
+                            })
+                            .clone()
+                    }
+                };
 
                 cmd.set(
                     id,
                     draw_cmd(),
                     DrawCommand {
                         mesh: self.mesh,
-                        bind_group: bind_group.clone(),
+                        bind_group,
                         shader: self.shader.clone(),
                         index_count: 6,
                         vertex_offset: 0,
@@ -177,3 +329,31 @@ impl RectRenderer {
             })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn gradient_data() {
+        // Matches the layout of `Gradient` in `solid.wgsl`, padded to a multiple of 16 bytes
+        assert_eq!(std::mem::size_of::<GradientData>(), 32);
+        assert_eq!(std::mem::align_of::<GradientData>(), 4);
+
+        let linear = GradientData::new(&Gradient::linear(FRAC_PI_2, []));
+        assert_eq!(linear.kind, GRADIENT_LINEAR);
+        assert!(linear.direction.abs_diff_eq(vec2(0.0, 1.0), 1e-6));
+
+        let radial = GradientData::new(&Gradient::radial(vec2(0.25, 0.75), 0.5, []));
+        assert_eq!(radial.kind, GRADIENT_RADIAL);
+        assert_eq!(radial.center, vec2(0.25, 0.75));
+        assert_eq!(radial.radius, 0.5);
+
+        let bytes: &[u8] = bytemuck::bytes_of(&radial);
+        assert_eq!(&bytes[0..4], &GRADIENT_RADIAL.to_ne_bytes());
+        assert_eq!(&bytes[4..8], &0.5f32.to_ne_bytes());
+        assert_eq!(&bytes[16..20], &0.25f32.to_ne_bytes());
+    }
+}
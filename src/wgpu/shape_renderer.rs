@@ -5,12 +5,17 @@ use palette::Srgba;
 use slotmap::new_key_type;
 use wgpu::{BindGroup, BufferUsages, RenderPass, ShaderStages, TextureFormat};
 
-use crate::{assets::Handle, components::color, Frame};
+use crate::{
+    assets::Handle,
+    components::{color, opacity},
+    Frame,
+};
 
 use super::{
     components::{draw_cmd, model_matrix},
     graphics::{BindGroupBuilder, BindGroupLayoutBuilder, Mesh, Shader, TypedBuffer},
     mesh_buffer::MeshHandle,
+    path_renderer::PathRenderer,
     rect_renderer::RectRenderer,
     renderer::RendererContext,
     text_renderer::TextRenderer,
@@ -55,6 +60,7 @@ pub struct ShapeRenderer {
 
     rect_renderer: RectRenderer,
     text_renderer: TextRenderer,
+    path_renderer: PathRenderer,
 }
 
 impl ShapeRenderer {
@@ -88,6 +94,7 @@ impl ShapeRenderer {
             commands: Vec::new(),
             rect_renderer: RectRenderer::new(ctx, frame, color_format, &object_bind_group_layout),
             text_renderer: TextRenderer::new(ctx, frame, color_format, &object_bind_group_layout),
+            path_renderer: PathRenderer::new(ctx, frame, color_format, &object_bind_group_layout),
         }
     }
 
@@ -107,8 +114,12 @@ impl ShapeRenderer {
         self.text_renderer.update_meshes(ctx, frame);
         self.text_renderer.update(&ctx.gpu, frame);
 
+        self.path_renderer.update_meshes(ctx, frame);
+        self.path_renderer.update(&ctx.gpu, frame);
+
         let mut query = Query::new((
             color().opt_or(Srgba::new(1.0, 1.0, 1.0, 1.0)),
+            opacity().opt_or(1.0),
             model_matrix(),
             draw_cmd(),
         ))
@@ -120,12 +131,12 @@ impl ShapeRenderer {
 
         let commands = query
             .iter()
-            .map(|(&color, &model, cmd)| {
+            .map(|(&color, &opacity, &model, cmd)| {
                 let instance = self.objects.len() as u32;
 
                 self.objects.push(ObjectData {
                     model_matrix: model,
-                    color: srgba_to_vec4(color),
+                    color: srgba_to_vec4(color) * vec4(1.0, 1.0, 1.0, opacity),
                 });
 
                 InstancedDrawCommandRef {
@@ -187,7 +198,7 @@ struct ObjectData {
     color: Vec4,
 }
 
-fn srgba_to_vec4(color: Srgba) -> Vec4 {
+pub(crate) fn srgba_to_vec4(color: Srgba) -> Vec4 {
     let (r, g, b, a) = color.into_linear().into_components();
 
     vec4(r, g, b, a)
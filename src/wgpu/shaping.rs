@@ -0,0 +1,1301 @@
+use std::{borrow::Cow, ops::Range};
+
+use flax::EntityRef;
+use glam::{vec2, Vec2};
+use palette::Srgba;
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level, ParagraphInfo};
+
+use crate::{
+    assets::Handle,
+    components::{self, Rect},
+    layout::TextMeasure,
+    text::{RichText, TextLayout, TextOverflow, VerticalAlign, WrapMode},
+};
+
+use super::{
+    components::{font, font_fallback, rich_text_fonts},
+    font::{font_chain, Font},
+};
+
+/// A glyph positioned relative to the top left corner of the text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    /// Index of the glyph in the font
+    pub glyph: u16,
+    /// Index of the span this glyph belongs to
+    pub span: usize,
+    /// Index into the font chain of the span
+    pub font: usize,
+    /// Position of the glyph origin on the baseline, in pixels
+    pub pos: Vec2,
+    pub advance: f32,
+    /// Byte offset of the first character in the source text this glyph was produced from
+    pub cluster: usize,
+    /// The glyph is part of a right to left run
+    pub rtl: bool,
+}
+
+/// A single line of text after wrapping
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedLine {
+    /// Byte range of the source text making up this line
+    pub text: Range<usize>,
+    /// Range into [`ShapedText::glyphs`], in visual order
+    pub glyphs: Range<usize>,
+    /// Vertical position of the top of the line
+    pub top: f32,
+    /// Vertical position of the baseline
+    pub baseline: f32,
+    pub height: f32,
+    pub width: f32,
+}
+
+/// An underline or strikethrough
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedDecoration {
+    pub span: usize,
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+/// Text which has been shaped, reordered and broken into lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub lines: Vec<ShapedLine>,
+    pub decorations: Vec<ShapedDecoration>,
+    /// The extent of the laid out text
+    pub size: Vec2,
+}
+
+impl ShapedText {
+    /// Moves all glyphs, lines and decorations by `offset`
+    pub fn translate(&mut self, offset: Vec2) {
+        for glyph in &mut self.glyphs {
+            glyph.pos += offset;
+        }
+
+        for line in &mut self.lines {
+            line.top += offset.y;
+            line.baseline += offset.y;
+        }
+
+        for decoration in &mut self.decorations {
+            decoration.min += offset;
+            decoration.max += offset;
+        }
+    }
+
+    /// Offsets the text vertically within `height` according to the alignment
+    pub fn align_vertically(&mut self, height: f32, align: VerticalAlign) {
+        self.translate(vec2(0.0, (height - self.size.y) * align.factor()));
+    }
+
+    /// Returns the line at the vertical position, clamped to the first and last line
+    pub fn line_at(&self, y: f32) -> Option<&ShapedLine> {
+        self.lines
+            .iter()
+            .find(|v| y < v.top + v.height)
+            .or(self.lines.last())
+    }
+
+    /// Returns the line containing the byte `index`
+    pub fn line_of(&self, index: usize) -> Option<&ShapedLine> {
+        self.lines
+            .iter()
+            .find(|v| v.text.contains(&index))
+            .or(self.lines.last())
+    }
+
+    /// Returns the byte offset in `text` closest to `pos`.
+    ///
+    /// `text` is the source text the glyphs were shaped from.
+    pub fn hit(&self, text: &str, pos: Vec2) -> usize {
+        let Some(line) = self.line_at(pos.y) else {
+            return 0;
+        };
+
+        // Combining marks do not take up any space of their own
+        let mut glyphs = self.glyphs[line.glyphs.clone()]
+            .iter()
+            .filter(|v| v.advance > 0.0);
+
+        let Some(glyph) = glyphs.clone().find(|v| pos.x < v.pos.x + v.advance) else {
+            // Past the end of the line
+            return match glyphs.next_back() {
+                Some(glyph) if glyph.rtl => glyph.cluster,
+                Some(glyph) => self.cluster_end(text, line, glyph.cluster),
+                None => line.text.start,
+            };
+        };
+
+        // The leading edge of right to left glyphs is on the right
+        let leading = pos.x < glyph.pos.x + glyph.advance / 2.0;
+        if leading != glyph.rtl {
+            glyph.cluster
+        } else {
+            self.cluster_end(text, line, glyph.cluster)
+        }
+    }
+
+    /// Returns the byte offset of the end of the cluster beginning at `cluster`
+    fn cluster_end(&self, text: &str, line: &ShapedLine, cluster: usize) -> usize {
+        let line_end = line.text.start + text[line.text.clone()].trim_end().len();
+
+        self.glyphs[line.glyphs.clone()]
+            .iter()
+            .map(|v| v.cluster)
+            .filter(|&v| v > cluster)
+            .fold(line_end.max(cluster), usize::min)
+    }
+
+    /// Returns the rectangles covering the glyphs within the byte `range`.
+    ///
+    /// Visually adjacent glyphs are merged, and each rectangle spans the full height of its line.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let mut rects = Vec::new();
+
+        for line in &self.lines {
+            let mut current: Option<(f32, f32)> = None;
+
+            for glyph in &self.glyphs[line.glyphs.clone()] {
+                if range.contains(&glyph.cluster) {
+                    let (min, max) = current.get_or_insert((glyph.pos.x, glyph.pos.x));
+                    *min = min.min(glyph.pos.x);
+                    *max = max.max(glyph.pos.x + glyph.advance);
+                } else if let Some((min, max)) = current.take() {
+                    rects.push(line_rect(line, min, max));
+                }
+            }
+
+            if let Some((min, max)) = current {
+                rects.push(line_rect(line, min, max));
+            }
+        }
+
+        rects
+    }
+}
+
+fn line_rect(line: &ShapedLine, min: f32, max: f32) -> Rect {
+    Rect {
+        min: vec2(min, line.top),
+        max: vec2(max, line.top + line.height),
+    }
+}
+
+/// A range of text sharing the same style
+#[derive(Clone)]
+pub struct ShapeSpan {
+    /// Byte range of the text covered by the span
+    pub range: Range<usize>,
+    /// The font followed by its fallbacks
+    pub fonts: Vec<Handle<Font>>,
+    pub px: f32,
+    pub color: Srgba,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl ShapeSpan {
+    pub fn new(range: Range<usize>, fonts: Vec<Handle<Font>>, px: f32) -> Self {
+        Self {
+            range,
+            fonts,
+            px,
+            color: Srgba::new(1.0, 1.0, 1.0, 1.0),
+            underline: false,
+            strikethrough: false,
+        }
+    }
+}
+
+/// Vertical metrics of a font scaled to a pixel size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    /// Approximate metrics for when the font is unavailable
+    fn fallback(px: f32) -> Self {
+        Self {
+            ascent: px * 0.8,
+            descent: -px * 0.2,
+            line_gap: 0.0,
+        }
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    fn max(self, other: Self) -> Self {
+        Self {
+            ascent: self.ascent.max(other.ascent),
+            descent: self.descent.min(other.descent),
+            line_gap: self.line_gap.max(other.line_gap),
+        }
+    }
+}
+
+/// Position and thickness of a decoration line relative to the baseline, y up
+#[derive(Debug, Clone, Copy)]
+struct DecorationMetrics {
+    position: f32,
+    thickness: f32,
+}
+
+impl DecorationMetrics {
+    fn new(
+        metrics: Option<rustybuzz::ttf_parser::LineMetrics>,
+        scale: f32,
+        px: f32,
+        default_position: f32,
+    ) -> Self {
+        match metrics {
+            Some(v) => Self {
+                position: v.position as f32 * scale,
+                thickness: (v.thickness as f32 * scale).max(1.0),
+            },
+            None => Self {
+                position: default_position * px,
+                thickness: (px / 14.0).max(1.0),
+            },
+        }
+    }
+}
+
+/// Font data of a span prepared for shaping
+struct SpanFaces<'a> {
+    faces: Vec<Option<&'a Face<'a>>>,
+    metrics: LineMetrics,
+    underline: DecorationMetrics,
+    strikethrough: DecorationMetrics,
+}
+
+impl<'a> SpanFaces<'a> {
+    fn new(span: &'a ShapeSpan) -> Self {
+        let faces = span
+            .fonts
+            .iter()
+            .map(|font| font.face())
+            .collect::<Vec<_>>();
+
+        // Metrics are always taken from the primary font
+        let Some(Some(primary)) = faces.first() else {
+            tracing::error!("Font can not be used for shaping");
+            return Self {
+                faces,
+                metrics: LineMetrics::fallback(span.px),
+                underline: DecorationMetrics::new(None, 0.0, span.px, -0.1),
+                strikethrough: DecorationMetrics::new(None, 0.0, span.px, 0.3),
+            };
+        };
+
+        let scale = span.px / primary.units_per_em() as f32;
+
+        Self {
+            metrics: LineMetrics {
+                ascent: primary.ascender() as f32 * scale,
+                descent: primary.descender() as f32 * scale,
+                line_gap: primary.line_gap() as f32 * scale,
+            },
+            underline: DecorationMetrics::new(primary.underline_metrics(), scale, span.px, -0.1),
+            strikethrough: DecorationMetrics::new(primary.strikeout_metrics(), scale, span.px, 0.3),
+            faces,
+        }
+    }
+}
+
+/// A glyph in logical order, before being placed on a line
+#[derive(Debug, Clone, Copy)]
+struct LogicalGlyph {
+    glyph: u16,
+    span: usize,
+    font: usize,
+    cluster: usize,
+    advance: f32,
+    offset: Vec2,
+    whitespace: bool,
+}
+
+/// Shapes `text` using OpenType shaping and the unicode bidirectional algorithm.
+///
+/// Each character is displayed using the first font in `fonts` which covers it. Lines are wrapped
+/// according to `layout` to fit within `max_width`, and broken at every paragraph separator.
+pub fn shape_text(
+    fonts: &[Handle<Font>],
+    text: &str,
+    px: f32,
+    max_width: Option<f32>,
+    layout: &TextLayout,
+) -> ShapedText {
+    shape_spans(
+        text,
+        &[ShapeSpan::new(0..text.len(), fonts.to_vec(), px)],
+        max_width,
+        layout,
+    )
+}
+
+/// Shapes text consisting of multiple differently styled spans as a single flow.
+///
+/// The spans are expected to be sorted and to cover the whole text.
+pub fn shape_spans(
+    text: &str,
+    spans: &[ShapeSpan],
+    max_width: Option<f32>,
+    layout: &TextLayout,
+) -> ShapedText {
+    let faces = spans.iter().map(SpanFaces::new).collect::<Vec<_>>();
+
+    let bidi = BidiInfo::new(text, None);
+    let ellipsis = layout.overflow == TextOverflow::Ellipsis;
+
+    let mut result = ShapedText::default();
+    // Index of the first decoration of each line
+    let mut line_decorations = Vec::new();
+    let mut top = 0.0;
+
+    'paragraphs: for (para_index, para) in bidi.paragraphs.iter().enumerate() {
+        let glyphs = shape_paragraph(spans, &faces, &bidi, para);
+        let lines = break_lines(&glyphs, max_width, layout.wrap);
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let start = result.glyphs.len();
+            let text_range = line_text_range(para, &glyphs, line.clone());
+
+            let is_last = layout
+                .max_lines
+                .is_some_and(|v| result.lines.len() + 1 >= v);
+
+            let truncated =
+                is_last && (line_index + 1 < lines.len() || para_index + 1 < bidi.paragraphs.len());
+
+            let overflows = max_width.is_some_and(|v| visible_width(&glyphs[line.clone()]) > v);
+
+            let line_glyphs = if ellipsis && (truncated || overflows) {
+                let span = glyphs[line.clone()]
+                    .last()
+                    .map(|v| v.span)
+                    .or_else(|| span_at(spans, text_range.start));
+
+                match span {
+                    Some(span) => {
+                        let ellipsis = shape_ellipsis(&spans[span], &faces[span], span);
+                        Cow::Owned(ellipsize(
+                            &glyphs[line.clone()],
+                            ellipsis,
+                            text_range.start,
+                            max_width,
+                        ))
+                    }
+                    None => Cow::Borrowed(&glyphs[line.clone()]),
+                }
+            } else {
+                Cow::Borrowed(&glyphs[line.clone()])
+            };
+
+            // The line is sized to fit the largest span on it
+            let metrics = line_glyphs
+                .iter()
+                .map(|v| faces[v.span].metrics)
+                .reduce(LineMetrics::max)
+                .or_else(|| Some(faces[span_at(spans, text_range.start)?].metrics))
+                .unwrap_or(LineMetrics::fallback(0.0));
+
+            // Extra space is distributed evenly above and below the line
+            let height = metrics.line_height() * layout.line_height;
+            let baseline = top + metrics.ascent + (height - metrics.line_height()) / 2.0;
+
+            let width = place_line(
+                &bidi,
+                para,
+                &line_glyphs,
+                text_range.clone(),
+                baseline,
+                &mut result,
+            );
+
+            line_decorations.push(result.decorations.len());
+            decorate_line(spans, &faces, start, baseline, &mut result);
+
+            result.lines.push(ShapedLine {
+                text: text_range,
+                glyphs: start..result.glyphs.len(),
+                top,
+                baseline,
+                height,
+                width,
+            });
+
+            result.size.x = result.size.x.max(width);
+            top += height;
+
+            if is_last {
+                break 'paragraphs;
+            }
+        }
+    }
+
+    result.size.y = top;
+
+    align_lines(&mut result, &line_decorations, max_width, layout);
+
+    result
+}
+
+/// Offsets each line horizontally according to the alignment.
+///
+/// Lines are aligned within `max_width`, or within the widest line if unbounded.
+fn align_lines(
+    result: &mut ShapedText,
+    line_decorations: &[usize],
+    max_width: Option<f32>,
+    layout: &TextLayout,
+) {
+    let factor = layout.align.factor();
+    if factor == 0.0 {
+        return;
+    }
+
+    let width = max_width.filter(|v| v.is_finite()).unwrap_or(result.size.x);
+
+    for (i, line) in result.lines.iter().enumerate() {
+        let offset = vec2((width - line.width) * factor, 0.0);
+
+        for glyph in &mut result.glyphs[line.glyphs.clone()] {
+            glyph.pos += offset;
+        }
+
+        let decorations = line_decorations[i]
+            ..line_decorations
+                .get(i + 1)
+                .copied()
+                .unwrap_or(result.decorations.len());
+
+        for decoration in &mut result.decorations[decorations] {
+            decoration.min += offset;
+            decoration.max += offset;
+        }
+    }
+}
+
+/// Resolves the spans of rich text against the style of the text entity.
+///
+/// `span_fonts` holds the font chain of each span, or an empty chain to use `fonts`.
+fn rich_text_spans(
+    rich_text: &RichText,
+    span_fonts: &[Vec<Handle<Font>>],
+    fonts: &[Handle<Font>],
+    px: f32,
+) -> Vec<ShapeSpan> {
+    rich_text
+        .span_ranges()
+        .zip(&rich_text.spans)
+        .enumerate()
+        .map(|(i, (range, span))| {
+            let fonts = match span_fonts.get(i) {
+                Some(v) if !v.is_empty() => v.clone(),
+                _ => fonts.to_vec(),
+            };
+
+            ShapeSpan {
+                range,
+                fonts,
+                px: span.font_size.unwrap_or(px),
+                color: span.color.unwrap_or(Srgba::new(1.0, 1.0, 1.0, 1.0)),
+                underline: span.underline,
+                strikethrough: span.strikethrough,
+            }
+        })
+        .collect()
+}
+
+/// Returns the size required to display the text
+pub fn measure_text(
+    fonts: &[Handle<Font>],
+    text: &str,
+    px: f32,
+    max_width: Option<f32>,
+    layout: &TextLayout,
+) -> Vec2 {
+    shape_text(fonts, text, px, max_width, layout).size
+}
+
+/// Measures text for layout by shaping it with the fonts of the entity
+#[derive(Default, Debug, Clone, Copy)]
+pub struct TextShaper;
+
+impl TextMeasure for TextShaper {
+    fn measure(
+        &self,
+        entity: &EntityRef,
+        max_width: Option<f32>,
+        layout: &TextLayout,
+    ) -> Option<Vec2> {
+        let (text, spans) = entity_spans(entity)?;
+        Some(shape_spans(&text, &spans, max_width, layout).size)
+    }
+}
+
+/// Collects the text of a text or rich text entity and the spans to shape it with.
+///
+/// Used by both layout and rendering. Returns `None` if the fonts of the entity are not loaded
+pub(crate) fn entity_spans(entity: &EntityRef) -> Option<(String, Vec<ShapeSpan>)> {
+    let fonts = match entity.get(font()) {
+        Ok(font) => {
+            let fallback = entity.get(font_fallback());
+            font_chain(
+                &font,
+                fallback.as_deref().map(Vec::as_slice).unwrap_or_default(),
+            )
+        }
+        Err(_) => Vec::new(),
+    };
+
+    let font_size = entity.get_copy(components::font_size()).unwrap_or(16.0);
+
+    if let Ok(text) = entity.get(components::text()) {
+        if fonts.is_empty() {
+            return None;
+        }
+
+        let spans = vec![ShapeSpan::new(0..text.len(), fonts, font_size)];
+        return Some((text.to_string(), spans));
+    }
+
+    let rich_text = entity.get(components::rich_text()).ok()?;
+    let span_fonts = entity.get(rich_text_fonts()).ok()?;
+
+    let spans = rich_text_spans(&rich_text, &span_fonts, &fonts, font_size);
+    Some((rich_text.text(), spans))
+}
+
+fn span_at(spans: &[ShapeSpan], index: usize) -> Option<usize> {
+    spans
+        .iter()
+        .position(|v| v.range.contains(&index))
+        .or_else(|| spans.len().checked_sub(1))
+}
+
+/// Strips the paragraph separator from the end of the paragraph
+fn paragraph_content(text: &str, para: &ParagraphInfo) -> Range<usize> {
+    let content = text[para.range.clone()].trim_end_matches(['\n', '\r', '\u{85}', '\u{2029}']);
+
+    para.range.start..para.range.start + content.len()
+}
+
+/// Shapes each run of equal embedding level, span and font, returning the glyphs in logical
+/// order
+fn shape_paragraph(
+    spans: &[ShapeSpan],
+    faces: &[SpanFaces],
+    bidi: &BidiInfo,
+    para: &ParagraphInfo,
+) -> Vec<LogicalGlyph> {
+    let content = paragraph_content(bidi.text, para);
+    let mut glyphs = Vec::new();
+
+    for level_run in level_runs(&bidi.levels, content) {
+        let rtl = bidi.levels[level_run.start].is_rtl();
+
+        for (span_index, span) in spans.iter().enumerate() {
+            let span_run = span.range.start.max(level_run.start)..span.range.end.min(level_run.end);
+            if span_run.is_empty() {
+                continue;
+            }
+
+            let span_faces = &faces[span_index].faces;
+            for (font, run) in font_runs(&span.fonts, span_faces, bidi.text, span_run) {
+                let Some(face) = &span_faces[font] else {
+                    continue;
+                };
+
+                let run_glyphs = shape_run(face, bidi.text, run, rtl, span.px, span_index, font);
+
+                // Right to left runs are returned in visual order
+                let start = glyphs.len();
+                glyphs.extend(run_glyphs);
+                if rtl {
+                    glyphs[start..].reverse();
+                }
+            }
+        }
+    }
+
+    glyphs
+}
+
+/// Shapes a run of `text` using a single font and direction
+fn shape_run(
+    face: &Face,
+    text: &str,
+    run: Range<usize>,
+    rtl: bool,
+    px: f32,
+    span: usize,
+    font: usize,
+) -> Vec<LogicalGlyph> {
+    let scale = px / face.units_per_em() as f32;
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(&text[run.clone()]);
+    buffer.set_direction(if rtl {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    });
+    buffer.guess_segment_properties();
+
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, pos)| {
+            let cluster = run.start + info.cluster as usize;
+            LogicalGlyph {
+                glyph: info.glyph_id as u16,
+                span,
+                font,
+                cluster,
+                advance: pos.x_advance as f32 * scale,
+                offset: vec2(pos.x_offset as f32, -pos.y_offset as f32) * scale,
+                whitespace: text[cluster..]
+                    .chars()
+                    .next()
+                    .is_some_and(char::is_whitespace),
+            }
+        })
+        .collect()
+}
+
+/// Shapes an ellipsis using the first font of the span which covers it
+fn shape_ellipsis(span: &ShapeSpan, faces: &SpanFaces, span_index: usize) -> Vec<LogicalGlyph> {
+    let covering = (0..span.fonts.len()).find(|&v| {
+        faces.faces[v].is_some() && span.fonts[v].font.lookup_glyph_index('\u{2026}') != 0
+    });
+
+    // Three periods look close enough for fonts without the ellipsis character
+    let (font, text) = match covering {
+        Some(font) => (font, "\u{2026}"),
+        None => (0, "..."),
+    };
+
+    match faces.faces.get(font) {
+        Some(Some(face)) => shape_run(face, text, 0..text.len(), false, span.px, span_index, font),
+        _ => Vec::new(),
+    }
+}
+
+/// Removes whole clusters from the logical end of a line until it fits together with the
+/// ellipsis, and appends the ellipsis.
+///
+/// `line_start` is the byte offset of the line, used for placing the ellipsis on an empty line.
+fn ellipsize(
+    glyphs: &[LogicalGlyph],
+    ellipsis: Vec<LogicalGlyph>,
+    line_start: usize,
+    max_width: Option<f32>,
+) -> Vec<LogicalGlyph> {
+    let max_width = max_width.unwrap_or(f32::INFINITY);
+    let ellipsis_width: f32 = ellipsis.iter().map(|v| v.advance).sum();
+
+    let mut kept = glyphs.to_vec();
+    let mut width: f32 = kept.iter().map(|v| v.advance).sum();
+
+    while let Some(last) = kept.last().copied() {
+        if !last.whitespace && width + ellipsis_width <= max_width {
+            break;
+        }
+
+        while kept.last().is_some_and(|v| v.cluster == last.cluster) {
+            width -= kept.pop().unwrap().advance;
+        }
+    }
+
+    // The ellipsis is placed in the same directional run as the last remaining character
+    let cluster = kept.last().map(|v| v.cluster).unwrap_or(line_start);
+    kept.extend(ellipsis.into_iter().map(|v| LogicalGlyph { cluster, ..v }));
+    kept
+}
+
+/// Returns the width of the glyphs excluding trailing whitespace
+fn visible_width(glyphs: &[LogicalGlyph]) -> f32 {
+    let end = glyphs
+        .iter()
+        .rposition(|v| !v.whitespace)
+        .map_or(0, |v| v + 1);
+
+    glyphs[..end].iter().map(|v| v.advance).sum()
+}
+
+/// Splits `range` into runs which are displayed using the same font.
+///
+/// Each character uses the first font in the chain which covers it. Whitespace, joiners and
+/// combining marks stay in the current run to keep clusters together.
+fn font_runs(
+    fonts: &[Handle<Font>],
+    faces: &[Option<&Face>],
+    text: &str,
+    range: Range<usize>,
+) -> Vec<(usize, Range<usize>)> {
+    let mut runs: Vec<(usize, Range<usize>)> = Vec::new();
+
+    for (offset, c) in text[range.clone()].char_indices() {
+        let start = range.start + offset;
+        let end = start + c.len_utf8();
+
+        let covers =
+            |font: usize| faces[font].is_some() && fonts[font].font.lookup_glyph_index(c) != 0;
+
+        let attached = c.is_whitespace() || matches!(bidi_class(c), BidiClass::NSM | BidiClass::BN);
+
+        let font = match runs.last() {
+            Some(&(current, _)) if attached && (c.is_whitespace() || covers(current)) => current,
+            _ => (0..fonts.len()).find(|&v| covers(v)).unwrap_or(0),
+        };
+
+        match runs.last_mut() {
+            Some((current, run)) if *current == font => run.end = end,
+            _ => runs.push((font, start..end)),
+        }
+    }
+
+    runs
+}
+
+/// Splits `range` into runs of equal embedding level
+fn level_runs(levels: &[Level], range: Range<usize>) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = range.start;
+
+    for i in range.clone() {
+        if levels[i] != levels[start] {
+            runs.push(start..i);
+            start = i;
+        }
+    }
+
+    if start < range.end {
+        runs.push(start..range.end);
+    }
+
+    runs
+}
+
+/// Greedily breaks the glyphs of a paragraph into lines, returning glyph ranges.
+///
+/// Trailing whitespace does not count towards the width of a line.
+fn break_lines(
+    glyphs: &[LogicalGlyph],
+    max_width: Option<f32>,
+    wrap: WrapMode,
+) -> Vec<Range<usize>> {
+    let max_width = match wrap {
+        WrapMode::None => f32::INFINITY,
+        WrapMode::Word | WrapMode::Char => max_width.unwrap_or(f32::INFINITY),
+    };
+
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut width = 0.0;
+    // Glyph index after the last whitespace and the width of the line up to it
+    let mut last_break: Option<(usize, f32)> = None;
+
+    for (i, glyph) in glyphs.iter().enumerate() {
+        if !glyph.whitespace && width + glyph.advance > max_width && i > line_start {
+            match last_break.take().filter(|_| wrap == WrapMode::Word) {
+                Some((index, break_width)) => {
+                    lines.push(line_start..index);
+                    line_start = index;
+                    width -= break_width;
+                }
+                // Break before the current character, keeping clusters together
+                None if glyphs[i - 1].cluster != glyph.cluster => {
+                    lines.push(line_start..i);
+                    line_start = i;
+                    width = 0.0;
+                }
+                None => {}
+            }
+        }
+
+        width += glyph.advance;
+
+        if glyph.whitespace {
+            last_break = Some((i + 1, width));
+        }
+    }
+
+    // An empty paragraph still occupies a line
+    lines.push(line_start..glyphs.len());
+    lines
+}
+
+fn line_text_range(
+    para: &ParagraphInfo,
+    glyphs: &[LogicalGlyph],
+    line: Range<usize>,
+) -> Range<usize> {
+    let start = glyphs
+        .get(line.start)
+        .map(|v| v.cluster)
+        .unwrap_or(para.range.start);
+
+    let end = glyphs
+        .get(line.end)
+        .map(|v| v.cluster)
+        .unwrap_or(para.range.end);
+
+    start..end
+}
+
+/// Reorders the glyphs of a line visually and appends them to `result`.
+///
+/// Returns the width of the line
+fn place_line(
+    bidi: &BidiInfo,
+    para: &ParagraphInfo,
+    glyphs: &[LogicalGlyph],
+    text_range: Range<usize>,
+    baseline: f32,
+    result: &mut ShapedText,
+) -> f32 {
+    // Trailing whitespace is not displayed
+    let visible = glyphs
+        .iter()
+        .rposition(|v| !v.whitespace)
+        .map(|v| &glyphs[..=v])
+        .unwrap_or_default();
+
+    if visible.is_empty() {
+        return 0.0;
+    }
+
+    let (levels, runs) = bidi.visual_runs(para, text_range);
+
+    let mut pen = 0.0;
+    for run in runs {
+        let in_run = visible.iter().filter(|v| run.contains(&v.cluster));
+
+        let rtl = levels[run.start].is_rtl();
+
+        let mut place = |glyph: &LogicalGlyph| {
+            result.glyphs.push(ShapedGlyph {
+                glyph: glyph.glyph,
+                span: glyph.span,
+                font: glyph.font,
+                pos: vec2(pen, baseline) + glyph.offset,
+                advance: glyph.advance,
+                cluster: glyph.cluster,
+                rtl,
+            });
+
+            pen += glyph.advance;
+        };
+
+        if rtl {
+            in_run.rev().for_each(&mut place);
+        } else {
+            in_run.for_each(&mut place);
+        }
+    }
+
+    pen
+}
+
+/// Underlines and strikes through visually contiguous glyphs of the same span, beginning at
+/// glyph `start`
+fn decorate_line(
+    spans: &[ShapeSpan],
+    faces: &[SpanFaces],
+    start: usize,
+    baseline: f32,
+    result: &mut ShapedText,
+) {
+    let mut glyphs = result.glyphs[start..].iter().peekable();
+
+    while let Some(first) = glyphs.next() {
+        let mut last = first;
+        while let Some(next) = glyphs.next_if(|v| v.span == first.span) {
+            last = next;
+        }
+
+        let span = &spans[first.span];
+        let metrics = &faces[first.span];
+
+        let lines = [
+            (span.underline, metrics.underline),
+            (span.strikethrough, metrics.strikethrough),
+        ];
+
+        for (_, line) in lines.into_iter().filter(|v| v.0) {
+            let y = baseline - line.position - line.thickness / 2.0;
+            result.decorations.push(ShapedDecoration {
+                span: first.span,
+                min: vec2(first.pos.x, y),
+                max: vec2(last.pos.x + last.advance, y + line.thickness),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assets::{fs::BytesFromFile, AssetCache},
+        font::FontFromFile,
+        text::TextAlign,
+    };
+
+    use super::*;
+
+    fn load_font(assets: &AssetCache, path: &str) -> Handle<Font> {
+        assets
+            .try_load(&FontFromFile {
+                path: BytesFromFile(path.into()),
+            })
+            .unwrap()
+    }
+
+    fn inter(assets: &AssetCache) -> Handle<Font> {
+        load_font(assets, "assets/fonts/Inter/static/Inter-Regular.ttf")
+    }
+
+    /// Covers hebrew, which Inter does not
+    fn dejavu(assets: &AssetCache) -> Handle<Font> {
+        load_font(assets, "assets/fonts/DejaVuSans/DejaVuSans.ttf")
+    }
+
+    fn clusters(shaped: &ShapedText) -> Vec<usize> {
+        shaped.glyphs.iter().map(|v| v.cluster).collect()
+    }
+
+    #[test]
+    fn shape_latin() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets)];
+        let face = fonts[0].face().unwrap();
+        let scale = 16.0 / face.units_per_em() as f32;
+
+        let text = "Hello";
+        let shaped = shape_text(&fonts, text, 16.0, None, &TextLayout::default());
+
+        assert_eq!(shaped.glyphs.len(), 5);
+        assert_eq!(shaped.lines.len(), 1);
+        assert_eq!(clusters(&shaped), [0, 1, 2, 3, 4]);
+
+        let mut pen = 0.0;
+        for (glyph, c) in shaped.glyphs.iter().zip(text.chars()) {
+            let id = face.glyph_index(c).unwrap();
+            assert_eq!(glyph.glyph, id.0);
+            assert_eq!(glyph.font, 0);
+            assert!(!glyph.rtl);
+            assert_eq!(glyph.pos.x, pen);
+            assert!(glyph.advance > 0.0);
+
+            pen += glyph.advance;
+        }
+
+        // Without kerning the advance is the one of the font, scaled to the pixel size
+        let l = face.glyph_index('l').unwrap();
+        let advance = face.glyph_hor_advance(l).unwrap() as f32 * scale;
+        assert_eq!(shaped.glyphs[2].advance, advance);
+        assert_eq!(shaped.glyphs[3].advance, advance);
+
+        let ascent = face.ascender() as f32 * scale;
+        let descent = face.descender() as f32 * scale;
+        assert_eq!(shaped.lines[0].baseline, ascent);
+        assert_eq!(shaped.size, vec2(pen, shaped.lines[0].height));
+        assert!((shaped.lines[0].height - (ascent - descent)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn font_fallback() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets), dejavu(&assets)];
+
+        // "Hi שלום!"
+        let text = "Hi \u{5e9}\u{5dc}\u{5d5}\u{5dd}!";
+        let shaped = shape_text(&fonts, text, 16.0, None, &TextLayout::default());
+
+        let font_of = |cluster: usize| {
+            shaped
+                .glyphs
+                .iter()
+                .find(|v| v.cluster == cluster)
+                .map(|v| v.font)
+        };
+
+        // Each character uses the first font which covers it, and the space stays with the run
+        // before it
+        assert_eq!(shaped.glyphs.len(), text.chars().count());
+        assert_eq!(font_of(0), Some(0));
+        assert_eq!(font_of(1), Some(0));
+        assert_eq!(font_of(2), Some(0));
+        for (i, _) in text.char_indices().skip(3).take(4) {
+            assert_eq!(font_of(i), Some(1));
+        }
+        assert_eq!(font_of(11), Some(0));
+
+        // Without a covering font the primary font is used
+        let shaped = shape_text(&fonts[..1], text, 16.0, None, &TextLayout::default());
+        assert!(shaped.glyphs.iter().all(|v| v.font == 0));
+        assert_eq!(shaped.glyphs[3].glyph, 0);
+    }
+
+    #[test]
+    fn bidi_visual_order() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets), dejavu(&assets)];
+
+        // Hebrew letters are two bytes each
+        let text = "abc \u{5d0}\u{5d1}\u{5d2} def";
+        let shaped = shape_text(&fonts, text, 16.0, None, &TextLayout::default());
+
+        // The right to left run is reversed within the left to right paragraph
+        assert_eq!(clusters(&shaped), [0, 1, 2, 3, 8, 6, 4, 10, 11, 12, 13]);
+        assert_eq!(
+            shaped.glyphs.iter().map(|v| v.rtl).collect::<Vec<_>>(),
+            [false, false, false, false, true, true, true, false, false, false, false]
+        );
+
+        // Glyphs are placed left to right in visual order
+        assert!(shaped
+            .glyphs
+            .windows(2)
+            .all(|v| v[0].pos.x + v[0].advance == v[1].pos.x));
+
+        // A paragraph starting with a right to left character is right to left, and the left to
+        // right run is placed at the visual start
+        let text = "\u{5d0}\u{5d1}\u{5d2} abc";
+        let shaped = shape_text(&fonts, text, 16.0, None, &TextLayout::default());
+        assert_eq!(clusters(&shaped), [7, 8, 9, 6, 4, 2, 0]);
+
+        // Hit testing uses the leading edge of right to left glyphs
+        let alef = shaped.glyphs.last().unwrap();
+        assert_eq!(
+            shaped.hit(text, vec2(alef.pos.x + alef.advance - 0.1, 1.0)),
+            0
+        );
+        assert_eq!(shaped.hit(text, vec2(alef.pos.x + 0.1, 1.0)), 2);
+    }
+
+    fn line_texts<'a>(text: &'a str, shaped: &ShapedText) -> Vec<&'a str> {
+        shaped
+            .lines
+            .iter()
+            .map(|v| text[v.text.clone()].trim_end())
+            .collect()
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets)];
+        let width = |text| {
+            shape_text(&fonts, text, 16.0, None, &TextLayout::default())
+                .size
+                .x
+        };
+
+        let words = "lorem ipsum dolor sit";
+        let word_width = width("lorem ipsum") + 0.5;
+        let char_width = width("nnnn") + 0.5;
+
+        let cases: [(&str, Option<f32>, WrapMode, &[&str]); 8] = [
+            (
+                words,
+                Some(word_width),
+                WrapMode::Word,
+                &["lorem ipsum", "dolor sit"],
+            ),
+            (words, None, WrapMode::Word, &[words]),
+            (words, Some(word_width), WrapMode::None, &[words]),
+            // Trailing whitespace does not cause a break
+            (
+                "lorem ipsum   ",
+                Some(word_width),
+                WrapMode::Word,
+                &["lorem ipsum"],
+            ),
+            // A word which does not fit on a line of its own is broken between characters
+            (
+                "nnnnnnnnnn",
+                Some(char_width),
+                WrapMode::Word,
+                &["nnnn", "nnnn", "nn"],
+            ),
+            (
+                "nnnnnnnnnn",
+                Some(char_width),
+                WrapMode::Char,
+                &["nnnn", "nnnn", "nn"],
+            ),
+            (
+                "nnnnnnnnnn",
+                Some(char_width),
+                WrapMode::None,
+                &["nnnnnnnnnn"],
+            ),
+            // Explicit line breaks apply regardless of the wrap mode
+            (
+                "lorem\n\nipsum",
+                None,
+                WrapMode::None,
+                &["lorem", "", "ipsum"],
+            ),
+        ];
+
+        for (text, max_width, wrap, expected) in cases {
+            let layout = TextLayout {
+                wrap,
+                ..Default::default()
+            };
+
+            let shaped = shape_text(&fonts, text, 16.0, max_width, &layout);
+            assert_eq!(
+                line_texts(text, &shaped),
+                expected,
+                "{text:?} wrapped with {wrap:?} at {max_width:?}"
+            );
+
+            if wrap != WrapMode::None {
+                let max_width = max_width.unwrap_or(f32::INFINITY);
+                assert!(shaped.lines.iter().all(|v| v.width <= max_width));
+            }
+        }
+    }
+
+    #[test]
+    fn ellipsis() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets)];
+        let face = fonts[0].face().unwrap();
+        let ellipsis = face.glyph_index('\u{2026}').unwrap().0;
+
+        let width = |text| {
+            shape_text(&fonts, text, 16.0, None, &TextLayout::default())
+                .size
+                .x
+        };
+
+        let text = "lorem ipsum dolor sit amet consectetur";
+        let max_width = width("lorem ipsum");
+
+        let cases = [
+            (WrapMode::None, None),
+            (WrapMode::Word, Some(1)),
+            (WrapMode::Char, Some(2)),
+        ];
+
+        for (wrap, max_lines) in cases {
+            let layout = TextLayout {
+                wrap,
+                overflow: TextOverflow::Ellipsis,
+                max_lines,
+                ..Default::default()
+            };
+
+            let shaped = shape_text(&fonts, text, 16.0, Some(max_width), &layout);
+            let last = shaped.lines.last().unwrap();
+
+            assert_eq!(shaped.lines.len(), max_lines.unwrap_or(1), "{wrap:?}");
+            assert!(shaped.size.x <= max_width, "{wrap:?}");
+            assert_eq!(
+                shaped.glyphs[last.glyphs.end - 1].glyph,
+                ellipsis,
+                "{wrap:?}"
+            );
+        }
+
+        // Text which fits is left as is
+        let layout = TextLayout {
+            overflow: TextOverflow::Ellipsis,
+            ..Default::default()
+        };
+
+        let shaped = shape_text(&fonts, "lorem", 16.0, Some(max_width), &layout);
+        assert!(shaped.glyphs.iter().all(|v| v.glyph != ellipsis));
+
+        // Clipping only limits the number of lines
+        let layout = TextLayout {
+            wrap: WrapMode::None,
+            overflow: TextOverflow::Clip,
+            ..Default::default()
+        };
+
+        let shaped = shape_text(&fonts, text, 16.0, Some(max_width), &layout);
+        assert_eq!(shaped.size.x, width(text));
+    }
+
+    #[test]
+    fn alignment() {
+        let assets = AssetCache::new();
+        let fonts = [inter(&assets)];
+        let width = |text| {
+            shape_text(&fonts, text, 16.0, None, &TextLayout::default())
+                .size
+                .x
+        };
+
+        let text = "lorem\nip";
+        let lorem = width("lorem");
+        let ip = width("ip");
+
+        let cases = [
+            (TextAlign::Left, Some(100.0), [0.0, 0.0]),
+            (
+                TextAlign::Center,
+                Some(100.0),
+                [(100.0 - lorem) / 2.0, (100.0 - ip) / 2.0],
+            ),
+            (TextAlign::Right, Some(100.0), [100.0 - lorem, 100.0 - ip]),
+            // Without a max width lines are aligned to the widest line
+            (TextAlign::Center, None, [0.0, (lorem - ip) / 2.0]),
+            (TextAlign::Right, None, [0.0, lorem - ip]),
+        ];
+
+        for (align, max_width, offsets) in cases {
+            let layout = TextLayout {
+                align,
+                ..Default::default()
+            };
+
+            let shaped = shape_text(&fonts, text, 16.0, max_width, &layout);
+            for (line, offset) in shaped.lines.iter().zip(offsets) {
+                let x = shaped.glyphs[line.glyphs.start].pos.x;
+                assert!(
+                    (x - offset).abs() < 1e-3,
+                    "{align:?} at {max_width:?}: {x} != {offset}"
+                );
+            }
+        }
+
+        let cases = [
+            (VerticalAlign::Top, 0.0),
+            (VerticalAlign::Center, 0.5),
+            (VerticalAlign::Bottom, 1.0),
+        ];
+
+        for (align, factor) in cases {
+            let mut shaped = shape_text(&fonts, text, 16.0, None, &TextLayout::default());
+            let height = shaped.size.y;
+            let baseline = shaped.lines[0].baseline;
+
+            shaped.align_vertically(100.0, align);
+
+            let offset = (100.0 - height) * factor;
+            assert_eq!(shaped.lines[0].top, offset, "{align:?}");
+            assert_eq!(shaped.lines[0].baseline, baseline + offset, "{align:?}");
+            assert_eq!(shaped.glyphs[0].pos.y, baseline + offset, "{align:?}");
+        }
+    }
+}
@@ -1,8 +1,22 @@
-use flax::{entity_ids, BoxedSystem, CommandBuffer, FetchExt, Query, QueryBorrow, System};
+use flax::{
+    entity_ids,
+    fetch::{Modified, TransformFetch},
+    BoxedSystem, CommandBuffer, Component, EntityIds, Fetch, FetchExt, Opt, OptOr, Query,
+    QueryBorrow, System,
+};
 
-use crate::assets::AssetCache;
+use crate::{
+    assets::{AssetCache, Handle},
+    components::{filled_rect, rich_text},
+    font::{FontFamilyDesc, FontStyle, FontWeight},
+    shapes::FilledRect,
+    text::RichText,
+};
 
-use super::components::{self, font_from_file};
+use super::{
+    components::{self, font_family, font_from_file, font_style, font_weight},
+    font::Font,
+};
 
 pub fn load_fonts_system(assets: AssetCache) -> BoxedSystem {
     System::builder()
@@ -11,9 +25,198 @@ pub fn load_fonts_system(assets: AssetCache) -> BoxedSystem {
         .build(
             move |cmd: &mut CommandBuffer, mut query: QueryBorrow<_, _>| {
                 for (id, key) in &mut query {
-                    let font = assets.load(key);
-                    tracing::info!(?id, "Set font {key:?}");
-                    cmd.set(id, components::font(), font);
+                    match assets.try_load(key) {
+                        Ok(font) => {
+                            tracing::info!(?id, "Set font {key:?}");
+                            cmd.set(id, components::font(), font);
+                        }
+                        Err(err) => tracing::error!(?id, "Failed to load font {key:?}: {err:?}"),
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+struct FontFamilyQuery {
+    #[fetch(ignore)]
+    id: EntityIds,
+    family: Component<FontFamilyDesc>,
+    weight: OptOr<Component<FontWeight>, FontWeight>,
+    style: OptOr<Component<FontStyle>, FontStyle>,
+}
+
+impl FontFamilyQuery {
+    fn new() -> Self {
+        Self {
+            id: entity_ids(),
+            family: font_family(),
+            weight: font_weight().opt_or(FontWeight::default()),
+            style: font_style().opt_or(FontStyle::default()),
+        }
+    }
+}
+
+/// Selects the font and fallback chain for entities using a font family
+pub fn load_font_families_system(assets: AssetCache) -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new(FontFamilyQuery::new().transform_fetch(Modified)))
+        .build(
+            move |cmd: &mut CommandBuffer, mut query: QueryBorrow<_, _>| {
+                for item in &mut query {
+                    let family = assets.load(item.family);
+
+                    let mut chain = family.chain(*item.weight, *item.style).into_iter();
+                    let Some(font) = chain.next() else {
+                        tracing::error!(?item.id, "Font family {:?} has no faces", family.name);
+                        continue;
+                    };
+
+                    tracing::info!(?item.id, "Set font family {:?}", family.name);
+                    cmd.set(item.id, components::font(), font);
+                    cmd.set(item.id, components::font_fallback(), chain.collect());
+                }
+            },
+        )
+        .boxed()
+}
+
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+struct RichTextFontsQuery {
+    #[fetch(ignore)]
+    id: EntityIds,
+    rich_text: Component<RichText>,
+    family: Opt<Component<FontFamilyDesc>>,
+    weight: OptOr<Component<FontWeight>, FontWeight>,
+    style: OptOr<Component<FontStyle>, FontStyle>,
+}
+
+impl RichTextFontsQuery {
+    fn new() -> Self {
+        Self {
+            id: entity_ids(),
+            rich_text: rich_text(),
+            family: font_family().opt(),
+            weight: font_weight().opt_or(FontWeight::default()),
+            style: font_style().opt_or(FontStyle::default()),
+        }
+    }
+}
+
+/// Selects the font chain of each rich text span which overrides the family, weight or style
+pub fn load_rich_text_fonts_system(assets: AssetCache) -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new(
+            RichTextFontsQuery::new().transform_fetch(Modified),
+        ))
+        .build(
+            move |cmd: &mut CommandBuffer, mut query: QueryBorrow<_, _>| {
+                for item in &mut query {
+                    let fonts = item
+                        .rich_text
+                        .spans
+                        .iter()
+                        .map(|span| {
+                            // Spans without any overrides use the font of the entity
+                            if span.font.is_none() && span.weight.is_none() && span.style.is_none()
+                            {
+                                return Vec::new();
+                            }
+
+                            let Some(family) = span.font.as_ref().or(item.family) else {
+                                return Vec::new();
+                            };
+
+                            assets.load(family).chain(
+                                span.weight.unwrap_or(*item.weight),
+                                span.style.unwrap_or(*item.style),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+
+                    cmd.set(item.id, components::rich_text_fonts(), fonts);
+                }
+            },
+        )
+        .boxed()
+}
+
+/// Replaces the stale fonts and images of entities with the reloaded assets.
+///
+/// Assets become stale when [`AssetCache::check_modified`] finds that their files were modified.
+pub fn reload_assets_system(assets: AssetCache) -> BoxedSystem {
+    let mut generation = assets.stale_generation();
+
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new((
+            entity_ids(),
+            components::font().opt(),
+            components::font_fallback().opt(),
+            components::rich_text_fonts().opt(),
+            filled_rect().opt(),
+        )))
+        .build(
+            move |cmd: &mut CommandBuffer, mut query: QueryBorrow<_, _>| {
+                let current = assets.stale_generation();
+                if current == generation {
+                    return;
+                }
+
+                generation = current;
+                tracing::info!("Reloading stale assets");
+
+                let reload_fonts = |fonts: &[Handle<Font>]| -> Option<Vec<Handle<Font>>> {
+                    if !fonts.iter().any(|v| assets.is_stale(v)) {
+                        return None;
+                    }
+
+                    Some(
+                        fonts
+                            .iter()
+                            .map(|v| assets.reload(v).unwrap_or_else(|| v.clone()))
+                            .collect(),
+                    )
+                };
+
+                for (id, font, fallback, rich_text_fonts, rect) in &mut query {
+                    if let Some(font) = font.and_then(|v| assets.reload(v)) {
+                        cmd.set(id, components::font(), font);
+                    }
+
+                    if let Some(fallback) = fallback.and_then(|v| reload_fonts(v)) {
+                        cmd.set(id, components::font_fallback(), fallback);
+                    }
+
+                    if let Some(fonts) = rich_text_fonts {
+                        if fonts.iter().any(|v| v.iter().any(|v| assets.is_stale(v))) {
+                            let fonts = fonts
+                                .iter()
+                                .map(|v| reload_fonts(v).unwrap_or_else(|| v.clone()))
+                                .collect();
+
+                            cmd.set(id, components::rich_text_fonts(), fonts);
+                        }
+                    }
+
+                    if let Some(rect) = rect {
+                        if let Some(image) = rect.fill_image.as_ref().and_then(|v| assets.reload(v))
+                        {
+                            cmd.set(
+                                id,
+                                filled_rect(),
+                                FilledRect {
+                                    fill_image: Some(image),
+                                    ..rect.clone()
+                                },
+                            );
+                        }
+                    }
                 }
             },
         )
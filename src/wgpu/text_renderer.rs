@@ -1,21 +1,22 @@
-use std::collections::{btree_map, BTreeMap};
-
 use flax::{
     entity_ids,
     fetch::{Modified, TransformFetch},
     filter::{All, With},
-    CommandBuffer, Component, Debuggable, EntityIds, Fetch, FetchExt, Mutable, Opt, OptOr, Query,
+    CommandBuffer, Component, Debuggable, Entity, EntityIds, EntityRef, Fetch, FetchExt, Mutable,
+    Opt, Query,
 };
-use fontdue::layout::{Layout, TextStyle};
-use glam::{vec2, vec3, Mat4, Quat, Vec2, Vec3};
-use itertools::Itertools;
+use glam::{vec2, vec3, Mat4, Quat, Vec2, Vec3, Vec4};
+use palette::Srgba;
 use wgpu::{BindGroup, BindGroupLayout, Sampler, SamplerDescriptor, ShaderStages, TextureFormat};
 
 use crate::{
-    assets::{map::HandleMap, AssetCache, Handle},
-    components::{font_size, rect, screen_position, text, Rect},
+    assets::{AssetCache, Handle},
+    components::{
+        font_size, line_height, max_lines, rect, rich_text, screen_position, selection_color, text,
+        text_align, text_overflow, text_selection, text_wrap, vertical_align, Rect,
+    },
+    text::{RichText, TextAlign, TextLayout, TextOverflow, TextSelection, VerticalAlign, WrapMode},
     wgpu::{
-        font::FontAtlas,
         graphics::{allocator::Allocation, BindGroupBuilder},
         shape_renderer::DrawCommand,
     },
@@ -23,11 +24,16 @@ use crate::{
 };
 
 use super::{
-    components::{draw_cmd, font, mesh_handle, model_matrix},
+    components::{
+        draw_cmd, font, font_fallback, mesh_handle, model_matrix, rich_text_fonts, shaped_text,
+    },
     font::Font,
+    glyph_cache::GlyphCache,
     graphics::{shader::ShaderDesc, BindGroupLayoutBuilder, Shader, Vertex, VertexDesc},
     mesh_buffer::MeshHandle,
     renderer::RendererContext,
+    shape_renderer::srgba_to_vec4,
+    shaping::{entity_spans, shape_spans, ShapeSpan},
     Gpu,
 };
 
@@ -35,6 +41,8 @@ flax::component! {
     text_mesh: Allocation => [ Debuggable ],
 }
 
+const DEFAULT_SELECTION_COLOR: Srgba = Srgba::new(0.3, 0.5, 0.9, 0.5);
+
 #[derive(Fetch)]
 struct ObjectQuery {
     rect: Component<Rect>,
@@ -53,11 +61,14 @@ impl ObjectQuery {
 }
 
 struct MeshGenerator {
-    fonts: HandleMap<Font, RenderFont>,
+    glyph_cache: GlyphCache,
     shader: Handle<Shader>,
     text_layout: BindGroupLayout,
 
     sampler: Handle<Sampler>,
+    /// Bind group for the current glyph cache texture
+    bind_group: Handle<BindGroup>,
+    bind_group_generation: u32,
 }
 
 impl MeshGenerator {
@@ -95,11 +106,195 @@ impl MeshGenerator {
                 ..Default::default()
             }));
 
+        let glyph_cache = GlyphCache::new(&ctx.gpu, 512, 4096);
+
+        let bind_group = frame.assets.insert(
+            BindGroupBuilder::new("TextRenderer::bind_group")
+                .bind_sampler(&sampler)
+                .bind_texture(&glyph_cache.texture().view(&Default::default()))
+                .build(&ctx.gpu, &text_layout),
+        );
+
         Self {
-            fonts: HandleMap::new(),
+            bind_group_generation: glyph_cache.generation(),
+            glyph_cache,
             shader,
             text_layout,
             sampler,
+            bind_group,
+        }
+    }
+
+    /// Returns the bind group for the glyph cache, recreating it if the texture has changed
+    fn bind_group(&mut self, gpu: &Gpu, assets: &AssetCache) -> Handle<BindGroup> {
+        if self.bind_group_generation != self.glyph_cache.generation() {
+            self.bind_group = assets.insert(
+                BindGroupBuilder::new("TextRenderer::bind_group")
+                    .bind_sampler(&self.sampler)
+                    .bind_texture(&self.glyph_cache.texture().view(&Default::default()))
+                    .build(gpu, &self.text_layout),
+            );
+
+            self.bind_group_generation = self.glyph_cache.generation();
+        }
+
+        self.bind_group.clone()
+    }
+
+    /// Shapes the text of the entity and writes its glyphs to the mesh
+    fn update_mesh(
+        &mut self,
+        ctx: &mut RendererContext,
+        frame: &Frame,
+        cmd: &mut CommandBuffer,
+        id: Entity,
+        mesh: Option<&mut MeshHandle>,
+    ) {
+        let Some(item) = TextItem::from_entity(&frame.world.entity(id).unwrap()) else {
+            return;
+        };
+
+        tracing::debug!(%id, "updating mesh for {:?}", item.text);
+
+        let size = item.rect.size();
+        let mut shaped = shape_spans(&item.text, &item.spans, Some(size.x), &item.layout);
+
+        shaped.align_vertically(size.y, item.layout.vertical_align);
+
+        let bounds = (item.layout.overflow != TextOverflow::Visible).then_some(size);
+
+        let mut vertices = Vec::with_capacity(shaped.glyphs.len() * 4);
+        let mut indices = Vec::with_capacity(shaped.glyphs.len() * 6);
+
+        let mut push_quad = |min: Vec2, max: Vec2, uv_min: Vec2, uv_max: Vec2, color: Vec4| {
+            let Some((min, max, uv_min, uv_max)) = clip_quad(min, max, uv_min, uv_max, bounds)
+            else {
+                return;
+            };
+
+            let base = vertices.len() as u32;
+            vertices.extend([
+                // Bottom left
+                Vertex::new(vec3(min.x, max.y, 0.0), vec2(uv_min.x, uv_max.y)).with_color(color),
+                Vertex::new(vec3(max.x, max.y, 0.0), vec2(uv_max.x, uv_max.y)).with_color(color),
+                Vertex::new(vec3(max.x, min.y, 0.0), vec2(uv_max.x, uv_min.y)).with_color(color),
+                Vertex::new(vec3(min.x, min.y, 0.0), vec2(uv_min.x, uv_min.y)).with_color(color),
+            ]);
+
+            indices.extend([base, base + 1, base + 2, base + 2, base + 3, base]);
+        };
+
+        let solid = self.glyph_cache.solid();
+
+        // The selection is drawn first to appear behind the glyphs
+        if let Some(selection) = item.selection.filter(|v| !v.is_empty()) {
+            let color = srgba_to_vec4(item.selection_color);
+            for rect in shaped.selection_rects(selection.range()) {
+                push_quad(rect.min, rect.max, solid, solid, color);
+            }
+        }
+
+        for glyph in &shaped.glyphs {
+            let span = &item.spans[glyph.span];
+            let Some(location) =
+                self.glyph_cache
+                    .get(&ctx.gpu, &span.fonts[glyph.font], glyph.glyph, span.px)
+            else {
+                continue;
+            };
+
+            if location.min == location.max {
+                continue;
+            }
+
+            // Texture coordinates are in pixels so that they remain valid when the atlas grows
+            let uv_min = location.min.as_vec2();
+            let uv_max = location.max.as_vec2();
+
+            let min = glyph.pos + location.offset;
+            let max = min + (uv_max - uv_min);
+
+            push_quad(min, max, uv_min, uv_max, srgba_to_vec4(span.color));
+        }
+
+        for decoration in &shaped.decorations {
+            let color = srgba_to_vec4(item.spans[decoration.span].color);
+            push_quad(decoration.min, decoration.max, solid, solid, color);
+        }
+
+        let mesh = match mesh {
+            Some(mesh) => {
+                ctx.mesh_buffer
+                    .reallocate(&ctx.gpu, mesh, vertices.len(), indices.len());
+
+                *mesh
+            }
+            None => {
+                let mesh = ctx
+                    .mesh_buffer
+                    .allocate(&ctx.gpu, vertices.len(), indices.len());
+                cmd.set(id, mesh_handle(), mesh);
+                mesh
+            }
+        };
+
+        ctx.mesh_buffer.write(&ctx.gpu, &mesh, &vertices, &indices);
+
+        cmd.set(
+            id,
+            draw_cmd(),
+            DrawCommand {
+                mesh,
+                bind_group: self.bind_group(&ctx.gpu, &frame.assets),
+                shader: self.shader.clone(),
+                index_count: indices.len() as u32,
+                vertex_offset: mesh.vb().start() as i32,
+            },
+        );
+
+        // Kept for hit testing the text
+        cmd.set(id, shaped_text(), shaped);
+    }
+}
+
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+/// Style shared by text and rich text entities.
+///
+/// Only used for rebuilding the mesh when the style changes, the values themselves are read from
+/// the entity the same way as when measuring the text
+pub struct TextStyleQuery {
+    rect: Component<Rect>,
+    font_fallback: Opt<Component<Vec<Handle<Font>>>>,
+    font_size: Opt<Component<f32>>,
+
+    text_align: Opt<Component<TextAlign>>,
+    vertical_align: Opt<Component<VerticalAlign>>,
+    line_height: Opt<Component<f32>>,
+    text_wrap: Opt<Component<WrapMode>>,
+    text_overflow: Opt<Component<TextOverflow>>,
+    max_lines: Opt<Component<usize>>,
+
+    text_selection: Opt<Component<TextSelection>>,
+    selection_color: Opt<Component<Srgba>>,
+}
+
+impl TextStyleQuery {
+    fn new() -> Self {
+        Self {
+            rect: rect(),
+            font_fallback: font_fallback().opt(),
+            font_size: font_size().opt(),
+
+            text_align: text_align().opt(),
+            vertical_align: vertical_align().opt(),
+            line_height: line_height().opt(),
+            text_wrap: text_wrap().opt(),
+            text_overflow: text_overflow().opt(),
+            max_lines: max_lines().opt(),
+
+            text_selection: text_selection().opt(),
+            selection_color: selection_color().opt(),
         }
     }
 }
@@ -113,10 +308,9 @@ pub struct TextMeshQuery {
     #[fetch(ignore)]
     mesh: Opt<Mutable<MeshHandle>>,
 
-    rect: Component<Rect>,
     text: Component<String>,
     font: Component<Handle<Font>>,
-    font_size: OptOr<Component<f32>, f32>,
+    style: TextStyleQuery,
 }
 
 impl TextMeshQuery {
@@ -124,58 +318,66 @@ impl TextMeshQuery {
         Self {
             id: entity_ids(),
             mesh: mesh_handle().as_mut().opt(),
-            rect: rect(),
             text: text(),
             font: font(),
-            font_size: font_size().opt_or(16.0),
+            style: TextStyleQuery::new(),
         }
     }
 }
 
-pub struct RasterizedFont {
-    /// Stored to retrieve *where* the character is located
-    atlas: FontAtlas,
-    bind_group: Handle<BindGroup>,
-}
+#[derive(Fetch, Debug, Clone)]
+#[fetch(transforms = [Modified])]
+/// Query rich text entities whose span fonts have been resolved
+pub struct RichTextMeshQuery {
+    #[fetch(ignore)]
+    id: EntityIds,
+    #[fetch(ignore)]
+    mesh: Opt<Mutable<MeshHandle>>,
 
-pub struct RenderFont {
-    font: Handle<Font>,
-    sampler: Handle<Sampler>,
-    rasterized: BTreeMap<u32, RasterizedFont>,
+    rich_text: Component<RichText>,
+    rich_text_fonts: Component<Vec<Vec<Handle<Font>>>>,
+    font: Opt<Component<Handle<Font>>>,
+    style: TextStyleQuery,
 }
 
-impl RenderFont {
-    fn get(
-        &mut self,
-        gpu: &Gpu,
-        assets: &AssetCache,
-        px: u32,
-        text: &str,
-        text_layout: &BindGroupLayout,
-    ) -> &mut RasterizedFont {
-        let rasterize = || {
-            let atlas = FontAtlas::new(assets, gpu, &self.font, px as f32, text.chars()).unwrap();
-
-            let bind_group = assets.insert(
-                BindGroupBuilder::new("TextRenderer::bind_group")
-                    .bind_sampler(&self.sampler)
-                    .bind_texture(&atlas.texture.view(&Default::default()))
-                    .build(gpu, text_layout),
-            );
+impl RichTextMeshQuery {
+    fn new() -> Self {
+        Self {
+            id: entity_ids(),
+            mesh: mesh_handle().as_mut().opt(),
+            rich_text: rich_text(),
+            rich_text_fonts: rich_text_fonts(),
+            font: font().opt(),
+            style: TextStyleQuery::new(),
+        }
+    }
+}
 
-            RasterizedFont { atlas, bind_group }
-        };
+/// The text of an entity and its style, resolved for generating a mesh
+struct TextItem {
+    rect: Rect,
+    text: String,
+    spans: Vec<ShapeSpan>,
+    layout: TextLayout,
+    selection: Option<TextSelection>,
+    selection_color: Srgba,
+}
 
-        match self.rasterized.entry(px) {
-            btree_map::Entry::Vacant(slot) => slot.insert(rasterize()),
-            btree_map::Entry::Occupied(slot) => {
-                let rasterized = slot.into_mut();
-                if !text.chars().all(|c| rasterized.atlas.contains_char(c)) {
-                    *rasterized = rasterize();
-                }
-                rasterized
-            }
-        }
+impl TextItem {
+    /// Returns `None` if the fonts of the entity have not been loaded
+    fn from_entity(entity: &EntityRef) -> Option<Self> {
+        let (text, spans) = entity_spans(entity)?;
+
+        Some(Self {
+            rect: entity.get_copy(rect()).ok()?,
+            text,
+            spans,
+            layout: TextLayout::from_entity(entity),
+            selection: entity.get_copy(text_selection()).ok(),
+            selection_color: entity
+                .get_copy(selection_color())
+                .unwrap_or(DEFAULT_SELECTION_COLOR),
+        })
     }
 }
 
@@ -183,7 +385,12 @@ pub struct TextRenderer {
     mesh_generator: MeshGenerator,
 
     object_query: Query<ObjectQuery, (All, With)>,
+    rich_object_query: Query<ObjectQuery, (All, With)>,
     mesh_query: Query<<TextMeshQuery as TransformFetch<Modified>>::Output, All>,
+    rich_mesh_query: Query<<RichTextMeshQuery as TransformFetch<Modified>>::Output, All>,
+    /// Used to rebuild all meshes when the glyph cache is invalidated
+    all_mesh_query: Query<TextMeshQuery, All>,
+    all_rich_mesh_query: Query<RichTextMeshQuery, All>,
 }
 
 impl TextRenderer {
@@ -196,148 +403,109 @@ impl TextRenderer {
         let mesh_generator = MeshGenerator::new(ctx, frame, color_format, object_layout);
         Self {
             object_query: Query::new(ObjectQuery::new()).with(text()),
+            rich_object_query: Query::new(ObjectQuery::new()).with(rich_text()),
             mesh_generator,
             mesh_query: Query::new(TextMeshQuery::new().transform_fetch(Modified)),
+            rich_mesh_query: Query::new(RichTextMeshQuery::new().transform_fetch(Modified)),
+            all_mesh_query: Query::new(TextMeshQuery::new()),
+            all_rich_mesh_query: Query::new(RichTextMeshQuery::new()),
         }
     }
 
     pub fn update_meshes(&mut self, ctx: &mut RendererContext, frame: &mut Frame) {
         let mut cmd = CommandBuffer::new();
 
-        (self.mesh_query.borrow(&frame.world)).for_each(|item| {
-            tracing::debug!(%item.id, "updating mesh for {:?}", item.text);
-
-            let render_font = self
-                .mesh_generator
-                .fonts
-                .entry(item.font)
-                .or_insert_with_key(|key| RenderFont {
-                    rasterized: BTreeMap::new(),
-                    font: key.clone(),
-                    sampler: self.mesh_generator.sampler.clone(),
-                });
-
-            let rasterized = render_font.get(
-                &ctx.gpu,
-                &frame.assets,
-                *item.font_size as _,
-                item.text,
-                &self.mesh_generator.text_layout,
-            );
+        self.mesh_generator.glyph_cache.tick();
+        let generation = self.mesh_generator.glyph_cache.generation();
 
-            let mut layout = Layout::<()>::new(fontdue::layout::CoordinateSystem::PositiveYDown);
-
-            let size = item.rect.size();
-            layout.reset(&fontdue::layout::LayoutSettings {
-                x: item.rect.min.x,
-                y: item.rect.min.x,
-                max_width: Some(size.x),
-                max_height: Some(size.y),
-                horizontal_align: fontdue::layout::HorizontalAlign::Left,
-                vertical_align: fontdue::layout::VerticalAlign::Top,
-                line_height: 1.0,
-                wrap_style: fontdue::layout::WrapStyle::Word,
-                wrap_hard_breaks: true,
-            });
-
-            layout.append(
-                &[&item.font.font],
-                &TextStyle {
-                    text: item.text,
-                    px: *item.font_size,
-                    font_index: 0,
-                    user_data: (),
-                },
-            );
+        self.update_modified(ctx, frame, &mut cmd);
 
-            let glyph_count = layout.glyphs().len();
-
-            let vertices = layout
-                .glyphs()
-                .iter()
-                .flat_map(|glyph| {
-                    let atlas_glyph = rasterized.atlas.glyphs.get(&glyph.key.glyph_index).unwrap();
-
-                    let glyph_width = glyph.width as f32;
-                    let glyph_height = glyph.height as f32;
-
-                    let atlas_size = rasterized.atlas.size();
-                    let atlas_size = vec2(atlas_size.width as f32, atlas_size.height as f32);
-
-                    let uv_min = atlas_glyph.min.as_vec2() / atlas_size;
-                    let uv_max = atlas_glyph.max.as_vec2() / atlas_size;
-
-                    [
-                        // Bottom left
-                        Vertex::new(
-                            vec3(glyph.x, glyph.y + glyph_height, 0.0),
-                            vec2(uv_min.x, uv_max.y),
-                        ),
-                        Vertex::new(
-                            vec3(glyph.x + glyph_width, glyph.y + glyph_height, 0.0),
-                            vec2(uv_max.x, uv_max.y),
-                        ),
-                        Vertex::new(
-                            vec3(glyph.x + glyph_width, glyph.y, 0.0),
-                            vec2(uv_max.x, uv_min.y),
-                        ),
-                        Vertex::new(vec3(glyph.x, glyph.y, 0.0), vec2(uv_min.x, uv_min.y)),
-                    ]
-                })
-                .collect_vec();
-
-            let indices = (0..)
-                .step_by(4)
-                .take(glyph_count)
-                .flat_map(|i| [i, 1 + i, 2 + i, 2 + i, 3 + i, i])
-                .collect_vec();
-
-            let mesh = match item.mesh {
-                Some(mesh) => {
-                    ctx.mesh_buffer
-                        .reallocate(&ctx.gpu, mesh, vertices.len(), indices.len());
-
-                    *mesh
-                }
-                None => {
-                    let mesh = ctx
-                        .mesh_buffer
-                        .allocate(&ctx.gpu, vertices.len(), indices.len());
-                    cmd.set(item.id, mesh_handle(), mesh);
-                    mesh
-                }
-            };
+        // Glyphs were evicted or the atlas was replaced, which invalidates the existing meshes
+        if self.mesh_generator.glyph_cache.generation() != generation {
+            let generation = self.mesh_generator.glyph_cache.generation();
 
-            ctx.mesh_buffer.write(&ctx.gpu, &mesh, &vertices, &indices);
-
-            cmd.set(
-                item.id,
-                draw_cmd(),
-                DrawCommand {
-                    mesh,
-                    bind_group: rasterized.bind_group.clone(),
-                    shader: self.mesh_generator.shader.clone(),
-                    index_count: indices.len() as u32,
-                    vertex_offset: mesh.vb().start() as i32,
-                },
-            );
-        });
+            self.update_all(ctx, frame, &mut cmd);
+
+            if self.mesh_generator.glyph_cache.generation() != generation {
+                tracing::warn!(
+                    "Glyph cache was invalidated while rebuilding text, the atlas may be too small"
+                );
+            }
+        }
 
         cmd.apply(&mut frame.world).unwrap();
     }
 
+    fn update_modified(
+        &mut self,
+        ctx: &mut RendererContext,
+        frame: &Frame,
+        cmd: &mut CommandBuffer,
+    ) {
+        for item in &mut self.mesh_query.borrow(&frame.world) {
+            self.mesh_generator
+                .update_mesh(ctx, frame, cmd, item.id, item.mesh);
+        }
+
+        for item in &mut self.rich_mesh_query.borrow(&frame.world) {
+            self.mesh_generator
+                .update_mesh(ctx, frame, cmd, item.id, item.mesh);
+        }
+    }
+
+    fn update_all(&mut self, ctx: &mut RendererContext, frame: &Frame, cmd: &mut CommandBuffer) {
+        for item in &mut self.all_mesh_query.borrow(&frame.world) {
+            self.mesh_generator
+                .update_mesh(ctx, frame, cmd, item.id, item.mesh);
+        }
+
+        for item in &mut self.all_rich_mesh_query.borrow(&frame.world) {
+            self.mesh_generator
+                .update_mesh(ctx, frame, cmd, item.id, item.mesh);
+        }
+    }
+
     pub fn update(&mut self, _: &Gpu, frame: &Frame) {
-        self.object_query
-            .borrow(&frame.world)
-            .iter()
-            .for_each(|item| {
-                let pos = *item.pos + item.rect.pos();
-                // tracing::info!("Updating text rect: {rect:?} at {pos}");
-                *item.model_matrix = Mat4::from_scale_rotation_translation(
-                    Vec3::ONE,
-                    Quat::IDENTITY,
-                    pos.extend(0.1),
-                );
-            })
+        for item in &mut self.object_query.borrow(&frame.world) {
+            *item.model_matrix = model_matrix_for(item.rect, *item.pos);
+        }
+
+        for item in &mut self.rich_object_query.borrow(&frame.world) {
+            *item.model_matrix = model_matrix_for(item.rect, *item.pos);
+        }
     }
 }
+
+fn model_matrix_for(rect: &Rect, screen_pos: Vec2) -> Mat4 {
+    let pos = screen_pos + rect.pos();
+    Mat4::from_scale_rotation_translation(Vec3::ONE, Quat::IDENTITY, pos.extend(0.1))
+}
+
+/// Clips a quad and its texture coordinates to `0..bounds`
+fn clip_quad(
+    min: Vec2,
+    max: Vec2,
+    uv_min: Vec2,
+    uv_max: Vec2,
+    bounds: Option<Vec2>,
+) -> Option<(Vec2, Vec2, Vec2, Vec2)> {
+    let Some(bounds) = bounds else {
+        return Some((min, max, uv_min, uv_max));
+    };
+
+    let clipped_min = min.max(Vec2::ZERO);
+    let clipped_max = max.min(bounds);
+
+    if clipped_min.cmpge(clipped_max).any() {
+        return None;
+    }
+
+    let uv_scale = (uv_max - uv_min) / (max - min);
+
+    Some((
+        clipped_min,
+        clipped_max,
+        uv_min + (clipped_min - min) * uv_scale,
+        uv_min + (clipped_max - min) * uv_scale,
+    ))
+}
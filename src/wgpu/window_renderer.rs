@@ -1,7 +1,7 @@
 use anyhow::Context;
 use glam::Mat4;
 use wgpu::{Operations, RenderPassDescriptor, SurfaceError};
-use winit::dpi::PhysicalSize;
+use winit::{dpi::PhysicalSize, window::Window};
 
 use crate::Frame;
 
@@ -32,6 +32,10 @@ impl WindowRenderer {
         }
     }
 
+    pub fn window(&self) -> &Window {
+        self.surface.window()
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         let w = new_size.width as f32;
         let h = new_size.height as f32;